@@ -1,3 +1,6 @@
+// Warn users once they are down to this many unused recovery codes
+const LOW_RECOVERY_CODES_THRESHOLD = 3;
+
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
@@ -30,6 +33,18 @@ signupLoginLink.addEventListener("click", (e) => {
     signupSection.style.display = "none";
 });
 
+// Logins started by another app through /authorize continue there afterwards.
+// Only paths of the authorize endpoint are followed, so the parameter can't be
+// used to send users to other sites.
+function continueAuthorization() {
+    const returnTo = new URLSearchParams(window.location.search).get("return_to");
+    if (returnTo !== null && returnTo.startsWith("/authorize?")) {
+        window.location.assign(returnTo);
+        return true;
+    }
+    return false;
+}
+
 // -----------------------------------------------------
 
 const loginForm = document.getElementById("login-form");
@@ -66,7 +81,9 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            if (!continueAuthorization()) {
+                alert("You have successfully logged in.");
+            }
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -104,7 +121,16 @@ signupButton.addEventListener("click", (e) => {
             signupForm.password.value = "";
             signupForm.twoFA.checked = false;
             signupErrAlter.style.display = "none";
-            alert("You have successfully created a user.");
+            response.json().then(data => {
+                if (data.recoveryCodes !== undefined) {
+                    alert("You have successfully created a user.\n\n"
+                        + "Store these recovery codes somewhere safe. Each one can be used once "
+                        + "in place of a verification code, and they will not be shown again:\n\n"
+                        + data.recoveryCodes.join("\n"));
+                } else {
+                    alert("You have successfully created a user.");
+                }
+            });
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -126,6 +152,17 @@ const TwoFAForm = document.getElementById("2fa-form");
 const TwoFAButton = document.getElementById("2fa-form-submit");
 const TwoFAErrAlter = document.getElementById("2fa-err-alert");
 
+// Logins through an identity provider come back here when the account has 2FA
+const pendingLogin = new URLSearchParams(window.location.search);
+if (pendingLogin.has("login_attempt_id")) {
+    TwoFAForm.email.value = pendingLogin.get("email");
+    TwoFAForm.login_attempt_id.value = pendingLogin.get("login_attempt_id");
+
+    loginSection.style.display = "none";
+    twoFASection.style.display = "block";
+    signupSection.style.display = "none";
+}
+
 TwoFAButton.addEventListener("click", (e) => {
     e.preventDefault();
 
@@ -145,7 +182,18 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            if (continueAuthorization()) {
+                return;
+            }
+            response.json().then(data => {
+                let remaining = data.recoveryCodesRemaining;
+                if (remaining !== undefined && remaining <= LOW_RECOVERY_CODES_THRESHOLD) {
+                    alert(`You have successfully logged in.\n\nYou only have ${remaining} recovery code(s) left. `
+                        + "Generate a new set so you don't get locked out of your account.");
+                } else {
+                    alert("You have successfully logged in.");
+                }
+            });
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE users DROP COLUMN IF EXISTS verification_email_sent_at;
+ALTER TABLE users DROP COLUMN IF EXISTS email_verified;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS totp_secrets;
+
+ALTER TABLE users ADD COLUMN requires_2fa BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE users SET requires_2fa = (two_fa_method <> 'none');
+ALTER TABLE users DROP COLUMN two_fa_method;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+ALTER TABLE users ADD COLUMN two_fa_method TEXT NOT NULL DEFAULT 'none'
+    CHECK (two_fa_method IN ('none', 'email', 'totp'));
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+ALTER TABLE users DROP COLUMN requires_2fa;
+
+-- Secrets are stored encrypted. pending_secret holds a secret that has been
+-- enrolled but not yet confirmed with a valid code.
+CREATE TABLE IF NOT EXISTS totp_secrets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   secret TEXT,
+   pending_secret TEXT
+);
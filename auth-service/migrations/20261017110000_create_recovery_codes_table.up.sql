@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Only SHA-256 hashes of the codes are stored. used_at is set once a code
+-- has been redeemed.
+CREATE TABLE IF NOT EXISTS recovery_codes(
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   code_hash TEXT NOT NULL,
+   used_at TIMESTAMPTZ,
+   PRIMARY KEY (email, code_hash)
+);
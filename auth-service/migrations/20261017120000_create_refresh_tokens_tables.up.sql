@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- A family is started at login and holds every refresh token rotated from it.
+CREATE TABLE IF NOT EXISTS refresh_token_families(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS refresh_token_families_email_idx ON refresh_token_families(email);
+
+-- Only SHA-256 hashes of the tokens are stored. used_at is set once a token
+-- has been rotated.
+CREATE TABLE IF NOT EXISTS refresh_tokens(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   family_id TEXT NOT NULL REFERENCES refresh_token_families(id) ON DELETE CASCADE,
+   expires_at TIMESTAMPTZ NOT NULL,
+   used_at TIMESTAMPTZ
+);
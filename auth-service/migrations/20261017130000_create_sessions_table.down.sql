@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE refresh_token_families DROP COLUMN IF EXISTS session_id;
+DROP TABLE IF EXISTS sessions;
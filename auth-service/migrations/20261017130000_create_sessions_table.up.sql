@@ -0,0 +1,26 @@
+-- Add up migration script here
+-- One row per login session. Rows are deleted when a session is revoked.
+CREATE TABLE IF NOT EXISTS sessions(
+   id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   user_agent TEXT,
+   ip_address TEXT,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions(email);
+
+-- Each refresh token family belongs to a session and ends with it. Families
+-- started before sessions existed get a session of their own.
+ALTER TABLE refresh_token_families ADD COLUMN session_id TEXT REFERENCES sessions(id) ON DELETE CASCADE;
+
+INSERT INTO sessions (id, email, created_at, expires_at)
+SELECT id, email, created_at, NOW() + INTERVAL '30 days'
+FROM refresh_token_families
+WHERE revoked_at IS NULL;
+
+UPDATE refresh_token_families SET session_id = id WHERE revoked_at IS NULL;
+DELETE FROM refresh_token_families WHERE session_id IS NULL;
+
+ALTER TABLE refresh_token_families ALTER COLUMN session_id SET NOT NULL;
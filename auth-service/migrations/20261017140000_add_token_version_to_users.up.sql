@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Bumped to invalidate every JWT issued to the user so far
+ALTER TABLE users ADD COLUMN token_version BIGINT NOT NULL DEFAULT 0;
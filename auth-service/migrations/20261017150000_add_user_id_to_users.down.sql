@@ -0,0 +1,20 @@
+-- Add down migration script here
+ALTER TABLE sessions ADD COLUMN email TEXT;
+UPDATE sessions SET email = users.email FROM users WHERE users.id = sessions.user_id;
+ALTER TABLE sessions ALTER COLUMN email SET NOT NULL;
+ALTER TABLE sessions DROP COLUMN user_id;
+
+ALTER TABLE users DROP CONSTRAINT users_pkey;
+ALTER TABLE users DROP CONSTRAINT users_email_key CASCADE;
+ALTER TABLE users ADD PRIMARY KEY (email);
+ALTER TABLE users DROP COLUMN id;
+
+ALTER TABLE totp_secrets
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE recovery_codes
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE refresh_token_families
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE sessions
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions(email);
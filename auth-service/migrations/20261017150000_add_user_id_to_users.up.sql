@@ -0,0 +1,23 @@
+-- Add up migration script here
+-- Users get a stable ID as their primary key so their email can change. Tables
+-- keyed by email keep referencing it and follow changes through ON UPDATE CASCADE.
+ALTER TABLE users ADD COLUMN id UUID NOT NULL DEFAULT gen_random_uuid();
+
+-- Dropping the old primary key also drops the foreign keys referencing it
+ALTER TABLE users DROP CONSTRAINT users_pkey CASCADE;
+ALTER TABLE users ADD PRIMARY KEY (id);
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+
+ALTER TABLE totp_secrets
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE recovery_codes
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+ALTER TABLE refresh_token_families
+   ADD FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+-- Sessions belong to the user rather than to their email
+ALTER TABLE sessions ADD COLUMN user_id UUID REFERENCES users(id) ON DELETE CASCADE;
+UPDATE sessions SET user_id = users.id FROM users WHERE users.email = sessions.email;
+ALTER TABLE sessions ALTER COLUMN user_id SET NOT NULL;
+ALTER TABLE sessions DROP COLUMN email;
+CREATE INDEX IF NOT EXISTS sessions_user_id_idx ON sessions(user_id);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Set when the user asks for their account to be deleted. The row is purged once
+-- the grace period has passed, until then the account can be restored.
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMPTZ;
+CREATE INDEX users_deleted_at_idx ON users (deleted_at) WHERE deleted_at IS NOT NULL;
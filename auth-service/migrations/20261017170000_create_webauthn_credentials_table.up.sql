@@ -0,0 +1,12 @@
+-- Add up migration script here
+-- Passkeys registered by users. The ID is the base64url encoded credential ID
+-- and the public key an uncompressed P-256 point.
+CREATE TABLE IF NOT EXISTS webauthn_credentials(
+   id TEXT NOT NULL PRIMARY KEY,
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   public_key BYTEA NOT NULL,
+   sign_count BIGINT NOT NULL DEFAULT 0,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS webauthn_credentials_user_id_idx ON webauthn_credentials(user_id);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- Applications that log users in through the OAuth2 endpoints. Authorization
+-- codes are only sent to one of a client's redirect URIs, compared exactly.
+CREATE TABLE IF NOT EXISTS oauth_clients(
+   id TEXT NOT NULL PRIMARY KEY,
+   name TEXT NOT NULL,
+   redirect_uris TEXT[] NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
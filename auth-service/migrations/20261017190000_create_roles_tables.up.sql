@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- Roles and the permissions they grant. Users have the permissions of all the
+-- roles granted to them, which are embedded in their auth tokens at login.
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT NOT NULL PRIMARY KEY,
+   permissions TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON DELETE CASCADE,
+   granted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (user_id, role)
+);
+
+-- The first admin has to be granted the role in the database
+INSERT INTO roles (name, permissions) VALUES ('admin', ARRAY['roles:manage']) ON CONFLICT DO NOTHING;
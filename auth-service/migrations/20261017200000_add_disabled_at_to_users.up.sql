@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Set while an admin has disabled the account. Disabled users can't log in until
+-- the account is enabled again.
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMPTZ;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled_at TIMESTAMPTZ;
+UPDATE users SET disabled_at = COALESCE(status_changed_at, NOW()) WHERE status <> 'active';
+ALTER TABLE users DROP COLUMN IF EXISTS status_changed_at;
+ALTER TABLE users DROP COLUMN IF EXISTS status_reason;
+ALTER TABLE users DROP COLUMN IF EXISTS status;
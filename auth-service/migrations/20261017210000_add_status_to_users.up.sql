@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Users who aren't active can't log in and their tokens stop working. The reason
+-- is a note for admins about why the status was set.
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'suspended', 'locked', 'pending_verification'));
+ALTER TABLE users ADD COLUMN status_reason TEXT;
+ALTER TABLE users ADD COLUMN status_changed_at TIMESTAMPTZ;
+
+-- Accounts disabled through the admin API are suspended
+UPDATE users SET status = 'suspended', status_changed_at = disabled_at WHERE disabled_at IS NOT NULL;
+ALTER TABLE users DROP COLUMN disabled_at;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS audit_log;
+DROP FUNCTION IF EXISTS audit_log_append_only();
@@ -0,0 +1,36 @@
+-- Add up migration script here
+-- Security-relevant events for incident response. Each row's hash covers its
+-- contents and the hash of the row before it. There is no foreign key to users,
+-- so the trail outlives purged accounts.
+CREATE TABLE IF NOT EXISTS audit_log(
+   id BIGINT NOT NULL PRIMARY KEY,
+   occurred_at TIMESTAMPTZ NOT NULL,
+   event_type TEXT NOT NULL,
+   user_id UUID,
+   email TEXT,
+   actor TEXT,
+   ip_address TEXT,
+   user_agent TEXT,
+   details TEXT,
+   prev_hash TEXT NOT NULL,
+   hash TEXT NOT NULL UNIQUE
+);
+
+CREATE INDEX audit_log_user_id_idx ON audit_log (user_id);
+CREATE INDEX audit_log_event_type_idx ON audit_log (event_type);
+CREATE INDEX audit_log_occurred_at_idx ON audit_log (occurred_at);
+
+-- Rows can only be added
+CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS TRIGGER AS $$
+BEGIN
+   RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_update_or_delete
+   BEFORE UPDATE OR DELETE ON audit_log
+   FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_truncate
+   BEFORE TRUNCATE ON audit_log
+   FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
@@ -0,0 +1,30 @@
+-- Add up migration script here
+-- Endpoints that are sent authentication events. The signing secret is
+-- encrypted like TOTP secrets, since it has to be read back to sign deliveries.
+CREATE TABLE IF NOT EXISTS webhook_subscriptions(
+   id UUID NOT NULL PRIMARY KEY,
+   url TEXT NOT NULL,
+   secret TEXT NOT NULL,
+   event_types TEXT[] NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Each event sent to each subscribed endpoint, with the outcome of its last attempt
+CREATE TABLE IF NOT EXISTS webhook_deliveries(
+   id UUID NOT NULL PRIMARY KEY,
+   subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+   event_id UUID NOT NULL,
+   event_type TEXT NOT NULL,
+   payload TEXT NOT NULL,
+   status TEXT NOT NULL CHECK (status IN ('pending', 'succeeded', 'dead_lettered')),
+   attempts INTEGER NOT NULL DEFAULT 0,
+   next_attempt_at TIMESTAMPTZ,
+   last_attempt_at TIMESTAMPTZ,
+   last_response_status INTEGER,
+   last_error TEXT,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- The worker only looks for pending deliveries
+CREATE INDEX webhook_deliveries_due_idx ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_subscription_id_idx ON webhook_deliveries (subscription_id, created_at);
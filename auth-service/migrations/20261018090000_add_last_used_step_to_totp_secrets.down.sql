@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE totp_secrets DROP COLUMN IF EXISTS last_used_step;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- The 30 second time step of the last code accepted from the secret. Codes for
+-- that step or an earlier one are refused, so each code can only be used once.
+ALTER TABLE totp_secrets ADD COLUMN last_used_step BIGINT;
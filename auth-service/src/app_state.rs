@@ -2,10 +2,23 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::domain::EmailClient;
-use crate::data_stores::data_store::{BannedTokenStore, TwoFACodeStore, UserStore, BannedTokenStoreType};
+use crate::data_stores::data_store::{TwoFACodeStore, UserStore, BannedTokenStoreType, PasswordResetTokenStore, TotpSecretStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TokenVersionStore, FailedAttemptStore, WebauthnCredentialStore, OAuthClientStore, IdentityProviderStore, RoleStore, AuditLog, WebhookStore};
 
 pub type UserStoreType = Arc<RwLock<Box<dyn UserStore + Send + Sync>>>;
 pub type TwoFACodeStoreType = Arc<RwLock<Box<dyn TwoFACodeStore + Send + Sync>>>;
+pub type PasswordResetTokenStoreType = Arc<RwLock<Box<dyn PasswordResetTokenStore + Send + Sync>>>;
+pub type TotpSecretStoreType = Arc<RwLock<Box<dyn TotpSecretStore + Send + Sync>>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<Box<dyn RecoveryCodeStore + Send + Sync>>>;
+pub type RefreshTokenStoreType = Arc<RwLock<Box<dyn RefreshTokenStore + Send + Sync>>>;
+pub type SessionStoreType = Arc<RwLock<Box<dyn SessionStore + Send + Sync>>>;
+pub type TokenVersionStoreType = Arc<RwLock<Box<dyn TokenVersionStore + Send + Sync>>>;
+pub type FailedAttemptStoreType = Arc<RwLock<Box<dyn FailedAttemptStore + Send + Sync>>>;
+pub type WebauthnCredentialStoreType = Arc<RwLock<Box<dyn WebauthnCredentialStore + Send + Sync>>>;
+pub type OAuthClientStoreType = Arc<RwLock<Box<dyn OAuthClientStore + Send + Sync>>>;
+pub type IdentityProviderStoreType = Arc<RwLock<Box<dyn IdentityProviderStore + Send + Sync>>>;
+pub type RoleStoreType = Arc<RwLock<Box<dyn RoleStore + Send + Sync>>>;
+pub type AuditLogType = Arc<RwLock<Box<dyn AuditLog + Send + Sync>>>;
+pub type WebhookStoreType = Arc<RwLock<Box<dyn WebhookStore + Send + Sync>>>;
 pub type EmailClientType = Arc<RwLock<Box<dyn EmailClient + Send + Sync>>>;
 
 #[derive(Clone)]
@@ -14,20 +27,60 @@ pub struct AppState {
     pub banned_token_store: Arc<RwLock<BannedTokenStoreType>>,
     pub two_fa_code_store: TwoFACodeStoreType,
     pub email_client: EmailClientType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub totp_secret_store: TotpSecretStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub token_version_store: TokenVersionStoreType,
+    pub failed_attempt_store: FailedAttemptStoreType,
+    pub webauthn_credential_store: WebauthnCredentialStoreType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub identity_provider_store: IdentityProviderStoreType,
+    pub role_store: RoleStoreType,
+    pub audit_log: AuditLogType,
+    pub webhook_store: WebhookStoreType,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: Arc<RwLock<BannedTokenStoreType>>,
         two_fa_code_store: TwoFACodeStoreType,
         email_client: EmailClientType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        totp_secret_store: TotpSecretStoreType,
+        recovery_code_store: RecoveryCodeStoreType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_store: SessionStoreType,
+        token_version_store: TokenVersionStoreType,
+        failed_attempt_store: FailedAttemptStoreType,
+        webauthn_credential_store: WebauthnCredentialStoreType,
+        oauth_client_store: OAuthClientStoreType,
+        identity_provider_store: IdentityProviderStoreType,
+        role_store: RoleStoreType,
+        audit_log: AuditLogType,
+        webhook_store: WebhookStoreType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
             email_client,
+            password_reset_token_store,
+            totp_secret_store,
+            recovery_code_store,
+            refresh_token_store,
+            session_store,
+            token_version_store,
+            failed_attempt_store,
+            webauthn_credential_store,
+            oauth_client_store,
+            identity_provider_store,
+            role_store,
+            audit_log,
+            webhook_store,
         }
     }
 }
@@ -1,10 +1,23 @@
-use crate::domain::user::User;
+use crate::domain::user::{TwoFAMethod, User, UserId, UserStatus};
+use crate::domain::totp::TotpSecret;
+use crate::domain::recovery_code::RecoveryCode;
+use crate::domain::refresh_token::RefreshToken;
+use crate::domain::session::{Session, SessionId};
+use crate::domain::webauthn::WebauthnCredential;
+use crate::domain::oauth::OAuthClient;
+use crate::domain::identity_provider::IdentityProvider;
+use crate::domain::role::{Role, UserRoles};
+use crate::domain::audit::{AuditEvent, AuditLogFilter, AuditRecord, ChainVerification, ChainVerifier};
+use crate::domain::webhook::{DeliveryStatus, WebhookDelivery, WebhookSubscription};
 use crate::domain::email::Email;
 use crate::domain::password::Password;
 use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
 use crate::data_stores::redis_banned_token_store::RedisBannedTokenStore;
+use crate::utils::constants::MAX_TWO_FA_CODE_ATTEMPTS;
 
 // ============================================================================
 // TRAITS
@@ -14,7 +27,26 @@ use crate::data_stores::redis_banned_token_store::RedisBannedTokenStore;
 pub trait UserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError>;
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    // Records that a verification email is being sent, failing with
+    // `VerificationEmailThrottled` if one was sent less than `cooldown_seconds` ago
+    async fn record_verification_email_sent(&mut self, email: &Email, cooldown_seconds: i64) -> Result<(), UserStoreError>;
+    async fn set_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError>;
+    // Fails with `UserAlreadyExists` if the new email belongs to another user
+    async fn update_email(&mut self, id: &UserId, new_email: &Email) -> Result<(), UserStoreError>;
+    // Soft-deletes the user. Their data stays around until it is purged.
+    async fn mark_deleted(&mut self, id: &UserId) -> Result<(), UserStoreError>;
+    async fn restore_user(&mut self, id: &UserId) -> Result<(), UserStoreError>;
+    // Hard-deletes users soft-deleted more than `grace_period_seconds` ago and returns them
+    async fn purge_deleted_users(&mut self, grace_period_seconds: i64) -> Result<Vec<User>, UserStoreError>;
+    // A page of the users whose email contains `search`, ordered by email, and how
+    // many users match in total
+    async fn list_users(&self, search: Option<&str>, limit: i64, offset: i64) -> Result<(Vec<User>, i64), UserStoreError>;
+    // Sets the user's status and the reason for it, and when it was changed
+    async fn set_status(&mut self, id: &UserId, status: UserStatus, reason: Option<String>) -> Result<(), UserStoreError>;
 }
 
 #[async_trait::async_trait]
@@ -39,6 +71,196 @@ pub trait TwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Counts a wrong code submitted for the login attempt. The code is removed once it
+    // has failed MAX_TWO_FA_CODE_ATTEMPTS times, and TooManyAttempts is returned.
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
+    // Swaps in a new code for the login attempt, keeping its ID. Fails with
+    // `ResendThrottled` if the current code was sent less than `cooldown_seconds`
+    // ago, and with `TooManyResends` once it has been replaced `max_resends` times.
+    async fn replace_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+        cooldown_seconds: i64,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError>;
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: &PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError>;
+    // Stores only keep a hash of the token, see `PasswordResetToken::hash`
+    async fn get_token_hash(
+        &self,
+        email: &Email,
+    ) -> Result<String, PasswordResetTokenStoreError>;
+}
+
+// Secrets are enrolled as pending and only become active once the user has
+// proven they can generate codes from them.
+#[async_trait::async_trait]
+pub trait TotpSecretStore {
+    async fn set_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: &TotpSecret,
+    ) -> Result<(), TotpSecretStoreError>;
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError>;
+    async fn activate_pending_secret(&mut self, email: &Email) -> Result<(), TotpSecretStoreError>;
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError>;
+    // Records that a code for the time `step` was accepted. Fails with `CodeAlreadyUsed`
+    // if a code for that step or a later one was accepted before.
+    async fn record_used_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError>;
+}
+
+// Only hashes of the codes are stored. Replacing a user's codes invalidates
+// every code issued before.
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError>;
+    async fn use_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError>;
+    async fn remaining_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+}
+
+// Refresh tokens belong to a family started at login, one per session. Rotating a
+// token retires it in favour of the next token in its family. Presenting a retired
+// token means it was stolen or replayed, so the whole family is revoked.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn create_family(
+        &mut self,
+        email: &Email,
+        session_id: &SessionId,
+        token: &RefreshToken,
+        ttl_seconds: i64,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: &RefreshToken,
+        ttl_seconds: i64,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError>;
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+    async fn revoke_all_families(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+// Registry of each user's login sessions. A session ends when it is revoked, or
+// when it hasn't been extended for `ttl_seconds`.
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: &Session, ttl_seconds: i64) -> Result<(), SessionStoreError>;
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError>;
+    async fn is_session_active(&self, user_id: &UserId, id: &SessionId) -> Result<bool, SessionStoreError>;
+    async fn extend_session(&mut self, id: &SessionId, ttl_seconds: i64) -> Result<(), SessionStoreError>;
+    async fn revoke_session(&mut self, user_id: &UserId, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn revoke_all_sessions(&mut self, user_id: &UserId) -> Result<(), SessionStoreError>;
+}
+
+// Every JWT carries its user's token version at the time it was issued. Bumping
+// the version invalidates all of them at once, including ones we never saw.
+#[async_trait::async_trait]
+pub trait TokenVersionStore {
+    async fn get_version(&self, user_id: &UserId) -> Result<i64, TokenVersionStoreError>;
+    async fn bump_version(&mut self, user_id: &UserId) -> Result<i64, TokenVersionStoreError>;
+    // Forgets the version of a user who no longer exists
+    async fn remove_version(&mut self, user_id: &UserId) -> Result<(), TokenVersionStoreError>;
+}
+
+// Sliding-window counters of failed attempts, keyed by what is being guessed at
+// (an account) or by who is guessing (a client IP).
+#[async_trait::async_trait]
+pub trait FailedAttemptStore {
+    // Seconds left on the key's lockout, if it is locked out
+    async fn lockout_remaining(&self, key: &str) -> Result<Option<i64>, FailedAttemptStoreError>;
+    // Counts a failure against the key and locks it out once the policy's limit is
+    // reached. Returns the length of the lockout if this failure started one.
+    async fn record_failure(&mut self, key: &str, policy: &LockoutPolicy) -> Result<Option<i64>, FailedAttemptStoreError>;
+    // Forgets the key's failures after a successful attempt. Past lockouts still
+    // count towards the length of the next one.
+    async fn clear_failures(&mut self, key: &str) -> Result<(), FailedAttemptStoreError>;
+}
+
+// Passkeys registered by users, looked up by the credential ID the authenticator
+// presents when logging in.
+#[async_trait::async_trait]
+pub trait WebauthnCredentialStore {
+    async fn add_credential(&mut self, credential: &WebauthnCredential) -> Result<(), WebauthnCredentialStoreError>;
+    async fn get_credential(&self, id: &str) -> Result<WebauthnCredential, WebauthnCredentialStoreError>;
+    async fn get_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>, WebauthnCredentialStoreError>;
+    async fn update_sign_count(&mut self, id: &str, sign_count: u32) -> Result<(), WebauthnCredentialStoreError>;
+}
+
+// Applications registered to log users in through the OAuth2 endpoints
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    async fn add_client(&mut self, client: &OAuthClient) -> Result<(), OAuthClientStoreError>;
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError>;
+}
+
+// Upstream OpenID Connect providers users can log in with, by name
+#[async_trait::async_trait]
+pub trait IdentityProviderStore {
+    async fn add_provider(&mut self, provider: &IdentityProvider) -> Result<(), IdentityProviderStoreError>;
+    async fn get_provider(&self, name: &str) -> Result<IdentityProvider, IdentityProviderStoreError>;
+}
+
+// Roles are defined with the permissions they grant, and granted to users. Users
+// have the permissions of all of their roles.
+#[async_trait::async_trait]
+pub trait RoleStore {
+    // Defines the role, or replaces the permissions of an existing one
+    async fn define_role(&mut self, role: &Role, permissions: &[String]) -> Result<(), RoleStoreError>;
+    // Granting a role the user already has does nothing
+    async fn grant_role(&mut self, user_id: &UserId, role: &Role) -> Result<(), RoleStoreError>;
+    async fn revoke_role(&mut self, user_id: &UserId, role: &Role) -> Result<(), RoleStoreError>;
+    async fn get_user_roles(&self, user_id: &UserId) -> Result<UserRoles, RoleStoreError>;
+}
+
+// Append-only record of security-relevant events. Records are hash-chained, see
+// `AuditRecord`, so tampering with the stored log can be detected.
+#[async_trait::async_trait]
+pub trait AuditLog {
+    // Writes the event after the last record and returns it as recorded
+    async fn append(&mut self, event: AuditEvent) -> Result<AuditRecord, AuditLogError>;
+    // A page of the records matching the filter, newest first, and how many
+    // records match in total
+    async fn query(&self, filter: &AuditLogFilter, limit: i64, offset: i64) -> Result<(Vec<AuditRecord>, i64), AuditLogError>;
+    // Checks the whole chain, oldest record first
+    async fn verify(&self) -> Result<ChainVerification, AuditLogError>;
+}
+
+// Webhook subscriptions and the deliveries of events to them. Deleting a
+// subscription deletes its deliveries too.
+#[async_trait::async_trait]
+pub trait WebhookStore {
+    async fn add_subscription(&mut self, subscription: &WebhookSubscription) -> Result<(), WebhookStoreError>;
+    async fn get_subscription(&self, id: &Uuid) -> Result<WebhookSubscription, WebhookStoreError>;
+    // Oldest first
+    async fn list_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError>;
+    async fn delete_subscription(&mut self, id: &Uuid) -> Result<(), WebhookStoreError>;
+    async fn add_deliveries(&mut self, deliveries: &[WebhookDelivery]) -> Result<(), WebhookStoreError>;
+    // Hands out up to `limit` pending deliveries that are due, oldest due first.
+    // They aren't handed out again for `lease_seconds`, so workers running at the
+    // same time don't send the same delivery.
+    async fn claim_due_deliveries(&mut self, limit: i64, lease_seconds: i64) -> Result<Vec<WebhookDelivery>, WebhookStoreError>;
+    // Saves the outcome of an attempt at the delivery
+    async fn update_delivery(&mut self, delivery: &WebhookDelivery) -> Result<(), WebhookStoreError>;
+    // A page of the subscription's deliveries, newest first, and how many it has in total
+    async fn list_deliveries(&self, subscription_id: &Uuid, limit: i64, offset: i64) -> Result<(Vec<WebhookDelivery>, i64), WebhookStoreError>;
 }
 
 // ============================================================================
@@ -50,6 +272,7 @@ pub enum UserStoreError {
     UserAlreadyExists,
     UserNotFound,
     InvalidCredentials,
+    VerificationEmailThrottled,
     UnexpectedError,
 }
 
@@ -63,6 +286,96 @@ pub enum BannedTokenStoreError {
 #[derive(Debug, PartialEq)]
 pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
+    TooManyAttempts,
+    ResendThrottled,
+    TooManyResends,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordResetTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TotpSecretStoreError {
+    SecretNotFound,
+    CodeAlreadyUsed,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecoveryCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    TokenNotFound,
+    TokenExpired,
+    TokenRevoked,
+    // The token was already used, so the family was revoked. Carries the user and
+    // session the family belonged to, so the session can be ended too.
+    TokenReused(Email, SessionId),
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TokenVersionStoreError {
+    UserNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FailedAttemptStoreError {
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebauthnCredentialStoreError {
+    CredentialAlreadyExists,
+    CredentialNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OAuthClientStoreError {
+    ClientAlreadyExists,
+    ClientNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IdentityProviderStoreError {
+    ProviderAlreadyExists,
+    ProviderNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RoleStoreError {
+    RoleNotFound,
+    RoleNotGranted,
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuditLogError {
+    UnexpectedError,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebhookStoreError {
+    SubscriptionNotFound,
+    DeliveryNotFound,
     UnexpectedError,
 }
 
@@ -166,6 +479,71 @@ impl AsRef<str> for TwoFACode {
     }
 }
 
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PasswordResetToken(String);
+
+impl PasswordResetToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == PASSWORD_RESET_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(PasswordResetToken(token))
+        } else {
+            Err("Invalid password reset token".to_string())
+        }
+    }
+
+    // Stores keep a SHA-256 hash so a leaked store can't be used to reset passwords
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+
+    // Compares digests rather than the tokens, so how long the comparison takes
+    // tells nothing about how much of the token was right
+    pub fn matches_hash(&self, hash: &str) -> bool {
+        self.hash() == hash
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(PASSWORD_RESET_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        PasswordResetToken(token)
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A key is locked out once it collects `max_failures` failures within
+// `window_seconds`. Every lockout lasts twice as long as the one before, up to
+// `max_lockout_seconds`, until the key goes `backoff_reset_seconds` without one.
+#[derive(Clone, Copy, Debug)]
+pub struct LockoutPolicy {
+    pub max_failures: u32,
+    pub window_seconds: i64,
+    pub base_lockout_seconds: i64,
+    pub max_lockout_seconds: i64,
+    pub backoff_reset_seconds: i64,
+}
+
+impl LockoutPolicy {
+    // Length of the `lockouts`th lockout in a row, counting from 1
+    pub fn lockout_seconds(&self, lockouts: u32) -> i64 {
+        let doublings = lockouts.saturating_sub(1).min(32);
+        self.base_lockout_seconds
+            .saturating_mul(1 << doublings)
+            .min(self.max_lockout_seconds)
+    }
+}
+
 // ============================================================================
 // CONCRETE IMPLEMENTATIONS
 // ============================================================================
@@ -173,6 +551,7 @@ impl AsRef<str> for TwoFACode {
 #[derive(Default)]
 pub struct HashmapUserStore {
     users: HashMap<Email, User>,
+    verification_emails_sent_at: HashMap<Email, DateTime<Utc>>,
 }
 
 #[async_trait::async_trait]
@@ -189,6 +568,10 @@ impl UserStore for HashmapUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         self.users.get(email).cloned().ok_or(UserStoreError::UserNotFound)
     }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.users.values().find(|user| user.id == *id).cloned().ok_or(UserStoreError::UserNotFound)
+    }
     
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
         let user = self.get_user(email).await?;
@@ -198,6 +581,116 @@ impl UserStore for HashmapUserStore {
             Err(UserStoreError::InvalidCredentials)
         }
     }
+
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        let user = self.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.password = password;
+        Ok(())
+    }
+
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.email_verified = true;
+        Ok(())
+    }
+
+    async fn record_verification_email_sent(&mut self, email: &Email, cooldown_seconds: i64) -> Result<(), UserStoreError> {
+        if !self.users.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let now = Utc::now();
+        if let Some(sent_at) = self.verification_emails_sent_at.get(email) {
+            if (now - *sent_at).num_seconds() < cooldown_seconds {
+                return Err(UserStoreError::VerificationEmailThrottled);
+            }
+        }
+        self.verification_emails_sent_at.insert(email.clone(), now);
+        Ok(())
+    }
+
+    async fn set_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError> {
+        let user = self.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.two_fa_method = method;
+        Ok(())
+    }
+
+    async fn update_email(&mut self, id: &UserId, new_email: &Email) -> Result<(), UserStoreError> {
+        let mut user = self.get_user_by_id(id).await?;
+        if user.email == *new_email {
+            return Ok(());
+        }
+        if self.users.contains_key(new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        self.users.remove(&user.email);
+        if let Some(sent_at) = self.verification_emails_sent_at.remove(&user.email) {
+            self.verification_emails_sent_at.insert(new_email.clone(), sent_at);
+        }
+        user.email = new_email.clone();
+        self.users.insert(new_email.clone(), user);
+        Ok(())
+    }
+
+    async fn mark_deleted(&mut self, id: &UserId) -> Result<(), UserStoreError> {
+        let user = self.users.values_mut().find(|user| user.id == *id).ok_or(UserStoreError::UserNotFound)?;
+        user.deleted_at.get_or_insert_with(Utc::now);
+        Ok(())
+    }
+
+    async fn restore_user(&mut self, id: &UserId) -> Result<(), UserStoreError> {
+        let user = self.users.values_mut().find(|user| user.id == *id).ok_or(UserStoreError::UserNotFound)?;
+        user.deleted_at = None;
+        Ok(())
+    }
+
+    async fn purge_deleted_users(&mut self, grace_period_seconds: i64) -> Result<Vec<User>, UserStoreError> {
+        let cutoff = Utc::now() - chrono::Duration::seconds(grace_period_seconds);
+        let expired: Vec<Email> = self
+            .users
+            .values()
+            .filter(|user| user.deleted_at.is_some_and(|deleted_at| deleted_at <= cutoff))
+            .map(|user| user.email.clone())
+            .collect();
+
+        Ok(expired
+            .iter()
+            .filter_map(|email| {
+                self.verification_emails_sent_at.remove(email);
+                self.users.remove(email)
+            })
+            .collect())
+    }
+
+    async fn list_users(&self, search: Option<&str>, limit: i64, offset: i64) -> Result<(Vec<User>, i64), UserStoreError> {
+        let search = search.map(str::to_lowercase);
+        let mut users: Vec<&User> = self
+            .users
+            .values()
+            .filter(|user| match &search {
+                Some(search) => user.email.as_ref().to_lowercase().contains(search.as_str()),
+                None => true,
+            })
+            .collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+
+        let total = users.len() as i64;
+        let page = users
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok((page, total))
+    }
+
+    async fn set_status(&mut self, id: &UserId, status: UserStatus, reason: Option<String>) -> Result<(), UserStoreError> {
+        let user = self.users.values_mut().find(|user| user.id == *id).ok_or(UserStoreError::UserNotFound)?;
+        user.status = status;
+        user.status_reason = reason;
+        user.status_changed_at = Some(Utc::now());
+        Ok(())
+    }
 }
 
 #[derive(Default)]
@@ -240,9 +733,18 @@ impl BannedTokenStore for HashsetBannedTokenStore {
     }
 }
 
+struct TwoFACodeRecord {
+    login_attempt_id: LoginAttemptId,
+    code: TwoFACode,
+    // Wrong codes submitted for this login attempt
+    failed_attempts: u32,
+    resends: u32,
+    sent_at: DateTime<Utc>,
+}
+
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<Email, TwoFACodeRecord>,
 }
 
 #[async_trait::async_trait]
@@ -253,7 +755,14 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         login_attempt_id: &LoginAttemptId,
         code: &TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+        let record = TwoFACodeRecord {
+            login_attempt_id: login_attempt_id.clone(),
+            code: code.clone(),
+            failed_attempts: 0,
+            resends: 0,
+            sent_at: Utc::now(),
+        };
+        self.codes.insert(email.clone(), record);
         Ok(())
     }
     
@@ -264,74 +773,926 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
     
     async fn get_code(&self, email: &Email) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
         self.codes.get(email)
-            .cloned()
+            .map(|record| (record.login_attempt_id.clone(), record.code.clone()))
             .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
     }
-}
-
-// ============================================================================
-// TESTS
-// ============================================================================
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    // HashmapUserStore tests
-    #[tokio::test]
-    async fn test_add_user() {
-        let mut store = HashmapUserStore::default();
-        let user = User {
-            email: Email::parse("test@email.com".to_string()).unwrap(),
-            password: Password::parse("password123".to_string()).unwrap(),
-            requires_2fa: false,
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let record = match self.codes.get_mut(email) {
+            Some(record) if record.login_attempt_id == *login_attempt_id => record,
+            _ => return Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         };
-        assert_eq!(store.add_user(user).await, Ok(()));
-    }
 
-    #[tokio::test]
-    async fn test_get_user() {
-        let mut store = HashmapUserStore::default();
-        let user = User {
-            email: Email::parse("test@gmail.com".to_string()).unwrap(),
-            password: Password::parse("password123".to_string()).unwrap(),
-            requires_2fa: false,
-        };
-        let _ = store.add_user(user).await;
-        if let Ok(user) = store.get_user(&Email::parse("test@gmail.com".to_string()).unwrap()).await {
-            assert_eq!(user.email, Email::parse("test@gmail.com".to_string()).unwrap());
-            assert_eq!(user.password, Password::parse("password123".to_string()).unwrap());
-            assert_eq!(user.requires_2fa, false);
-        } else {
-            panic!("User not found");
+        record.failed_attempts += 1;
+        if record.failed_attempts >= MAX_TWO_FA_CODE_ATTEMPTS {
+            self.codes.remove(email);
+            return Err(TwoFACodeStoreError::TooManyAttempts);
         }
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_validate_user() {
-        let mut store = HashmapUserStore::default();
-        let user = User {
-            email: Email::parse("test@gmail.com".to_string()).unwrap(),
-            password: Password::parse("password123".to_string()).unwrap(),
-            requires_2fa: false,
+    async fn replace_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+        cooldown_seconds: i64,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let record = match self.codes.get_mut(email) {
+            Some(record) if record.login_attempt_id == *login_attempt_id => record,
+            _ => return Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         };
 
-        let _ = store.add_user(user).await;
-        assert_eq!(store.validate_user(&Email::parse("test@gmail.com".to_string()).unwrap(), &Password::parse("password123".to_string()).unwrap()).await, Ok(()));
+        if record.resends >= max_resends {
+            return Err(TwoFACodeStoreError::TooManyResends);
+        }
+        let now = Utc::now();
+        if (now - record.sent_at).num_seconds() < cooldown_seconds {
+            return Err(TwoFACodeStoreError::ResendThrottled);
+        }
+
+        record.code = code.clone();
+        record.resends += 1;
+        record.sent_at = now;
+        Ok(())
     }
+}
 
-    // HashsetBannedTokenStore tests
-    #[tokio::test]
-    async fn test_store_token() {
-        let mut store = HashsetBannedTokenStore::default();
-        store.store_token("test_token".to_string()).await.unwrap();
-        assert!(store.is_token_banned("test_token").await.unwrap());
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    token_hashes: HashMap<Email, String>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: &PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        self.token_hashes.insert(email.clone(), token.hash());
+        Ok(())
     }
 
-    #[tokio::test]
-    async fn test_is_token_banned() {
-        let store = HashsetBannedTokenStore::default();
-        assert!(!store.is_token_banned("test_token").await.unwrap());
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        self.token_hashes.remove(email);
+        Ok(())
+    }
+
+    async fn get_token_hash(&self, email: &Email) -> Result<String, PasswordResetTokenStoreError> {
+        self.token_hashes.get(email)
+            .cloned()
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapTotpSecretStore {
+    secrets: HashMap<Email, TotpSecret>,
+    pending_secrets: HashMap<Email, TotpSecret>,
+    last_used_steps: HashMap<Email, u64>,
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for HashmapTotpSecretStore {
+    async fn set_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: &TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        self.pending_secrets.insert(email.clone(), secret.clone());
+        Ok(())
+    }
+
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        self.pending_secrets.get(email)
+            .cloned()
+            .ok_or(TotpSecretStoreError::SecretNotFound)
+    }
+
+    async fn activate_pending_secret(&mut self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        let secret = self.pending_secrets.remove(email).ok_or(TotpSecretStoreError::SecretNotFound)?;
+        self.secrets.insert(email.clone(), secret);
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        self.secrets.get(email)
+            .cloned()
+            .ok_or(TotpSecretStoreError::SecretNotFound)
+    }
+
+    async fn record_used_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        if self.last_used_steps.get(email).is_some_and(|last| *last >= step) {
+            return Err(TotpSecretStoreError::CodeAlreadyUsed);
+        }
+        self.last_used_steps.insert(email.clone(), step);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    // Hashes of the codes each user has not used yet
+    code_hashes: HashMap<Email, HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let hashes = codes.iter().map(RecoveryCode::hash).collect();
+        self.code_hashes.insert(email.clone(), hashes);
+        Ok(())
+    }
+
+    async fn use_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError> {
+        let removed = self.code_hashes
+            .get_mut(email)
+            .is_some_and(|hashes| hashes.remove(&code.hash()));
+        if removed {
+            Ok(())
+        } else {
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        }
+    }
+
+    async fn remaining_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.code_hashes.get(email).map_or(0, HashSet::len))
+    }
+}
+
+struct RefreshTokenRecord {
+    family_id: String,
+    expires_at: DateTime<Utc>,
+    used: bool,
+}
+
+struct RefreshTokenFamily {
+    email: Email,
+    session_id: SessionId,
+    revoked: bool,
+}
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    // Keyed by token hash
+    tokens: HashMap<String, RefreshTokenRecord>,
+    families: HashMap<String, RefreshTokenFamily>,
+}
+
+impl HashmapRefreshTokenStore {
+    fn add_to_family(&mut self, family_id: String, token: &RefreshToken, ttl_seconds: i64) {
+        let record = RefreshTokenRecord {
+            family_id,
+            expires_at: Utc::now() + chrono::Duration::seconds(ttl_seconds),
+            used: false,
+        };
+        self.tokens.insert(token.hash(), record);
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn create_family(
+        &mut self,
+        email: &Email,
+        session_id: &SessionId,
+        token: &RefreshToken,
+        ttl_seconds: i64,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let family_id = Uuid::new_v4().to_string();
+        let family = RefreshTokenFamily { email: email.clone(), session_id: session_id.clone(), revoked: false };
+        self.families.insert(family_id.clone(), family);
+        self.add_to_family(family_id, token, ttl_seconds);
+        Ok(())
+    }
+
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: &RefreshToken,
+        ttl_seconds: i64,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError> {
+        let record = self.tokens.get_mut(&token.hash()).ok_or(RefreshTokenStoreError::TokenNotFound)?;
+        let family = self.families.get_mut(&record.family_id).ok_or(RefreshTokenStoreError::UnexpectedError)?;
+
+        if family.revoked {
+            return Err(RefreshTokenStoreError::TokenRevoked);
+        }
+        if record.used {
+            family.revoked = true;
+            return Err(RefreshTokenStoreError::TokenReused(family.email.clone(), family.session_id.clone()));
+        }
+        if record.expires_at <= Utc::now() {
+            return Err(RefreshTokenStoreError::TokenExpired);
+        }
+
+        record.used = true;
+        let family_id = record.family_id.clone();
+        let session = (family.email.clone(), family.session_id.clone());
+        self.add_to_family(family_id, new_token, ttl_seconds);
+        Ok(session)
+    }
+
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let record = self.tokens.get(&token.hash()).ok_or(RefreshTokenStoreError::TokenNotFound)?;
+        if let Some(family) = self.families.get_mut(&record.family_id) {
+            family.revoked = true;
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_families(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        self.families
+            .values_mut()
+            .filter(|family| family.email == *email)
+            .for_each(|family| family.revoked = true);
+        Ok(())
+    }
+}
+
+struct SessionRecord {
+    session: Session,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<SessionId, SessionRecord>,
+}
+
+impl HashmapSessionStore {
+    fn active_session(&self, id: &SessionId) -> Option<&Session> {
+        self.sessions
+            .get(id)
+            .filter(|record| record.expires_at > Utc::now())
+            .map(|record| &record.session)
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: &Session, ttl_seconds: i64) -> Result<(), SessionStoreError> {
+        let record = SessionRecord {
+            session: session.clone(),
+            expires_at: Utc::now() + chrono::Duration::seconds(ttl_seconds),
+        };
+        self.sessions.insert(session.id.clone(), record);
+        Ok(())
+    }
+
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .keys()
+            .filter_map(|id| self.active_session(id))
+            .filter(|session| session.user_id == *user_id)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    async fn is_session_active(&self, user_id: &UserId, id: &SessionId) -> Result<bool, SessionStoreError> {
+        Ok(self.active_session(id).is_some_and(|session| session.user_id == *user_id))
+    }
+
+    async fn extend_session(&mut self, id: &SessionId, ttl_seconds: i64) -> Result<(), SessionStoreError> {
+        if self.active_session(id).is_none() {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+        if let Some(record) = self.sessions.get_mut(id) {
+            record.expires_at = Utc::now() + chrono::Duration::seconds(ttl_seconds);
+        }
+        Ok(())
+    }
+
+    async fn revoke_session(&mut self, user_id: &UserId, id: &SessionId) -> Result<(), SessionStoreError> {
+        if !self.is_session_active(user_id, id).await? {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+        self.sessions.remove(id);
+        Ok(())
+    }
+
+    async fn revoke_all_sessions(&mut self, user_id: &UserId) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, record| record.session.user_id != *user_id);
+        Ok(())
+    }
+}
+
+// Users start out at version 0
+#[derive(Default)]
+pub struct HashmapTokenVersionStore {
+    versions: HashMap<UserId, i64>,
+}
+
+#[async_trait::async_trait]
+impl TokenVersionStore for HashmapTokenVersionStore {
+    async fn get_version(&self, user_id: &UserId) -> Result<i64, TokenVersionStoreError> {
+        Ok(self.versions.get(user_id).copied().unwrap_or_default())
+    }
+
+    async fn bump_version(&mut self, user_id: &UserId) -> Result<i64, TokenVersionStoreError> {
+        let version = self.versions.entry(user_id.clone()).or_default();
+        *version += 1;
+        Ok(*version)
+    }
+
+    async fn remove_version(&mut self, user_id: &UserId) -> Result<(), TokenVersionStoreError> {
+        self.versions.remove(user_id);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct FailedAttemptRecord {
+    failures: Vec<DateTime<Utc>>,
+    locked_until: Option<DateTime<Utc>>,
+    lockouts: u32,
+    last_lockout_at: Option<DateTime<Utc>>,
+}
+
+impl FailedAttemptRecord {
+    // Nothing left in it would count towards or prolong a lockout
+    fn is_expired(&self, now: DateTime<Utc>, policy: &LockoutPolicy) -> bool {
+        let window_start = now - chrono::Duration::seconds(policy.window_seconds);
+        let backoff_start = now - chrono::Duration::seconds(policy.backoff_reset_seconds);
+        self.failures.iter().all(|failed_at| *failed_at <= window_start)
+            && self.locked_until.is_none_or(|locked_until| locked_until <= now)
+            && self.last_lockout_at.is_none_or(|locked_at| locked_at <= backoff_start)
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapFailedAttemptStore {
+    records: HashMap<String, FailedAttemptRecord>,
+}
+
+#[async_trait::async_trait]
+impl FailedAttemptStore for HashmapFailedAttemptStore {
+    async fn lockout_remaining(&self, key: &str) -> Result<Option<i64>, FailedAttemptStoreError> {
+        let now = Utc::now();
+        Ok(self
+            .records
+            .get(key)
+            .and_then(|record| record.locked_until)
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| (locked_until - now).num_seconds().max(1)))
+    }
+
+    async fn record_failure(&mut self, key: &str, policy: &LockoutPolicy) -> Result<Option<i64>, FailedAttemptStoreError> {
+        let now = Utc::now();
+        // Keys are only ever added here, so this is where the ones that have run out get dropped
+        self.records.retain(|_, record| !record.is_expired(now, policy));
+        let record = self.records.entry(key.to_owned()).or_default();
+
+        let window_start = now - chrono::Duration::seconds(policy.window_seconds);
+        record.failures.retain(|failed_at| *failed_at > window_start);
+        record.failures.push(now);
+        if record.failures.len() < policy.max_failures as usize {
+            return Ok(None);
+        }
+
+        let backoff_start = now - chrono::Duration::seconds(policy.backoff_reset_seconds);
+        if record.last_lockout_at.is_some_and(|locked_at| locked_at <= backoff_start) {
+            record.lockouts = 0;
+        }
+        record.lockouts += 1;
+
+        let lockout_seconds = policy.lockout_seconds(record.lockouts);
+        record.failures.clear();
+        record.locked_until = Some(now + chrono::Duration::seconds(lockout_seconds));
+        record.last_lockout_at = Some(now);
+        Ok(Some(lockout_seconds))
+    }
+
+    async fn clear_failures(&mut self, key: &str) -> Result<(), FailedAttemptStoreError> {
+        if let Some(record) = self.records.get_mut(key) {
+            record.failures.clear();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapWebauthnCredentialStore {
+    credentials: HashMap<String, WebauthnCredential>,
+}
+
+#[async_trait::async_trait]
+impl WebauthnCredentialStore for HashmapWebauthnCredentialStore {
+    async fn add_credential(&mut self, credential: &WebauthnCredential) -> Result<(), WebauthnCredentialStoreError> {
+        if self.credentials.contains_key(&credential.id) {
+            return Err(WebauthnCredentialStoreError::CredentialAlreadyExists);
+        }
+        self.credentials.insert(credential.id.clone(), credential.clone());
+        Ok(())
+    }
+
+    async fn get_credential(&self, id: &str) -> Result<WebauthnCredential, WebauthnCredentialStoreError> {
+        self.credentials
+            .get(id)
+            .cloned()
+            .ok_or(WebauthnCredentialStoreError::CredentialNotFound)
+    }
+
+    async fn get_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>, WebauthnCredentialStoreError> {
+        let mut credentials: Vec<WebauthnCredential> = self
+            .credentials
+            .values()
+            .filter(|credential| credential.user_id == *user_id)
+            .cloned()
+            .collect();
+        credentials.sort_by_key(|credential| credential.created_at);
+        Ok(credentials)
+    }
+
+    async fn update_sign_count(&mut self, id: &str, sign_count: u32) -> Result<(), WebauthnCredentialStoreError> {
+        let credential = self
+            .credentials
+            .get_mut(id)
+            .ok_or(WebauthnCredentialStoreError::CredentialNotFound)?;
+        credential.sign_count = sign_count;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapOAuthClientStore {
+    clients: HashMap<String, OAuthClient>,
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashmapOAuthClientStore {
+    async fn add_client(&mut self, client: &OAuthClient) -> Result<(), OAuthClientStoreError> {
+        if self.clients.contains_key(&client.id) {
+            return Err(OAuthClientStoreError::ClientAlreadyExists);
+        }
+        self.clients.insert(client.id.clone(), client.clone());
+        Ok(())
+    }
+
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        self.clients.get(id).cloned().ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+}
+
+// Providers come from the configuration, so they are only ever kept in memory
+#[derive(Default)]
+pub struct HashmapIdentityProviderStore {
+    providers: HashMap<String, IdentityProvider>,
+}
+
+#[async_trait::async_trait]
+impl IdentityProviderStore for HashmapIdentityProviderStore {
+    async fn add_provider(&mut self, provider: &IdentityProvider) -> Result<(), IdentityProviderStoreError> {
+        if self.providers.contains_key(&provider.name) {
+            return Err(IdentityProviderStoreError::ProviderAlreadyExists);
+        }
+        self.providers.insert(provider.name.clone(), provider.clone());
+        Ok(())
+    }
+
+    async fn get_provider(&self, name: &str) -> Result<IdentityProvider, IdentityProviderStoreError> {
+        self.providers.get(name).cloned().ok_or(IdentityProviderStoreError::ProviderNotFound)
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapRoleStore {
+    // Permissions of each defined role
+    roles: HashMap<Role, Vec<String>>,
+    grants: HashMap<UserId, HashSet<Role>>,
+}
+
+#[async_trait::async_trait]
+impl RoleStore for HashmapRoleStore {
+    async fn define_role(&mut self, role: &Role, permissions: &[String]) -> Result<(), RoleStoreError> {
+        self.roles.insert(role.clone(), permissions.to_vec());
+        Ok(())
+    }
+
+    async fn grant_role(&mut self, user_id: &UserId, role: &Role) -> Result<(), RoleStoreError> {
+        if !self.roles.contains_key(role) {
+            return Err(RoleStoreError::RoleNotFound);
+        }
+        self.grants.entry(user_id.clone()).or_default().insert(role.clone());
+        Ok(())
+    }
+
+    async fn revoke_role(&mut self, user_id: &UserId, role: &Role) -> Result<(), RoleStoreError> {
+        let revoked = self.grants.get_mut(user_id).is_some_and(|roles| roles.remove(role));
+        if revoked {
+            Ok(())
+        } else {
+            Err(RoleStoreError::RoleNotGranted)
+        }
+    }
+
+    async fn get_user_roles(&self, user_id: &UserId) -> Result<UserRoles, RoleStoreError> {
+        let granted = self.grants.get(user_id).into_iter().flatten();
+        let roles = granted.clone().map(|role| role.as_ref().to_owned()).collect();
+        let permissions = granted
+            .filter_map(|role| self.roles.get(role))
+            .flatten()
+            .cloned()
+            .collect();
+        Ok(UserRoles::new(roles, permissions))
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapAuditLog {
+    // Oldest first
+    records: Vec<AuditRecord>,
+}
+
+#[async_trait::async_trait]
+impl AuditLog for HashmapAuditLog {
+    async fn append(&mut self, event: AuditEvent) -> Result<AuditRecord, AuditLogError> {
+        let record = AuditRecord::new(self.records.last(), Utc::now(), event);
+        self.records.push(record.clone());
+        Ok(record)
+    }
+
+    async fn query(&self, filter: &AuditLogFilter, limit: i64, offset: i64) -> Result<(Vec<AuditRecord>, i64), AuditLogError> {
+        let matching: Vec<&AuditRecord> = self.records.iter().rev().filter(|record| filter.matches(record)).collect();
+
+        let total = matching.len() as i64;
+        let page = matching
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok((page, total))
+    }
+
+    async fn verify(&self) -> Result<ChainVerification, AuditLogError> {
+        let mut verifier = ChainVerifier::default();
+        let first_invalid_id = self.records.iter().find(|record| !verifier.check(record)).map(|record| record.id);
+        Ok(verifier.finish(first_invalid_id))
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapWebhookStore {
+    // Oldest first
+    subscriptions: Vec<WebhookSubscription>,
+    // Oldest first
+    deliveries: Vec<WebhookDelivery>,
+}
+
+#[async_trait::async_trait]
+impl WebhookStore for HashmapWebhookStore {
+    async fn add_subscription(&mut self, subscription: &WebhookSubscription) -> Result<(), WebhookStoreError> {
+        self.subscriptions.push(subscription.clone());
+        Ok(())
+    }
+
+    async fn get_subscription(&self, id: &Uuid) -> Result<WebhookSubscription, WebhookStoreError> {
+        self.subscriptions
+            .iter()
+            .find(|subscription| subscription.id == *id)
+            .cloned()
+            .ok_or(WebhookStoreError::SubscriptionNotFound)
+    }
+
+    async fn list_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError> {
+        Ok(self.subscriptions.clone())
+    }
+
+    async fn delete_subscription(&mut self, id: &Uuid) -> Result<(), WebhookStoreError> {
+        let count = self.subscriptions.len();
+        self.subscriptions.retain(|subscription| subscription.id != *id);
+        if self.subscriptions.len() == count {
+            return Err(WebhookStoreError::SubscriptionNotFound);
+        }
+        self.deliveries.retain(|delivery| delivery.subscription_id != *id);
+        Ok(())
+    }
+
+    async fn add_deliveries(&mut self, deliveries: &[WebhookDelivery]) -> Result<(), WebhookStoreError> {
+        self.deliveries.extend_from_slice(deliveries);
+        Ok(())
+    }
+
+    async fn claim_due_deliveries(&mut self, limit: i64, lease_seconds: i64) -> Result<Vec<WebhookDelivery>, WebhookStoreError> {
+        let now = Utc::now();
+        let mut due: Vec<&mut WebhookDelivery> = self
+            .deliveries
+            .iter_mut()
+            .filter(|delivery| delivery.status == DeliveryStatus::Pending)
+            .filter(|delivery| delivery.next_attempt_at.is_some_and(|next_attempt_at| next_attempt_at <= now))
+            .collect();
+        due.sort_by_key(|delivery| delivery.next_attempt_at);
+
+        Ok(due
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .map(|delivery| {
+                delivery.next_attempt_at = Some(now + chrono::Duration::seconds(lease_seconds));
+                delivery.clone()
+            })
+            .collect())
+    }
+
+    async fn update_delivery(&mut self, delivery: &WebhookDelivery) -> Result<(), WebhookStoreError> {
+        let stored = self
+            .deliveries
+            .iter_mut()
+            .find(|stored| stored.id == delivery.id)
+            .ok_or(WebhookStoreError::DeliveryNotFound)?;
+        *stored = delivery.clone();
+        Ok(())
+    }
+
+    async fn list_deliveries(&self, subscription_id: &Uuid, limit: i64, offset: i64) -> Result<(Vec<WebhookDelivery>, i64), WebhookStoreError> {
+        let matching: Vec<&WebhookDelivery> = self
+            .deliveries
+            .iter()
+            .rev()
+            .filter(|delivery| delivery.subscription_id == *subscription_id)
+            .collect();
+
+        let total = matching.len() as i64;
+        let page = matching
+            .into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect();
+        Ok((page, total))
+    }
+}
+
+// ============================================================================
+// TESTS
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::audit::AuditEventType;
+    use crate::domain::webhook::WebhookEventType;
+
+    // HashmapUserStore tests
+    #[tokio::test]
+    async fn test_add_user() {
+        let mut store = HashmapUserStore::default();
+        let user = User {
+            id: UserId::default(),
+            email: Email::parse("test@email.com".to_string()).unwrap(),
+            password: Password::parse("password123".to_string()).unwrap(),
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
+            deleted_at: None,
+            status: UserStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+        };
+        assert_eq!(store.add_user(user).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_get_user() {
+        let mut store = HashmapUserStore::default();
+        let user = User {
+            id: UserId::default(),
+            email: Email::parse("test@gmail.com".to_string()).unwrap(),
+            password: Password::parse("password123".to_string()).unwrap(),
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
+            deleted_at: None,
+            status: UserStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+        };
+        let _ = store.add_user(user).await;
+        if let Ok(user) = store.get_user(&Email::parse("test@gmail.com".to_string()).unwrap()).await {
+            assert_eq!(user.email, Email::parse("test@gmail.com".to_string()).unwrap());
+            assert_eq!(user.password, Password::parse("password123".to_string()).unwrap());
+            assert_eq!(user.two_fa_method, TwoFAMethod::None);
+        } else {
+            panic!("User not found");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_user() {
+        let mut store = HashmapUserStore::default();
+        let user = User {
+            id: UserId::default(),
+            email: Email::parse("test@gmail.com".to_string()).unwrap(),
+            password: Password::parse("password123".to_string()).unwrap(),
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
+            deleted_at: None,
+            status: UserStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+        };
+
+        let _ = store.add_user(user).await;
+        assert_eq!(store.validate_user(&Email::parse("test@gmail.com".to_string()).unwrap(), &Password::parse("password123".to_string()).unwrap()).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@gmail.com".to_string()).unwrap();
+        let user = User {
+            id: UserId::default(),
+            email: email.clone(),
+            password: Password::parse("password123".to_string()).unwrap(),
+            two_fa_method: TwoFAMethod::None,
+            email_verified: false,
+            deleted_at: None,
+            status: UserStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+        };
+        let _ = store.add_user(user).await;
+
+        let new_password = Password::parse("newpassword123".to_string()).unwrap();
+        assert_eq!(store.update_password(&email, new_password.clone()).await, Ok(()));
+        assert_eq!(store.validate_user(&email, &new_password).await, Ok(()));
+        assert_eq!(
+            store.validate_user(&email, &Password::parse("password123".to_string()).unwrap()).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_password_unknown_user() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@gmail.com".to_string()).unwrap();
+        let password = Password::parse("password123".to_string()).unwrap();
+        assert_eq!(store.update_password(&email, password).await, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_mark_email_verified() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@gmail.com".to_string()).unwrap();
+        let user = User::new(email.clone(), Password::parse("password123".to_string()).unwrap(), TwoFAMethod::None);
+        let _ = store.add_user(user).await;
+
+        assert!(!store.get_user(&email).await.unwrap().email_verified);
+        assert_eq!(store.mark_email_verified(&email).await, Ok(()));
+        assert!(store.get_user(&email).await.unwrap().email_verified);
+    }
+
+    #[tokio::test]
+    async fn test_record_verification_email_sent_is_throttled() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@gmail.com".to_string()).unwrap();
+        let user = User::new(email.clone(), Password::parse("password123".to_string()).unwrap(), TwoFAMethod::None);
+        let _ = store.add_user(user).await;
+
+        assert_eq!(store.record_verification_email_sent(&email, 60).await, Ok(()));
+        assert_eq!(
+            store.record_verification_email_sent(&email, 60).await,
+            Err(UserStoreError::VerificationEmailThrottled)
+        );
+        assert_eq!(store.record_verification_email_sent(&email, 0).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@gmail.com".to_string()).unwrap();
+        let user = User::new(email.clone(), Password::parse("password123".to_string()).unwrap(), TwoFAMethod::Email);
+        let _ = store.add_user(user).await;
+
+        assert_eq!(store.set_two_fa_method(&email, TwoFAMethod::Totp).await, Ok(()));
+        assert_eq!(store.get_user(&email).await.unwrap().two_fa_method, TwoFAMethod::Totp);
+    }
+
+    #[tokio::test]
+    async fn test_update_email() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let new_email = Email::parse("new@email.com".to_string()).unwrap();
+        let user = User::new(email.clone(), Password::parse("password123".to_string()).unwrap(), TwoFAMethod::None);
+        let id = user.id.clone();
+        store.add_user(user).await.unwrap();
+
+        store.update_email(&id, &new_email).await.unwrap();
+        assert!(matches!(store.get_user(&email).await, Err(UserStoreError::UserNotFound)));
+        assert_eq!(store.get_user(&new_email).await.unwrap().id, id);
+        assert_eq!(store.get_user_by_id(&id).await.unwrap().email, new_email);
+    }
+
+    #[tokio::test]
+    async fn test_update_email_to_taken_email() {
+        let mut store = HashmapUserStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let taken = Email::parse("taken@email.com".to_string()).unwrap();
+        let password = Password::parse("password123".to_string()).unwrap();
+        let user = User::new(email.clone(), password.clone(), TwoFAMethod::None);
+        let id = user.id.clone();
+        store.add_user(user).await.unwrap();
+        store.add_user(User::new(taken.clone(), password, TwoFAMethod::None)).await.unwrap();
+
+        assert_eq!(store.update_email(&id, &taken).await.unwrap_err(), UserStoreError::UserAlreadyExists);
+        assert_eq!(store.get_user(&email).await.unwrap().id, id);
+    }
+
+    #[tokio::test]
+    async fn test_purge_deleted_users() {
+        let mut store = HashmapUserStore::default();
+        let password = Password::parse("password123".to_string()).unwrap();
+        let deleted = User::new(Email::parse("deleted@email.com".to_string()).unwrap(), password.clone(), TwoFAMethod::None);
+        let restored = User::new(Email::parse("restored@email.com".to_string()).unwrap(), password.clone(), TwoFAMethod::None);
+        let active = User::new(Email::parse("active@email.com".to_string()).unwrap(), password, TwoFAMethod::None);
+        let (deleted_id, restored_id, active_id) = (deleted.id.clone(), restored.id.clone(), active.id.clone());
+        for user in [deleted, restored, active] {
+            store.add_user(user).await.unwrap();
+        }
+
+        store.mark_deleted(&deleted_id).await.unwrap();
+        store.mark_deleted(&restored_id).await.unwrap();
+        store.restore_user(&restored_id).await.unwrap();
+        assert!(store.get_user_by_id(&deleted_id).await.unwrap().is_pending_deletion());
+        assert!(!store.get_user_by_id(&restored_id).await.unwrap().is_pending_deletion());
+
+        // Still within the grace period
+        assert!(store.purge_deleted_users(60).await.unwrap().is_empty());
+
+        let purged = store.purge_deleted_users(0).await.unwrap();
+        assert_eq!(purged.iter().map(|user| user.id.clone()).collect::<Vec<_>>(), vec![deleted_id.clone()]);
+        assert!(matches!(store.get_user_by_id(&deleted_id).await, Err(UserStoreError::UserNotFound)));
+        assert!(store.get_user_by_id(&restored_id).await.is_ok());
+        assert!(store.get_user_by_id(&active_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_list_users() {
+        let mut store = HashmapUserStore::default();
+        let password = Password::parse("password123".to_string()).unwrap();
+        for email in ["carol@example.com", "alice@example.com", "bob@other.com"] {
+            let user = User::new(Email::parse(email.to_string()).unwrap(), password.clone(), TwoFAMethod::None);
+            store.add_user(user).await.unwrap();
+        }
+        let emails = |users: Vec<User>| users.into_iter().map(|user| user.email.as_ref().to_owned()).collect::<Vec<_>>();
+
+        let (users, total) = store.list_users(None, 2, 0).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(emails(users), vec!["alice@example.com", "bob@other.com"]);
+
+        let (users, _) = store.list_users(None, 2, 2).await.unwrap();
+        assert_eq!(emails(users), vec!["carol@example.com"]);
+
+        let (users, total) = store.list_users(Some("EXAMPLE"), 10, 0).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(emails(users), vec!["alice@example.com", "carol@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_set_status() {
+        let mut store = HashmapUserStore::default();
+        let user = User::new(Email::parse("test@email.com".to_string()).unwrap(), Password::parse("password123".to_string()).unwrap(), TwoFAMethod::None);
+        let id = user.id.clone();
+        store.add_user(user).await.unwrap();
+        assert!(store.get_user_by_id(&id).await.unwrap().is_active());
+
+        store.set_status(&id, UserStatus::Suspended, Some("Chargeback".to_string())).await.unwrap();
+        let user = store.get_user_by_id(&id).await.unwrap();
+        assert!(!user.is_active());
+        assert_eq!(user.status, UserStatus::Suspended);
+        assert_eq!(user.status_reason.as_deref(), Some("Chargeback"));
+        assert!(user.status_changed_at.is_some());
+
+        store.set_status(&id, UserStatus::Active, None).await.unwrap();
+        let user = store.get_user_by_id(&id).await.unwrap();
+        assert!(user.is_active());
+        assert_eq!(user.status_reason, None);
+        assert_eq!(store.set_status(&UserId::default(), UserStatus::Locked, None).await, Err(UserStoreError::UserNotFound));
+    }
+
+    // HashsetBannedTokenStore tests
+    #[tokio::test]
+    async fn test_store_token() {
+        let mut store = HashsetBannedTokenStore::default();
+        store.store_token("test_token".to_string()).await.unwrap();
+        assert!(store.is_token_banned("test_token").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_is_token_banned() {
+        let store = HashsetBannedTokenStore::default();
+        assert!(!store.is_token_banned("test_token").await.unwrap());
     }
 
     #[tokio::test]
@@ -371,6 +1732,552 @@ mod tests {
         assert_eq!(store.get_code(&email).await.unwrap_err(), TwoFACodeStoreError::LoginAttemptIdNotFound);
     }
 
+    #[tokio::test]
+    async fn test_code_is_removed_after_too_many_failed_attempts() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+        store.add_code(&email, &login_attempt_id, &code).await.unwrap();
+
+        // Failures for another login attempt don't count against this one
+        assert_eq!(
+            store.record_failed_attempt(&email, &LoginAttemptId::default()).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+
+        for _ in 1..MAX_TWO_FA_CODE_ATTEMPTS {
+            store.record_failed_attempt(&email, &login_attempt_id).await.unwrap();
+        }
+        assert_eq!(store.get_code(&email).await.unwrap(), (login_attempt_id.clone(), code.clone()));
+
+        assert_eq!(
+            store.record_failed_attempt(&email, &login_attempt_id).await.unwrap_err(),
+            TwoFACodeStoreError::TooManyAttempts
+        );
+        assert_eq!(store.get_code(&email).await.unwrap_err(), TwoFACodeStoreError::LoginAttemptIdNotFound);
+
+        // A new login starts with a clean count
+        store.add_code(&email, &login_attempt_id, &code).await.unwrap();
+        store.record_failed_attempt(&email, &login_attempt_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replace_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        store.add_code(&email, &login_attempt_id, &TwoFACode::default()).await.unwrap();
+
+        assert_eq!(
+            store.replace_code(&email, &LoginAttemptId::default(), &TwoFACode::default(), 0, 2).await.unwrap_err(),
+            TwoFACodeStoreError::LoginAttemptIdNotFound
+        );
+        assert_eq!(
+            store.replace_code(&email, &login_attempt_id, &TwoFACode::default(), 60, 2).await.unwrap_err(),
+            TwoFACodeStoreError::ResendThrottled
+        );
+
+        for _ in 0..2 {
+            let code = TwoFACode::default();
+            store.replace_code(&email, &login_attempt_id, &code, 0, 2).await.unwrap();
+            assert_eq!(store.get_code(&email).await.unwrap(), (login_attempt_id.clone(), code));
+        }
+        assert_eq!(
+            store.replace_code(&email, &login_attempt_id, &TwoFACode::default(), 0, 2).await.unwrap_err(),
+            TwoFACodeStoreError::TooManyResends
+        );
+    }
+
+    // HashmapPasswordResetTokenStore tests
+    #[tokio::test]
+    async fn test_add_password_reset_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let token = PasswordResetToken::default();
+        store.add_token(&email, &token).await.unwrap();
+        let hash = store.get_token_hash(&email).await.unwrap();
+        assert_ne!(hash, token.as_ref());
+        assert!(token.matches_hash(&hash));
+        assert!(!PasswordResetToken::default().matches_hash(&hash));
+    }
+
+    #[tokio::test]
+    async fn test_remove_password_reset_token() {
+        let mut store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let token = PasswordResetToken::default();
+        store.add_token(&email, &token).await.unwrap();
+        store.remove_token(&email).await.unwrap();
+        assert_eq!(store.get_token_hash(&email).await.unwrap_err(), PasswordResetTokenStoreError::TokenNotFound);
+    }
+
+    #[test]
+    fn test_password_reset_token_parse() {
+        let token = PasswordResetToken::default();
+        assert!(PasswordResetToken::parse(token.as_ref().to_string()).is_ok());
+        assert!(PasswordResetToken::parse("too-short".to_string()).is_err());
+        assert!(PasswordResetToken::parse("!".repeat(32)).is_err());
+    }
+
+    // HashmapTotpSecretStore tests
+    #[tokio::test]
+    async fn test_pending_totp_secret_is_not_active() {
+        let mut store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let secret = TotpSecret::default();
+        store.set_pending_secret(&email, &secret).await.unwrap();
+        assert_eq!(store.get_pending_secret(&email).await.unwrap(), secret);
+        assert_eq!(store.get_secret(&email).await.unwrap_err(), TotpSecretStoreError::SecretNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_activate_pending_totp_secret() {
+        let mut store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let secret = TotpSecret::default();
+        store.set_pending_secret(&email, &secret).await.unwrap();
+        store.activate_pending_secret(&email).await.unwrap();
+        assert_eq!(store.get_secret(&email).await.unwrap(), secret);
+        assert_eq!(store.get_pending_secret(&email).await.unwrap_err(), TotpSecretStoreError::SecretNotFound);
+        assert_eq!(store.activate_pending_secret(&email).await.unwrap_err(), TotpSecretStoreError::SecretNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_totp_time_step_is_single_use() {
+        let mut store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        store.record_used_step(&email, 100).await.unwrap();
+        assert_eq!(store.record_used_step(&email, 100).await.unwrap_err(), TotpSecretStoreError::CodeAlreadyUsed);
+        assert_eq!(store.record_used_step(&email, 99).await.unwrap_err(), TotpSecretStoreError::CodeAlreadyUsed);
+        store.record_used_step(&email, 101).await.unwrap();
+    }
+
+    // HashmapRecoveryCodeStore tests
+    #[tokio::test]
+    async fn test_recovery_code_is_single_use() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let codes = RecoveryCode::generate_set();
+        store.replace_codes(&email, &codes).await.unwrap();
+        assert_eq!(store.remaining_codes(&email).await.unwrap(), codes.len());
+
+        store.use_code(&email, &codes[0]).await.unwrap();
+        assert_eq!(store.use_code(&email, &codes[0]).await.unwrap_err(), RecoveryCodeStoreError::CodeNotFound);
+        assert_eq!(store.remaining_codes(&email).await.unwrap(), codes.len() - 1);
+    }
+
+    #[tokio::test]
+    async fn test_replace_recovery_codes_invalidates_old_codes() {
+        let mut store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        assert_eq!(store.remaining_codes(&email).await.unwrap(), 0);
+
+        let old_codes = RecoveryCode::generate_set();
+        store.replace_codes(&email, &old_codes).await.unwrap();
+        let new_codes = RecoveryCode::generate_set();
+        store.replace_codes(&email, &new_codes).await.unwrap();
+
+        assert_eq!(store.use_code(&email, &old_codes[0]).await.unwrap_err(), RecoveryCodeStoreError::CodeNotFound);
+        assert!(store.use_code(&email, &new_codes[0]).await.is_ok());
+    }
+
+    // HashmapRefreshTokenStore tests
+    #[tokio::test]
+    async fn test_rotate_refresh_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let session_id = SessionId::default();
+        let token = RefreshToken::default();
+        store.create_family(&email, &session_id, &token, 60).await.unwrap();
+
+        let new_token = RefreshToken::default();
+        let expected = (email.clone(), session_id.clone());
+        assert_eq!(store.rotate_token(&token, &new_token, 60).await.unwrap(), expected);
+        assert_eq!(store.rotate_token(&new_token, &RefreshToken::default(), 60).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_reused_refresh_token_revokes_family() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let session_id = SessionId::default();
+        let token = RefreshToken::default();
+        store.create_family(&email, &session_id, &token, 60).await.unwrap();
+
+        let new_token = RefreshToken::default();
+        store.rotate_token(&token, &new_token, 60).await.unwrap();
+
+        assert_eq!(
+            store.rotate_token(&token, &RefreshToken::default(), 60).await.unwrap_err(),
+            RefreshTokenStoreError::TokenReused(email, session_id)
+        );
+        assert_eq!(
+            store.rotate_token(&new_token, &RefreshToken::default(), 60).await.unwrap_err(),
+            RefreshTokenStoreError::TokenRevoked
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_refresh_token() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let token = RefreshToken::default();
+        store.create_family(&email, &SessionId::default(), &token, 0).await.unwrap();
+        assert_eq!(
+            store.rotate_token(&token, &RefreshToken::default(), 60).await.unwrap_err(),
+            RefreshTokenStoreError::TokenExpired
+        );
+        assert_eq!(
+            store.rotate_token(&RefreshToken::default(), &RefreshToken::default(), 60).await.unwrap_err(),
+            RefreshTokenStoreError::TokenNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_refresh_token_families() {
+        let mut store = HashmapRefreshTokenStore::default();
+        let email = Email::parse("test@email.com".to_string()).unwrap();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        store.create_family(&email, &SessionId::default(), &first, 60).await.unwrap();
+        store.create_family(&email, &SessionId::default(), &second, 60).await.unwrap();
+
+        store.revoke_all_families(&email).await.unwrap();
+
+        for token in [first, second] {
+            assert_eq!(
+                store.rotate_token(&token, &RefreshToken::default(), 60).await.unwrap_err(),
+                RefreshTokenStoreError::TokenRevoked
+            );
+        }
+    }
+
+    // HashmapSessionStore tests
+    #[tokio::test]
+    async fn test_add_and_get_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let other = UserId::default();
+        let session = Session::new(user_id.clone(), Some("Firefox".to_string()), Some("127.0.0.1".to_string()));
+        store.add_session(&session, 60).await.unwrap();
+        store.add_session(&Session::new(other, None, None), 60).await.unwrap();
+
+        assert_eq!(store.get_sessions(&user_id).await.unwrap(), vec![session.clone()]);
+        assert!(store.is_session_active(&user_id, &session.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_expired_session_is_inactive() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let session = Session::new(user_id.clone(), None, None);
+        store.add_session(&session, 0).await.unwrap();
+
+        assert!(!store.is_session_active(&user_id, &session.id).await.unwrap());
+        assert!(store.get_sessions(&user_id).await.unwrap().is_empty());
+        assert_eq!(store.extend_session(&session.id, 60).await.unwrap_err(), SessionStoreError::SessionNotFound);
+    }
+
+    #[tokio::test]
+    async fn test_revoke_session_only_for_its_owner() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let other = UserId::default();
+        let session = Session::new(user_id.clone(), None, None);
+        store.add_session(&session, 60).await.unwrap();
+
+        assert_eq!(store.revoke_session(&other, &session.id).await.unwrap_err(), SessionStoreError::SessionNotFound);
+        assert!(!store.is_session_active(&other, &session.id).await.unwrap());
+
+        store.revoke_session(&user_id, &session.id).await.unwrap();
+        assert!(!store.is_session_active(&user_id, &session.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_revoke_all_sessions() {
+        let mut store = HashmapSessionStore::default();
+        let user_id = UserId::default();
+        let first = Session::new(user_id.clone(), None, None);
+        let second = Session::new(user_id.clone(), None, None);
+        store.add_session(&first, 60).await.unwrap();
+        store.add_session(&second, 60).await.unwrap();
+
+        store.revoke_all_sessions(&user_id).await.unwrap();
+        assert!(store.get_sessions(&user_id).await.unwrap().is_empty());
+    }
+
+    // HashmapTokenVersionStore tests
+    #[tokio::test]
+    async fn test_bump_token_version() {
+        let mut store = HashmapTokenVersionStore::default();
+        let user_id = UserId::default();
+        let other = UserId::default();
+        assert_eq!(store.get_version(&user_id).await.unwrap(), 0);
+
+        assert_eq!(store.bump_version(&user_id).await.unwrap(), 1);
+        assert_eq!(store.bump_version(&user_id).await.unwrap(), 2);
+        assert_eq!(store.get_version(&user_id).await.unwrap(), 2);
+        assert_eq!(store.get_version(&other).await.unwrap(), 0);
+    }
+
+    // HashmapFailedAttemptStore tests
+    const TEST_LOCKOUT_POLICY: LockoutPolicy = LockoutPolicy {
+        max_failures: 3,
+        window_seconds: 60,
+        base_lockout_seconds: 10,
+        max_lockout_seconds: 25,
+        backoff_reset_seconds: 3600,
+    };
+
+    #[test]
+    fn test_lockout_backs_off_exponentially() {
+        let lockouts: Vec<i64> = (1..=4).map(|n| TEST_LOCKOUT_POLICY.lockout_seconds(n)).collect();
+        assert_eq!(lockouts, vec![10, 20, 25, 25]);
+        assert_eq!(TEST_LOCKOUT_POLICY.lockout_seconds(u32::MAX), 25);
+    }
+
+    #[tokio::test]
+    async fn test_failed_attempts_lock_out_key() {
+        let mut store = HashmapFailedAttemptStore::default();
+
+        assert_eq!(store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap(), None);
+        assert_eq!(store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap(), None);
+        assert_eq!(store.lockout_remaining("key").await.unwrap(), None);
+
+        assert_eq!(store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap(), Some(10));
+        assert!(store.lockout_remaining("key").await.unwrap().is_some_and(|remaining| remaining <= 10));
+        assert_eq!(store.lockout_remaining("other").await.unwrap(), None);
+
+        // The next lockout lasts twice as long
+        for _ in 0..2 {
+            store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap();
+        }
+        assert_eq!(store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap(), Some(20));
+    }
+
+    #[tokio::test]
+    async fn test_expired_failed_attempts_are_evicted() {
+        let mut store = HashmapFailedAttemptStore::default();
+        let policy = LockoutPolicy { window_seconds: 0, backoff_reset_seconds: 0, ..TEST_LOCKOUT_POLICY };
+
+        store.record_failure("key", &policy).await.unwrap();
+        store.record_failure("other", &policy).await.unwrap();
+
+        assert!(!store.records.contains_key("key"));
+        assert!(store.records.contains_key("other"));
+
+        // Keys that are still locked out are kept
+        for _ in 0..3 {
+            store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap();
+        }
+        store.record_failure("other", &policy).await.unwrap();
+        assert!(store.records.contains_key("key"));
+    }
+
+    #[tokio::test]
+    async fn test_clear_failures() {
+        let mut store = HashmapFailedAttemptStore::default();
+
+        for _ in 0..2 {
+            store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap();
+        }
+        store.clear_failures("key").await.unwrap();
+
+        assert_eq!(store.record_failure("key", &TEST_LOCKOUT_POLICY).await.unwrap(), None);
+        assert_eq!(store.lockout_remaining("key").await.unwrap(), None);
+    }
+
+    // HashmapWebauthnCredentialStore tests
+    #[tokio::test]
+    async fn test_add_and_get_webauthn_credentials() {
+        let mut store = HashmapWebauthnCredentialStore::default();
+        let user_id = UserId::default();
+        let credential = WebauthnCredential::new("credential".to_string(), user_id.clone(), vec![4; 65], 0);
+        store.add_credential(&credential).await.unwrap();
+        store
+            .add_credential(&WebauthnCredential::new("other".to_string(), UserId::default(), vec![4; 65], 0))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_credential("credential").await.unwrap(), credential);
+        assert_eq!(store.get_credentials(&user_id).await.unwrap(), vec![credential.clone()]);
+        assert_eq!(
+            store.add_credential(&credential).await.unwrap_err(),
+            WebauthnCredentialStoreError::CredentialAlreadyExists
+        );
+        assert_eq!(
+            store.get_credential("unknown").await.unwrap_err(),
+            WebauthnCredentialStoreError::CredentialNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_webauthn_sign_count() {
+        let mut store = HashmapWebauthnCredentialStore::default();
+        let credential = WebauthnCredential::new("credential".to_string(), UserId::default(), vec![4; 65], 0);
+        store.add_credential(&credential).await.unwrap();
+
+        store.update_sign_count("credential", 7).await.unwrap();
+        assert_eq!(store.get_credential("credential").await.unwrap().sign_count, 7);
+        assert_eq!(
+            store.update_sign_count("unknown", 1).await.unwrap_err(),
+            WebauthnCredentialStoreError::CredentialNotFound
+        );
+    }
+
+    // HashmapOAuthClientStore tests
+    #[tokio::test]
+    async fn test_add_and_get_oauth_client() {
+        let mut store = HashmapOAuthClientStore::default();
+        let client = OAuthClient::new(
+            "app".to_string(),
+            "App".to_string(),
+            vec!["https://app.example/callback".to_string()],
+        );
+        store.add_client(&client).await.unwrap();
+
+        assert_eq!(store.get_client("app").await.unwrap(), client);
+        assert_eq!(store.add_client(&client).await.unwrap_err(), OAuthClientStoreError::ClientAlreadyExists);
+        assert_eq!(store.get_client("unknown").await.unwrap_err(), OAuthClientStoreError::ClientNotFound);
+    }
+
+    // HashmapIdentityProviderStore tests
+    #[tokio::test]
+    async fn test_add_and_get_identity_provider() {
+        let mut store = HashmapIdentityProviderStore::default();
+        let provider = IdentityProvider::new(
+            "acme".to_string(),
+            "https://sso.acme.example".to_string(),
+            "client".to_string(),
+            "secret".to_string(),
+        )
+        .unwrap();
+        store.add_provider(&provider).await.unwrap();
+
+        assert_eq!(store.get_provider("acme").await.unwrap(), provider);
+        assert_eq!(
+            store.add_provider(&provider).await.unwrap_err(),
+            IdentityProviderStoreError::ProviderAlreadyExists
+        );
+        assert_eq!(
+            store.get_provider("unknown").await.unwrap_err(),
+            IdentityProviderStoreError::ProviderNotFound
+        );
+    }
+
+    // HashmapRoleStore tests
+    #[tokio::test]
+    async fn test_grant_and_revoke_roles() {
+        let mut store = HashmapRoleStore::default();
+        let user_id = UserId::default();
+        let admin = Role::parse("admin".to_string()).unwrap();
+        let support = Role::parse("support".to_string()).unwrap();
+        store.define_role(&admin, &["roles:manage".to_string(), "users:read".to_string()]).await.unwrap();
+        store.define_role(&support, &["users:read".to_string()]).await.unwrap();
+
+        assert_eq!(store.get_user_roles(&user_id).await.unwrap(), UserRoles::default());
+
+        store.grant_role(&user_id, &admin).await.unwrap();
+        store.grant_role(&user_id, &support).await.unwrap();
+        store.grant_role(&user_id, &support).await.unwrap();
+        let roles = store.get_user_roles(&user_id).await.unwrap();
+        assert_eq!(roles.roles, vec!["admin", "support"]);
+        assert_eq!(roles.permissions, vec!["roles:manage", "users:read"]);
+
+        store.revoke_role(&user_id, &admin).await.unwrap();
+        let roles = store.get_user_roles(&user_id).await.unwrap();
+        assert_eq!(roles.roles, vec!["support"]);
+        assert_eq!(roles.permissions, vec!["users:read"]);
+
+        assert_eq!(store.revoke_role(&user_id, &admin).await.unwrap_err(), RoleStoreError::RoleNotGranted);
+        let unknown = Role::parse("unknown".to_string()).unwrap();
+        assert_eq!(store.grant_role(&user_id, &unknown).await.unwrap_err(), RoleStoreError::RoleNotFound);
+    }
+
+    // HashmapAuditLog tests
+    #[tokio::test]
+    async fn test_append_and_query_audit_log() {
+        let mut log = HashmapAuditLog::default();
+        let user = User::new(Email::parse("test@email.com".to_string()).unwrap(), Password::parse("password123".to_string()).unwrap(), TwoFAMethod::None);
+        log.append(AuditEvent::new(AuditEventType::SignedUp).user(&user)).await.unwrap();
+        log.append(AuditEvent::new(AuditEventType::LoginFailed).user(&user).details("incorrect_credentials")).await.unwrap();
+        let last = log.append(AuditEvent::new(AuditEventType::LoginSucceeded).user(&user)).await.unwrap();
+        log.append(AuditEvent::new(AuditEventType::LoginFailed).details("unknown_account")).await.unwrap();
+        assert_eq!(last.id, 3);
+
+        // Newest first
+        let (records, total) = log.query(&AuditLogFilter::default(), 2, 0).await.unwrap();
+        assert_eq!(total, 4);
+        assert_eq!(records.iter().map(|record| record.id).collect::<Vec<_>>(), vec![4, 3]);
+
+        let filter = AuditLogFilter {
+            event_type: Some(AuditEventType::LoginFailed),
+            user_id: Some(user.id.clone()),
+            ..AuditLogFilter::default()
+        };
+        let (records, total) = log.query(&filter, 10, 0).await.unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(records[0].event.details.as_deref(), Some("incorrect_credentials"));
+
+        assert_eq!(log.verify().await.unwrap(), ChainVerification { checked: 4, first_invalid_id: None });
+    }
+
+    #[tokio::test]
+    async fn test_verify_detects_tampered_audit_log() {
+        let mut log = HashmapAuditLog::default();
+        for _ in 0..3 {
+            log.append(AuditEvent::new(AuditEventType::Logout)).await.unwrap();
+        }
+        log.records[1].event.event_type = AuditEventType::LoginSucceeded;
+        assert_eq!(log.verify().await.unwrap(), ChainVerification { checked: 1, first_invalid_id: Some(2) });
+    }
+
+    // HashmapWebhookStore tests
+    #[tokio::test]
+    async fn test_webhook_subscriptions() {
+        let mut store = HashmapWebhookStore::default();
+        let subscription = WebhookSubscription::new("https://example.com/hook".to_string(), vec![WebhookEventType::UserLoggedIn]);
+        store.add_subscription(&subscription).await.unwrap();
+        assert_eq!(store.get_subscription(&subscription.id).await.unwrap(), subscription);
+        assert_eq!(store.list_subscriptions().await.unwrap(), vec![subscription.clone()]);
+
+        let delivery = WebhookDelivery::new(subscription.id, Uuid::new_v4(), WebhookEventType::UserLoggedIn, "{}".to_string());
+        store.add_deliveries(&[delivery]).await.unwrap();
+
+        // Deliveries go with their subscription
+        store.delete_subscription(&subscription.id).await.unwrap();
+        assert_eq!(store.get_subscription(&subscription.id).await.unwrap_err(), WebhookStoreError::SubscriptionNotFound);
+        assert_eq!(store.delete_subscription(&subscription.id).await.unwrap_err(), WebhookStoreError::SubscriptionNotFound);
+        assert_eq!(store.list_deliveries(&subscription.id, 10, 0).await.unwrap(), (vec![], 0));
+    }
+
+    #[tokio::test]
+    async fn test_claim_due_deliveries() {
+        let mut store = HashmapWebhookStore::default();
+        let subscription_id = Uuid::new_v4();
+        let deliveries: Vec<WebhookDelivery> = (0..3)
+            .map(|_| WebhookDelivery::new(subscription_id, Uuid::new_v4(), WebhookEventType::UserSignedUp, "{}".to_string()))
+            .collect();
+        store.add_deliveries(&deliveries).await.unwrap();
+
+        // Claimed deliveries aren't handed out again while their lease lasts
+        let claimed = store.claim_due_deliveries(2, 60).await.unwrap();
+        assert_eq!(claimed.len(), 2);
+        let claimed_again = store.claim_due_deliveries(10, 60).await.unwrap();
+        assert_eq!(claimed_again.len(), 1);
+        assert!(store.claim_due_deliveries(10, 60).await.unwrap().is_empty());
+
+        // Finished deliveries are never handed out again
+        let mut delivery = claimed_again[0].clone();
+        delivery.record_attempt(Ok(200), Utc::now());
+        store.update_delivery(&delivery).await.unwrap();
+
+        let (page, total) = store.list_deliveries(&subscription_id, 1, 0).await.unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(page[0].status, DeliveryStatus::Succeeded);
+    }
+
     // BannedTokenStoreType tests
     #[tokio::test]
     async fn test_banned_token_store_type_hashset() {
@@ -1,4 +1,16 @@
 pub mod data_store;
+pub mod postgres_audit_log;
+pub mod postgres_oauth_client_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_refresh_token_store;
+pub mod postgres_role_store;
+pub mod postgres_session_store;
+pub mod postgres_token_version_store;
+pub mod postgres_totp_secret_store;
 pub mod postgres_user_store;
+pub mod postgres_webauthn_credential_store;
+pub mod postgres_webhook_store;
 pub mod redis_banned_token_store;
-pub mod redis_two_fa_code_store;
\ No newline at end of file
+pub mod redis_failed_attempt_store;
+pub mod redis_two_fa_code_store;
+pub mod redis_password_reset_token_store;
\ No newline at end of file
@@ -0,0 +1,208 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{AuditLog, AuditLogError};
+use crate::domain::audit::{AuditEvent, AuditEventType, AuditLogFilter, AuditRecord, ChainVerification, ChainVerifier};
+use crate::domain::user::UserId;
+
+// How many records `verify` reads at a time
+const VERIFY_BATCH_SIZE: i64 = 1000;
+
+pub struct PostgresAuditLog {
+    pool: PgPool,
+}
+
+impl PostgresAuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for PostgresAuditLog {
+    #[tracing::instrument(name = "Appending to audit log in PostgreSQL", skip_all)]
+    async fn append(&mut self, event: AuditEvent) -> Result<AuditRecord, AuditLogError> {
+        let mut transaction = self.pool.begin().await.map_err(|_| AuditLogError::UnexpectedError)?;
+
+        // Appends are serialized, also across instances, so every record chains
+        // onto the one written right before it. Reads aren't blocked.
+        sqlx::query!("LOCK TABLE audit_log IN EXCLUSIVE MODE")
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| AuditLogError::UnexpectedError)?;
+
+        let previous = sqlx::query_as!(
+            AuditRow,
+            r#"
+            SELECT id, occurred_at, event_type, user_id, email, actor, ip_address, user_agent, details, prev_hash, hash
+            FROM audit_log
+            ORDER BY id DESC
+            LIMIT 1
+            "#
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|_| AuditLogError::UnexpectedError)?
+        .map(AuditRecord::try_from)
+        .transpose()?;
+
+        let record = AuditRecord::new(previous.as_ref(), Utc::now(), event);
+        let event = &record.event;
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log
+                (id, occurred_at, event_type, user_id, email, actor, ip_address, user_agent, details, prev_hash, hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            record.id,
+            record.occurred_at,
+            event.event_type.as_str(),
+            event.user_id.as_ref().map(UserId::as_ref),
+            event.email,
+            event.actor,
+            event.ip_address,
+            event.user_agent,
+            event.details,
+            record.prev_hash,
+            record.hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| AuditLogError::UnexpectedError)?;
+
+        transaction.commit().await.map_err(|_| AuditLogError::UnexpectedError)?;
+        Ok(record)
+    }
+
+    #[tracing::instrument(name = "Querying audit log in PostgreSQL", skip_all)]
+    async fn query(&self, filter: &AuditLogFilter, limit: i64, offset: i64) -> Result<(Vec<AuditRecord>, i64), AuditLogError> {
+        let event_type = filter.event_type.map(|event_type| event_type.as_str());
+        let user_id = filter.user_id.as_ref().map(UserId::as_ref);
+
+        let rows = sqlx::query_as!(
+            AuditRow,
+            r#"
+            SELECT id, occurred_at, event_type, user_id, email, actor, ip_address, user_agent, details, prev_hash, hash
+            FROM audit_log
+            WHERE ($1::TEXT IS NULL OR event_type = $1)
+              AND ($2::UUID IS NULL OR user_id = $2)
+              AND ($3::TEXT IS NULL OR email = $3)
+              AND ($4::TEXT IS NULL OR actor = $4)
+              AND ($5::TIMESTAMPTZ IS NULL OR occurred_at >= $5)
+              AND ($6::TIMESTAMPTZ IS NULL OR occurred_at < $6)
+            ORDER BY id DESC
+            LIMIT $7 OFFSET $8
+            "#,
+            event_type,
+            user_id,
+            filter.email,
+            filter.actor,
+            filter.since,
+            filter.until,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| AuditLogError::UnexpectedError)?;
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!"
+            FROM audit_log
+            WHERE ($1::TEXT IS NULL OR event_type = $1)
+              AND ($2::UUID IS NULL OR user_id = $2)
+              AND ($3::TEXT IS NULL OR email = $3)
+              AND ($4::TEXT IS NULL OR actor = $4)
+              AND ($5::TIMESTAMPTZ IS NULL OR occurred_at >= $5)
+              AND ($6::TIMESTAMPTZ IS NULL OR occurred_at < $6)
+            "#,
+            event_type,
+            user_id,
+            filter.email,
+            filter.actor,
+            filter.since,
+            filter.until
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| AuditLogError::UnexpectedError)?;
+
+        let records = rows.into_iter().map(AuditRecord::try_from).collect::<Result<_, _>>()?;
+        Ok((records, total))
+    }
+
+    #[tracing::instrument(name = "Verifying audit log in PostgreSQL", skip_all)]
+    async fn verify(&self) -> Result<ChainVerification, AuditLogError> {
+        let mut verifier = ChainVerifier::default();
+        let mut after_id = 0;
+
+        loop {
+            let rows = sqlx::query_as!(
+                AuditRow,
+                r#"
+                SELECT id, occurred_at, event_type, user_id, email, actor, ip_address, user_agent, details, prev_hash, hash
+                FROM audit_log
+                WHERE id > $1
+                ORDER BY id
+                LIMIT $2
+                "#,
+                after_id,
+                VERIFY_BATCH_SIZE
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|_| AuditLogError::UnexpectedError)?;
+
+            if rows.is_empty() {
+                return Ok(verifier.finish(None));
+            }
+
+            for row in rows {
+                // Rows that can't even be read back are as broken as ones that don't match their hash
+                let id = row.id;
+                let intact = AuditRecord::try_from(row).is_ok_and(|record| verifier.check(&record));
+                if !intact {
+                    return Ok(verifier.finish(Some(id)));
+                }
+                after_id = id;
+            }
+        }
+    }
+}
+
+struct AuditRow {
+    id: i64,
+    occurred_at: DateTime<Utc>,
+    event_type: String,
+    user_id: Option<uuid::Uuid>,
+    email: Option<String>,
+    actor: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    details: Option<String>,
+    prev_hash: String,
+    hash: String,
+}
+
+impl TryFrom<AuditRow> for AuditRecord {
+    type Error = AuditLogError;
+
+    fn try_from(row: AuditRow) -> Result<Self, Self::Error> {
+        Ok(AuditRecord {
+            id: row.id,
+            occurred_at: row.occurred_at,
+            event: AuditEvent {
+                event_type: AuditEventType::parse(&row.event_type).map_err(|_| AuditLogError::UnexpectedError)?,
+                user_id: row.user_id.map(UserId::from),
+                email: row.email,
+                actor: row.actor,
+                ip_address: row.ip_address,
+                user_agent: row.user_agent,
+                details: row.details,
+            },
+            prev_hash: row.prev_hash,
+            hash: row.hash,
+        })
+    }
+}
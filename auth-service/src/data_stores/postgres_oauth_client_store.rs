@@ -0,0 +1,49 @@
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{OAuthClientStore, OAuthClientStoreError};
+use crate::domain::oauth::OAuthClient;
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+    #[tracing::instrument(name = "Adding OAuth client to PostgreSQL", skip_all)]
+    async fn add_client(&mut self, client: &OAuthClient) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            "INSERT INTO oauth_clients (id, name, redirect_uris) VALUES ($1, $2, $3)",
+            client.id,
+            client.name,
+            &client.redirect_uris
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                OAuthClientStoreError::ClientAlreadyExists
+            } else {
+                OAuthClientStoreError::UnexpectedError
+            }
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        let record = sqlx::query!("SELECT name, redirect_uris FROM oauth_clients WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|_| OAuthClientStoreError::UnexpectedError)?
+            .ok_or(OAuthClientStoreError::ClientNotFound)?;
+
+        Ok(OAuthClient::new(id.to_owned(), record.name, record.redirect_uris))
+    }
+}
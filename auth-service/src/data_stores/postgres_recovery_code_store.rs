@@ -0,0 +1,89 @@
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{RecoveryCodeStore, RecoveryCodeStoreError};
+use crate::domain::{email::Email, recovery_code::RecoveryCode};
+
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    #[tracing::instrument(name = "Replacing recovery codes in PostgreSQL", skip_all)]
+    async fn replace_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let hashes: Vec<String> = codes.iter().map(RecoveryCode::hash).collect();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!("DELETE FROM recovery_codes WHERE email = $1", email.as_ref())
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (email, code_hash)
+            SELECT $1, code_hash FROM UNNEST($2::TEXT[]) AS code_hash
+            "#,
+            email.as_ref(),
+            &hashes
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Redeeming recovery code in PostgreSQL", skip_all)]
+    async fn use_code(&mut self, email: &Email, code: &RecoveryCode) -> Result<(), RecoveryCodeStoreError> {
+        // Checking and marking the code in one statement keeps it single-use under concurrent requests
+        let result = sqlx::query!(
+            r#"
+            UPDATE recovery_codes SET used_at = NOW()
+            WHERE email = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            email.as_ref(),
+            code.hash()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(RecoveryCodeStoreError::CodeNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Counting remaining recovery codes in PostgreSQL", skip_all)]
+    async fn remaining_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM recovery_codes WHERE email = $1 AND used_at IS NULL"#,
+            email.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        Ok(count as usize)
+    }
+}
@@ -0,0 +1,187 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::data_stores::data_store::{RefreshTokenStore, RefreshTokenStoreError};
+use crate::domain::{email::Email, refresh_token::RefreshToken, session::SessionId};
+
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    #[tracing::instrument(name = "Creating refresh token family in PostgreSQL", skip_all)]
+    async fn create_family(
+        &mut self,
+        email: &Email,
+        session_id: &SessionId,
+        token: &RefreshToken,
+        ttl_seconds: i64,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let family_id = Uuid::new_v4().to_string();
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            "INSERT INTO refresh_token_families (id, email, session_id) VALUES ($1, $2, $3)",
+            family_id,
+            email.as_ref(),
+            session_id.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        add_to_family(&mut transaction, &family_id, token, ttl_seconds).await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Rotating refresh token in PostgreSQL", skip_all)]
+    async fn rotate_token(
+        &mut self,
+        token: &RefreshToken,
+        new_token: &RefreshToken,
+        ttl_seconds: i64,
+    ) -> Result<(Email, SessionId), RefreshTokenStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        // Lock the token row so concurrent rotations of the same token are serialized,
+        // and the second one is treated as reuse
+        let record = sqlx::query!(
+            r#"
+            SELECT t.family_id, f.email, f.session_id,
+                   t.used_at IS NOT NULL AS "used!",
+                   t.expires_at <= NOW() AS "expired!",
+                   f.revoked_at IS NOT NULL AS "revoked!"
+            FROM refresh_tokens t
+            JOIN refresh_token_families f ON f.id = t.family_id
+            WHERE t.token_hash = $1
+            FOR UPDATE OF t
+            "#,
+            token.hash()
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?
+        .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if record.revoked {
+            return Err(RefreshTokenStoreError::TokenRevoked);
+        }
+
+        let email = Email::parse(record.email).map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+        let session_id = SessionId::parse(record.session_id).map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        if record.used {
+            sqlx::query!(
+                "UPDATE refresh_token_families SET revoked_at = NOW() WHERE id = $1",
+                record.family_id
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+            transaction
+                .commit()
+                .await
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+            return Err(RefreshTokenStoreError::TokenReused(email, session_id));
+        }
+
+        if record.expired {
+            return Err(RefreshTokenStoreError::TokenExpired);
+        }
+
+        sqlx::query!(
+            "UPDATE refresh_tokens SET used_at = NOW() WHERE token_hash = $1",
+            token.hash()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        add_to_family(&mut transaction, &record.family_id, new_token, ttl_seconds).await?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok((email, session_id))
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_token_families SET revoked_at = NOW()
+            WHERE id = (SELECT family_id FROM refresh_tokens WHERE token_hash = $1)
+              AND revoked_at IS NULL
+            "#,
+            token.hash()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(RefreshTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all refresh token families in PostgreSQL", skip_all)]
+    async fn revoke_all_families(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            "UPDATE refresh_token_families SET revoked_at = NOW() WHERE email = $1 AND revoked_at IS NULL",
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+async fn add_to_family(
+    transaction: &mut Transaction<'_, Postgres>,
+    family_id: &str,
+    token: &RefreshToken,
+    ttl_seconds: i64,
+) -> Result<(), RefreshTokenStoreError> {
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (token_hash, family_id, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(secs => $3))
+        "#,
+        token.hash(),
+        family_id,
+        ttl_seconds as f64
+    )
+    .execute(&mut **transaction)
+    .await
+    .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+    Ok(())
+}
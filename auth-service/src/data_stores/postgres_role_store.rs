@@ -0,0 +1,91 @@
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{RoleStore, RoleStoreError};
+use crate::domain::role::{Role, UserRoles};
+use crate::domain::user::UserId;
+
+pub struct PostgresRoleStore {
+    pool: PgPool,
+}
+
+impl PostgresRoleStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RoleStore for PostgresRoleStore {
+    #[tracing::instrument(name = "Defining role in PostgreSQL", skip_all)]
+    async fn define_role(&mut self, role: &Role, permissions: &[String]) -> Result<(), RoleStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO roles (name, permissions) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE SET permissions = EXCLUDED.permissions
+            "#,
+            role.as_ref(),
+            permissions
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Granting role in PostgreSQL", skip_all)]
+    async fn grant_role(&mut self, user_id: &UserId, role: &Role) -> Result<(), RoleStoreError> {
+        sqlx::query!(
+            "INSERT INTO user_roles (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            user_id.as_ref(),
+            role.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("user_roles_role_fkey") {
+                RoleStoreError::RoleNotFound
+            } else {
+                RoleStoreError::UnexpectedError
+            }
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking role in PostgreSQL", skip_all)]
+    async fn revoke_role(&mut self, user_id: &UserId, role: &Role) -> Result<(), RoleStoreError> {
+        let result = sqlx::query!(
+            "DELETE FROM user_roles WHERE user_id = $1 AND role = $2",
+            user_id.as_ref(),
+            role.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(RoleStoreError::RoleNotGranted);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving user roles from PostgreSQL", skip_all)]
+    async fn get_user_roles(&self, user_id: &UserId) -> Result<UserRoles, RoleStoreError> {
+        let records = sqlx::query!(
+            r#"
+            SELECT roles.name, roles.permissions
+            FROM user_roles JOIN roles ON roles.name = user_roles.role
+            WHERE user_roles.user_id = $1
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RoleStoreError::UnexpectedError)?;
+
+        let roles = records.iter().map(|record| record.name.clone()).collect();
+        let permissions = records.into_iter().flat_map(|record| record.permissions).collect();
+        Ok(UserRoles::new(roles, permissions))
+    }
+}
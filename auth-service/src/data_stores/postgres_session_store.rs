@@ -0,0 +1,137 @@
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{SessionStore, SessionStoreError};
+use crate::domain::{
+    session::{Session, SessionId},
+    user::UserId,
+};
+
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: &Session, ttl_seconds: i64) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (id, user_id, user_agent, ip_address, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(secs => $6))
+            "#,
+            session.id.as_ref(),
+            session.user_id.as_ref(),
+            session.user_agent,
+            session.ip_address,
+            session.created_at,
+            ttl_seconds as f64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, user_id: &UserId) -> Result<Vec<Session>, SessionStoreError> {
+        let records = sqlx::query!(
+            r#"
+            SELECT id, user_agent, ip_address, created_at
+            FROM sessions
+            WHERE user_id = $1 AND expires_at > NOW()
+            ORDER BY created_at
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                Ok(Session {
+                    id: SessionId::parse(record.id).map_err(|_| SessionStoreError::UnexpectedError)?,
+                    user_id: user_id.clone(),
+                    user_agent: record.user_agent,
+                    ip_address: record.ip_address,
+                    created_at: record.created_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Checking session in PostgreSQL", skip_all)]
+    async fn is_session_active(&self, user_id: &UserId, id: &SessionId) -> Result<bool, SessionStoreError> {
+        let record = sqlx::query!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM sessions WHERE id = $1 AND user_id = $2 AND expires_at > NOW()
+            ) AS "active!"
+            "#,
+            id.as_ref(),
+            user_id.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(record.active)
+    }
+
+    #[tracing::instrument(name = "Extending session in PostgreSQL", skip_all)]
+    async fn extend_session(&mut self, id: &SessionId, ttl_seconds: i64) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions SET expires_at = NOW() + make_interval(secs => $2)
+            WHERE id = $1 AND expires_at > NOW()
+            "#,
+            id.as_ref(),
+            ttl_seconds as f64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking session in PostgreSQL", skip_all)]
+    async fn revoke_session(&mut self, user_id: &UserId, id: &SessionId) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            "DELETE FROM sessions WHERE id = $1 AND user_id = $2 AND expires_at > NOW()",
+            id.as_ref(),
+            user_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all sessions in PostgreSQL", skip_all)]
+    async fn revoke_all_sessions(&mut self, user_id: &UserId) -> Result<(), SessionStoreError> {
+        sqlx::query!("DELETE FROM sessions WHERE user_id = $1", user_id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection, ExistenceCheck, SetExpiry, SetOptions};
+use sqlx::PgPool;
+use tokio::sync::RwLock;
+
+use crate::data_stores::data_store::{TokenVersionStore, TokenVersionStoreError};
+use crate::domain::user::UserId;
+
+// Token versions live in the users table. Every authenticated request needs the
+// version, so it is cached in Redis.
+pub struct PostgresTokenVersionStore {
+    pool: PgPool,
+    cache: Arc<RwLock<Connection>>,
+}
+
+impl PostgresTokenVersionStore {
+    pub fn new(pool: PgPool, cache: Arc<RwLock<Connection>>) -> Self {
+        Self { pool, cache }
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenVersionStore for PostgresTokenVersionStore {
+    #[tracing::instrument(name = "Retrieving token version", skip_all)]
+    async fn get_version(&self, user_id: &UserId) -> Result<i64, TokenVersionStoreError> {
+        let key = get_key(user_id);
+
+        let cached: Option<i64> = self
+            .cache
+            .write()
+            .await
+            .get(&key)
+            .map_err(|_| TokenVersionStoreError::UnexpectedError)?;
+        if let Some(version) = cached {
+            return Ok(version);
+        }
+
+        let record = sqlx::query!("SELECT token_version FROM users WHERE id = $1", user_id.as_ref())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|_| TokenVersionStoreError::UnexpectedError)?
+            .ok_or(TokenVersionStoreError::UserNotFound)?;
+
+        // Only fill the cache if it is still empty. A concurrent bump may already
+        // have cached a newer version than the one we read.
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(CACHE_TTL_SECONDS));
+        self.cache
+            .write()
+            .await
+            .set_options::<_, _, ()>(&key, record.token_version, options)
+            .map_err(|_| TokenVersionStoreError::UnexpectedError)?;
+
+        Ok(record.token_version)
+    }
+
+    #[tracing::instrument(name = "Bumping token version", skip_all)]
+    async fn bump_version(&mut self, user_id: &UserId) -> Result<i64, TokenVersionStoreError> {
+        let record = sqlx::query!(
+            "UPDATE users SET token_version = token_version + 1 WHERE id = $1 RETURNING token_version",
+            user_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TokenVersionStoreError::UnexpectedError)?
+        .ok_or(TokenVersionStoreError::UserNotFound)?;
+
+        self.cache
+            .write()
+            .await
+            .set_ex::<_, _, ()>(get_key(user_id), record.token_version, CACHE_TTL_SECONDS as u64)
+            .map_err(|_| TokenVersionStoreError::UnexpectedError)?;
+
+        Ok(record.token_version)
+    }
+
+    #[tracing::instrument(name = "Removing token version", skip_all)]
+    async fn remove_version(&mut self, user_id: &UserId) -> Result<(), TokenVersionStoreError> {
+        // The version itself went with the user's row, only the cached copy is left
+        self.cache
+            .write()
+            .await
+            .del::<_, ()>(get_key(user_id))
+            .map_err(|_| TokenVersionStoreError::UnexpectedError)
+    }
+}
+
+const CACHE_TTL_SECONDS: usize = 3600; // 1 hour
+
+// We are using a key prefix to prevent collisions and organize data!
+const TOKEN_VERSION_KEY_PREFIX: &str = "token_version:";
+
+fn get_key(user_id: &UserId) -> String {
+    format!("{}{}", TOKEN_VERSION_KEY_PREFIX, user_id)
+}
@@ -0,0 +1,118 @@
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{TotpSecretStore, TotpSecretStoreError};
+use crate::domain::{email::Email, totp::TotpSecret};
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+
+pub struct PostgresTotpSecretStore {
+    pool: PgPool,
+}
+
+impl PostgresTotpSecretStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for PostgresTotpSecretStore {
+    #[tracing::instrument(name = "Storing pending TOTP secret in PostgreSQL", skip_all)]
+    async fn set_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: &TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        let encrypted = encrypt_secret(secret.as_ref()).map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_secrets (email, pending_secret) VALUES ($1, $2)
+            ON CONFLICT (email) DO UPDATE SET pending_secret = EXCLUDED.pending_secret
+            "#,
+            email.as_ref(),
+            encrypted
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving pending TOTP secret from PostgreSQL", skip_all)]
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        let encrypted = sqlx::query_scalar!(
+            "SELECT pending_secret FROM totp_secrets WHERE email = $1",
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?
+        .flatten()
+        .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        parse_encrypted_secret(&encrypted)
+    }
+
+    #[tracing::instrument(name = "Activating pending TOTP secret in PostgreSQL", skip_all)]
+    async fn activate_pending_secret(&mut self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets SET secret = pending_secret, pending_secret = NULL
+            WHERE email = $1 AND pending_secret IS NOT NULL
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpSecretStoreError::SecretNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP secret from PostgreSQL", skip_all)]
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        let encrypted = sqlx::query_scalar!(
+            "SELECT secret FROM totp_secrets WHERE email = $1",
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?
+        .flatten()
+        .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        parse_encrypted_secret(&encrypted)
+    }
+
+    #[tracing::instrument(name = "Recording used TOTP time step in PostgreSQL", skip_all)]
+    async fn record_used_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        // Checked and updated in one statement so concurrent logins can't both use the same code
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets SET last_used_step = $2
+            WHERE email = $1 AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            email.as_ref(),
+            step as i64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpSecretStoreError::CodeAlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_encrypted_secret(encrypted: &str) -> Result<TotpSecret, TotpSecretStoreError> {
+    let secret = decrypt_secret(encrypted).map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+    TotpSecret::parse(secret).map_err(|_| TotpSecretStoreError::UnexpectedError)
+}
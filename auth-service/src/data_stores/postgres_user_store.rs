@@ -5,10 +5,11 @@ use argon2::{
     PasswordVerifier, Version,
 };
 
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 
 use crate::data_stores::data_store::{UserStore, UserStoreError};
-use crate::domain::{email::Email, password::Password, user::User};
+use crate::domain::{email::Email, password::Password, user::{TwoFAMethod, User, UserId, UserStatus}};
 
 pub struct PostgresUserStore {
     pool: PgPool,
@@ -24,14 +25,16 @@ impl PostgresUserStore {
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let password_hash = compute_password_hash(&user.password.as_ref()).await
+        let password_hash = compute_password_hash(user.password.as_ref()).await
             .map_err(|_| UserStoreError::UnexpectedError)?;
         
         sqlx::query!(
-            "INSERT INTO users (email, password_hash, requires_2fa) VALUES ($1, $2, $3)",
+            "INSERT INTO users (id, email, password_hash, two_fa_method, email_verified) VALUES ($1, $2, $3, $4, $5)",
+            user.id.as_ref(),
             user.email.as_ref(),
             password_hash,
-            user.requires_2fa
+            user.two_fa_method.as_str(),
+            user.email_verified
         )
         .execute(&self.pool)
         .await
@@ -48,8 +51,9 @@ impl UserStore for PostgresUserStore {
 
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        let user_row = sqlx::query!(
-            "SELECT email, password_hash, requires_2fa FROM users WHERE email = $1",
+        let user_row = sqlx::query_as!(
+            UserRow,
+            "SELECT id, email, password_hash, two_fa_method, email_verified, deleted_at, status, status_reason, status_changed_at FROM users WHERE email = $1",
             email.as_ref()
         )
         .fetch_optional(&self.pool)
@@ -57,24 +61,277 @@ impl UserStore for PostgresUserStore {
         .map_err(|_| UserStoreError::UnexpectedError)?
         .ok_or(UserStoreError::UserNotFound)?;
 
-        let user = User::new(
-            Email::parse(user_row.email).map_err(|_| UserStoreError::UnexpectedError)?,
-            Password::parse(user_row.password_hash).map_err(|_| UserStoreError::UnexpectedError)?,
-            user_row.requires_2fa,
-        );
+        user_row.try_into()
+    }
+
+    #[tracing::instrument(name = "Retrieving user by ID from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let user_row = sqlx::query_as!(
+            UserRow,
+            "SELECT id, email, password_hash, two_fa_method, email_verified, deleted_at, status, status_reason, status_changed_at FROM users WHERE id = $1",
+            id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
 
-        Ok(user)
+        user_row.try_into()
     }
 
     #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip_all)]
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError> {
         let user = self.get_user(email).await?;
         
-        verify_password_hash(&user.password.as_ref(), password.as_ref()).await
+        verify_password_hash(user.password.as_ref(), password.as_ref()).await
             .map_err(|_| UserStoreError::InvalidCredentials)?;
         
         Ok(())
     }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(&mut self, email: &Email, password: Password) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref()).await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            "UPDATE users SET password_hash = $1 WHERE email = $2",
+            password_hash,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking email verified in PostgreSQL", skip_all)]
+    async fn mark_email_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET email_verified = TRUE WHERE email = $1",
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Recording verification email in PostgreSQL", skip_all)]
+    async fn record_verification_email_sent(&mut self, email: &Email, cooldown_seconds: i64) -> Result<(), UserStoreError> {
+        // Check and update in a single statement so concurrent requests can't both get through
+        let result = sqlx::query!(
+            r#"
+            UPDATE users SET verification_email_sent_at = NOW()
+            WHERE email = $1
+              AND (verification_email_sent_at IS NULL
+                   OR verification_email_sent_at <= NOW() - make_interval(secs => $2))
+            "#,
+            email.as_ref(),
+            cooldown_seconds as f64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            // Either the user doesn't exist or the cooldown hasn't elapsed yet
+            self.get_user(email).await?;
+            return Err(UserStoreError::VerificationEmailThrottled);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting 2FA method in PostgreSQL", skip_all)]
+    async fn set_two_fa_method(&mut self, email: &Email, method: TwoFAMethod) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET two_fa_method = $1 WHERE email = $2",
+            method.as_str(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating user email in PostgreSQL", skip_all)]
+    async fn update_email(&mut self, id: &UserId, new_email: &Email) -> Result<(), UserStoreError> {
+        // Rows keyed by email in other tables follow through ON UPDATE CASCADE
+        let result = sqlx::query!(
+            "UPDATE users SET email = $1 WHERE id = $2",
+            new_email.as_ref(),
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                UserStoreError::UserAlreadyExists
+            } else {
+                UserStoreError::UnexpectedError
+            }
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Soft-deleting user in PostgreSQL", skip_all)]
+    async fn mark_deleted(&mut self, id: &UserId) -> Result<(), UserStoreError> {
+        // Keep the original timestamp if the user was already deleted, so the grace period doesn't restart
+        let result = sqlx::query!(
+            "UPDATE users SET deleted_at = COALESCE(deleted_at, NOW()) WHERE id = $1",
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Restoring user in PostgreSQL", skip_all)]
+    async fn restore_user(&mut self, id: &UserId) -> Result<(), UserStoreError> {
+        let result = sqlx::query!("UPDATE users SET deleted_at = NULL WHERE id = $1", id.as_ref())
+            .execute(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Purging deleted users from PostgreSQL", skip_all)]
+    async fn purge_deleted_users(&mut self, grace_period_seconds: i64) -> Result<Vec<User>, UserStoreError> {
+        // Rows in other tables belonging to the users go with them through ON DELETE CASCADE
+        let user_rows = sqlx::query_as!(
+            UserRow,
+            r#"
+            DELETE FROM users
+            WHERE deleted_at <= NOW() - make_interval(secs => $1)
+            RETURNING id, email, password_hash, two_fa_method, email_verified, deleted_at, status, status_reason, status_changed_at
+            "#,
+            grace_period_seconds as f64
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        user_rows.into_iter().map(User::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Listing users from PostgreSQL", skip_all)]
+    async fn list_users(&self, search: Option<&str>, limit: i64, offset: i64) -> Result<(Vec<User>, i64), UserStoreError> {
+        // Wildcards in the search are matched literally
+        let pattern = search.map(|search| {
+            let escaped = search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            format!("%{}%", escaped)
+        });
+
+        let user_rows = sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, two_fa_method, email_verified, deleted_at, status, status_reason, status_changed_at
+            FROM users
+            WHERE $1::TEXT IS NULL OR email ILIKE $1
+            ORDER BY email
+            LIMIT $2 OFFSET $3
+            "#,
+            pattern.as_deref(),
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let total = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM users WHERE $1::TEXT IS NULL OR email ILIKE $1"#,
+            pattern.as_deref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let users = user_rows.into_iter().map(User::try_from).collect::<Result<_, _>>()?;
+        Ok((users, total))
+    }
+
+    #[tracing::instrument(name = "Setting user status in PostgreSQL", skip_all)]
+    async fn set_status(&mut self, id: &UserId, status: UserStatus, reason: Option<String>) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET status = $1, status_reason = $2, status_changed_at = NOW() WHERE id = $3",
+            status.as_str(),
+            reason,
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+struct UserRow {
+    id: uuid::Uuid,
+    email: String,
+    password_hash: String,
+    two_fa_method: String,
+    email_verified: bool,
+    deleted_at: Option<DateTime<Utc>>,
+    status: String,
+    status_reason: Option<String>,
+    status_changed_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: UserId::from(row.id),
+            email: Email::parse(row.email).map_err(|_| UserStoreError::UnexpectedError)?,
+            password: Password::parse(row.password_hash).map_err(|_| UserStoreError::UnexpectedError)?,
+            two_fa_method: TwoFAMethod::parse(&row.two_fa_method).map_err(|_| UserStoreError::UnexpectedError)?,
+            email_verified: row.email_verified,
+            deleted_at: row.deleted_at,
+            status: UserStatus::parse(&row.status).map_err(|_| UserStoreError::UnexpectedError)?,
+            status_reason: row.status_reason,
+            status_changed_at: row.status_changed_at,
+        })
+    }
 }
 
 #[tracing::instrument(name = "Verify password hash", skip_all)]
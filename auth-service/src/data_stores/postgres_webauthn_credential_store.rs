@@ -0,0 +1,112 @@
+use sqlx::PgPool;
+
+use crate::data_stores::data_store::{WebauthnCredentialStore, WebauthnCredentialStoreError};
+use crate::domain::{user::UserId, webauthn::WebauthnCredential};
+
+pub struct PostgresWebauthnCredentialStore {
+    pool: PgPool,
+}
+
+impl PostgresWebauthnCredentialStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebauthnCredentialStore for PostgresWebauthnCredentialStore {
+    #[tracing::instrument(name = "Adding WebAuthn credential to PostgreSQL", skip_all)]
+    async fn add_credential(&mut self, credential: &WebauthnCredential) -> Result<(), WebauthnCredentialStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO webauthn_credentials (id, user_id, public_key, sign_count, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            credential.id,
+            credential.user_id.as_ref(),
+            credential.public_key,
+            credential.sign_count as i64,
+            credential.created_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                WebauthnCredentialStoreError::CredentialAlreadyExists
+            } else {
+                WebauthnCredentialStoreError::UnexpectedError
+            }
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving WebAuthn credential from PostgreSQL", skip_all)]
+    async fn get_credential(&self, id: &str) -> Result<WebauthnCredential, WebauthnCredentialStoreError> {
+        let record = sqlx::query!(
+            r#"
+            SELECT user_id, public_key, sign_count, created_at
+            FROM webauthn_credentials
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebauthnCredentialStoreError::UnexpectedError)?
+        .ok_or(WebauthnCredentialStoreError::CredentialNotFound)?;
+
+        Ok(WebauthnCredential {
+            id: id.to_owned(),
+            user_id: UserId::from(record.user_id),
+            public_key: record.public_key,
+            sign_count: record.sign_count as u32,
+            created_at: record.created_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving WebAuthn credentials from PostgreSQL", skip_all)]
+    async fn get_credentials(&self, user_id: &UserId) -> Result<Vec<WebauthnCredential>, WebauthnCredentialStoreError> {
+        let records = sqlx::query!(
+            r#"
+            SELECT id, public_key, sign_count, created_at
+            FROM webauthn_credentials
+            WHERE user_id = $1
+            ORDER BY created_at
+            "#,
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebauthnCredentialStoreError::UnexpectedError)?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| WebauthnCredential {
+                id: record.id,
+                user_id: user_id.clone(),
+                public_key: record.public_key,
+                sign_count: record.sign_count as u32,
+                created_at: record.created_at,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Updating WebAuthn sign count in PostgreSQL", skip_all)]
+    async fn update_sign_count(&mut self, id: &str, sign_count: u32) -> Result<(), WebauthnCredentialStoreError> {
+        let result = sqlx::query!(
+            "UPDATE webauthn_credentials SET sign_count = $2 WHERE id = $1",
+            id,
+            sign_count as i64
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebauthnCredentialStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(WebauthnCredentialStoreError::CredentialNotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::data_stores::data_store::{WebhookStore, WebhookStoreError};
+use crate::domain::webhook::{DeliveryStatus, WebhookDelivery, WebhookEventType, WebhookSubscription};
+use crate::utils::crypto::{decrypt_secret, encrypt_secret};
+
+pub struct PostgresWebhookStore {
+    pool: PgPool,
+}
+
+impl PostgresWebhookStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebhookStore for PostgresWebhookStore {
+    #[tracing::instrument(name = "Adding webhook subscription to PostgreSQL", skip_all)]
+    async fn add_subscription(&mut self, subscription: &WebhookSubscription) -> Result<(), WebhookStoreError> {
+        let encrypted = encrypt_secret(&subscription.secret).map_err(|_| WebhookStoreError::UnexpectedError)?;
+        let event_types: Vec<String> = subscription
+            .event_types
+            .iter()
+            .map(|event_type| event_type.as_str().to_owned())
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_subscriptions (id, url, secret, event_types, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            subscription.id,
+            subscription.url,
+            encrypted,
+            &event_types,
+            subscription.created_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Getting webhook subscription from PostgreSQL", skip_all)]
+    async fn get_subscription(&self, id: &Uuid) -> Result<WebhookSubscription, WebhookStoreError> {
+        sqlx::query_as!(
+            SubscriptionRow,
+            r#"
+            SELECT id, url, secret, event_types, created_at
+            FROM webhook_subscriptions
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?
+        .ok_or(WebhookStoreError::SubscriptionNotFound)?
+        .try_into()
+    }
+
+    #[tracing::instrument(name = "Listing webhook subscriptions in PostgreSQL", skip_all)]
+    async fn list_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError> {
+        sqlx::query_as!(
+            SubscriptionRow,
+            r#"
+            SELECT id, url, secret, event_types, created_at
+            FROM webhook_subscriptions
+            ORDER BY created_at, id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?
+        .into_iter()
+        .map(WebhookSubscription::try_from)
+        .collect()
+    }
+
+    #[tracing::instrument(name = "Deleting webhook subscription from PostgreSQL", skip_all)]
+    async fn delete_subscription(&mut self, id: &Uuid) -> Result<(), WebhookStoreError> {
+        let result = sqlx::query!("DELETE FROM webhook_subscriptions WHERE id = $1", id)
+            .execute(&self.pool)
+            .await
+            .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(WebhookStoreError::SubscriptionNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Adding webhook deliveries to PostgreSQL", skip_all)]
+    async fn add_deliveries(&mut self, deliveries: &[WebhookDelivery]) -> Result<(), WebhookStoreError> {
+        let mut transaction = self.pool.begin().await.map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        for delivery in deliveries {
+            sqlx::query!(
+                r#"
+                INSERT INTO webhook_deliveries
+                    (id, subscription_id, event_id, event_type, payload, status, attempts, next_attempt_at, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                delivery.id,
+                delivery.subscription_id,
+                delivery.event_id,
+                delivery.event_type.as_str(),
+                delivery.payload,
+                delivery.status.as_str(),
+                delivery.attempts,
+                delivery.next_attempt_at,
+                delivery.created_at
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| WebhookStoreError::UnexpectedError)?;
+        }
+
+        transaction.commit().await.map_err(|_| WebhookStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Claiming due webhook deliveries in PostgreSQL", skip_all)]
+    async fn claim_due_deliveries(&mut self, limit: i64, lease_seconds: i64) -> Result<Vec<WebhookDelivery>, WebhookStoreError> {
+        // Rows another worker is claiming at the same moment are skipped rather than waited for
+        let rows = sqlx::query_as!(
+            DeliveryRow,
+            r#"
+            UPDATE webhook_deliveries
+            SET next_attempt_at = NOW() + make_interval(secs => $2)
+            WHERE id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, subscription_id, event_id, event_type, payload, status, attempts, next_attempt_at,
+                last_attempt_at, last_response_status, last_error, created_at
+            "#,
+            limit,
+            lease_seconds as f64
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        rows.into_iter().map(WebhookDelivery::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Updating webhook delivery in PostgreSQL", skip_all)]
+    async fn update_delivery(&mut self, delivery: &WebhookDelivery) -> Result<(), WebhookStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $2, attempts = $3, next_attempt_at = $4, last_attempt_at = $5,
+                last_response_status = $6, last_error = $7
+            WHERE id = $1
+            "#,
+            delivery.id,
+            delivery.status.as_str(),
+            delivery.attempts,
+            delivery.next_attempt_at,
+            delivery.last_attempt_at,
+            delivery.last_response_status,
+            delivery.last_error
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(WebhookStoreError::DeliveryNotFound);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Listing webhook deliveries in PostgreSQL", skip_all)]
+    async fn list_deliveries(&self, subscription_id: &Uuid, limit: i64, offset: i64) -> Result<(Vec<WebhookDelivery>, i64), WebhookStoreError> {
+        let rows = sqlx::query_as!(
+            DeliveryRow,
+            r#"
+            SELECT id, subscription_id, event_id, event_type, payload, status, attempts, next_attempt_at,
+                last_attempt_at, last_response_status, last_error, created_at
+            FROM webhook_deliveries
+            WHERE subscription_id = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            subscription_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        let total = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM webhook_deliveries WHERE subscription_id = $1"#,
+            subscription_id
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        let deliveries = rows.into_iter().map(WebhookDelivery::try_from).collect::<Result<_, _>>()?;
+        Ok((deliveries, total))
+    }
+}
+
+struct SubscriptionRow {
+    id: Uuid,
+    url: String,
+    secret: String,
+    event_types: Vec<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<SubscriptionRow> for WebhookSubscription {
+    type Error = WebhookStoreError;
+
+    fn try_from(row: SubscriptionRow) -> Result<Self, Self::Error> {
+        let event_types = row
+            .event_types
+            .iter()
+            .map(|event_type| WebhookEventType::parse(event_type))
+            .collect::<Result<_, _>>()
+            .map_err(|_| WebhookStoreError::UnexpectedError)?;
+
+        Ok(WebhookSubscription {
+            id: row.id,
+            url: row.url,
+            secret: decrypt_secret(&row.secret).map_err(|_| WebhookStoreError::UnexpectedError)?,
+            event_types,
+            created_at: row.created_at,
+        })
+    }
+}
+
+struct DeliveryRow {
+    id: Uuid,
+    subscription_id: Uuid,
+    event_id: Uuid,
+    event_type: String,
+    payload: String,
+    status: String,
+    attempts: i32,
+    next_attempt_at: Option<DateTime<Utc>>,
+    last_attempt_at: Option<DateTime<Utc>>,
+    last_response_status: Option<i32>,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<DeliveryRow> for WebhookDelivery {
+    type Error = WebhookStoreError;
+
+    fn try_from(row: DeliveryRow) -> Result<Self, Self::Error> {
+        Ok(WebhookDelivery {
+            id: row.id,
+            subscription_id: row.subscription_id,
+            event_id: row.event_id,
+            event_type: WebhookEventType::parse(&row.event_type).map_err(|_| WebhookStoreError::UnexpectedError)?,
+            payload: row.payload,
+            status: DeliveryStatus::parse(&row.status).map_err(|_| WebhookStoreError::UnexpectedError)?,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_attempt_at: row.last_attempt_at,
+            last_response_status: row.last_response_status,
+            last_error: row.last_error,
+            created_at: row.created_at,
+        })
+    }
+}
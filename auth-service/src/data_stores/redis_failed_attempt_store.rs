@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use redis::{Commands, Connection, RedisResult};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::data_stores::data_store::{
+    FailedAttemptStore, FailedAttemptStoreError, HashmapFailedAttemptStore, LockoutPolicy,
+};
+
+// Failures are kept in a sorted set scored by when they happened, so the ones
+// outside the sliding window can be dropped before counting. While Redis is
+// unreachable, failures are counted in memory instead so that guessing doesn't
+// become unlimited.
+pub struct RedisFailedAttemptStore {
+    conn: Arc<RwLock<Connection>>,
+    fallback: HashmapFailedAttemptStore,
+}
+
+impl RedisFailedAttemptStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn, fallback: HashmapFailedAttemptStore::default() }
+    }
+
+    async fn redis_lockout_remaining(&self, key: &str) -> RedisResult<Option<i64>> {
+        let ttl: i64 = self.conn.write().await.ttl(get_lockout_key(key))?;
+        Ok((ttl > 0).then_some(ttl))
+    }
+
+    async fn redis_record_failure(&self, key: &str, policy: &LockoutPolicy) -> RedisResult<Option<i64>> {
+        let now = Utc::now().timestamp_micros();
+        let window_start = now - policy.window_seconds * 1_000_000;
+        let failures_key = get_failures_key(key);
+        let mut conn = self.conn.write().await;
+
+        let (failures,): (u32,) = redis::pipe()
+            .atomic()
+            .zrembyscore(&failures_key, "-inf", window_start)
+            .ignore()
+            .zadd(&failures_key, Uuid::new_v4().to_string(), now)
+            .ignore()
+            .zcard(&failures_key)
+            .expire(&failures_key, policy.window_seconds)
+            .ignore()
+            .query(&mut *conn)?;
+        if failures < policy.max_failures {
+            return Ok(None);
+        }
+
+        // The lockout count expires on its own once the key has gone long enough without a lockout
+        let lockouts_key = get_lockouts_key(key);
+        let (lockouts,): (u32,) = redis::pipe()
+            .atomic()
+            .incr(&lockouts_key, 1)
+            .expire(&lockouts_key, policy.backoff_reset_seconds)
+            .ignore()
+            .query(&mut *conn)?;
+
+        let lockout_seconds = policy.lockout_seconds(lockouts);
+        redis::pipe()
+            .atomic()
+            .set_ex(get_lockout_key(key), 1, lockout_seconds as u64)
+            .ignore()
+            .del(&failures_key)
+            .ignore()
+            .query::<()>(&mut *conn)?;
+
+        Ok(Some(lockout_seconds))
+    }
+}
+
+#[async_trait::async_trait]
+impl FailedAttemptStore for RedisFailedAttemptStore {
+    #[tracing::instrument(name = "Checking lockout", skip_all)]
+    async fn lockout_remaining(&self, key: &str) -> Result<Option<i64>, FailedAttemptStoreError> {
+        // Lockouts that started while Redis was unreachable still hold
+        let fallback = self.fallback.lockout_remaining(key).await?;
+
+        match self.redis_lockout_remaining(key).await {
+            Ok(remaining) => Ok(remaining.max(fallback)),
+            Err(e) => {
+                tracing::warn!("Failed to check lockout in Redis, using in-memory counters: {}", e);
+                Ok(fallback)
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "Recording failed attempt", skip_all)]
+    async fn record_failure(&mut self, key: &str, policy: &LockoutPolicy) -> Result<Option<i64>, FailedAttemptStoreError> {
+        match self.redis_record_failure(key, policy).await {
+            Ok(lockout) => Ok(lockout),
+            Err(e) => {
+                tracing::warn!("Failed to record failed attempt in Redis, using in-memory counters: {}", e);
+                self.fallback.record_failure(key, policy).await
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "Clearing failed attempts", skip_all)]
+    async fn clear_failures(&mut self, key: &str) -> Result<(), FailedAttemptStoreError> {
+        self.fallback.clear_failures(key).await?;
+
+        // Failures left behind in Redis age out of the window on their own, so a
+        // successful login shouldn't fail over them
+        if let Err(e) = self.conn.write().await.del::<_, ()>(get_failures_key(key)) {
+            tracing::warn!("Failed to clear failed attempts in Redis: {}", e);
+        }
+        Ok(())
+    }
+}
+
+// We are using key prefixes to prevent collisions and organize data!
+const FAILURES_KEY_PREFIX: &str = "failed_attempts:";
+const LOCKOUT_KEY_PREFIX: &str = "lockout:";
+const LOCKOUTS_KEY_PREFIX: &str = "lockouts:";
+
+fn get_failures_key(key: &str) -> String {
+    format!("{}{}", FAILURES_KEY_PREFIX, key)
+}
+
+fn get_lockout_key(key: &str) -> String {
+    format!("{}{}", LOCKOUT_KEY_PREFIX, key)
+}
+
+fn get_lockouts_key(key: &str) -> String {
+    format!("{}{}", LOCKOUTS_KEY_PREFIX, key)
+}
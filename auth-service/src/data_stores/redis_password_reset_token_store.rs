@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use redis::{Commands, Connection};
+use tokio::sync::RwLock;
+
+use crate::data_stores::data_store::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError};
+use crate::domain::email::Email;
+
+pub struct RedisPasswordResetTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    async fn add_token(
+        &mut self,
+        email: &Email,
+        token: &PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        // Keying by email means requesting a new token replaces any older one
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+        conn.set_ex::<_, _, ()>(key, token.hash(), PASSWORD_RESET_TOKEN_TTL_SECONDS)
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn remove_token(&mut self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+        conn.del::<_, ()>(key).map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        Ok(())
+    }
+
+    async fn get_token_hash(
+        &self,
+        email: &Email,
+    ) -> Result<String, PasswordResetTokenStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+        conn.get(key).map_err(|_| PasswordResetTokenStoreError::TokenNotFound)
+    }
+}
+
+// Reset tokens are short-lived: 15 minutes
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 900;
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+
+fn get_key(email: &Email) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, email.as_ref())
+}
@@ -1,11 +1,13 @@
 use std::sync::Arc;
 
-use redis::{Commands, Connection};
+use chrono::Utc;
+use redis::{Commands, Connection, RedisResult};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
 use crate::data_stores::data_store::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
 use crate::domain::email::Email;
+use crate::utils::constants::MAX_TWO_FA_CODE_ATTEMPTS;
 
 pub struct RedisTwoFACodeStore {
     conn: Arc<RwLock<Connection>>,
@@ -36,10 +38,16 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         // Return TwoFACodeStoreError::UnexpectedError if casting fails or the call to set_ex fails.
 
         let key = get_key(email);
-        let tuple = TwoFATuple(login_attempt_id.as_ref().to_string(), code.as_ref().to_string());
-        let serialized = serde_json::to_string(&tuple).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let record = TwoFARecord {
+            login_attempt_id: login_attempt_id.as_ref().to_string(),
+            code: code.as_ref().to_string(),
+            failed_attempts: 0,
+            resends: 0,
+            sent_at: Utc::now().timestamp(),
+        };
+        let serialized = serde_json::to_string(&record).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
         let mut conn = self.conn.write().await;
-        conn.set_ex::<_, _, ()>(key, &serialized, TEN_MINUTES_IN_SECONDS as u64).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        conn.set_ex::<_, _, ()>(key, &serialized, TEN_MINUTES_IN_SECONDS).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
         Ok(())
     }
 
@@ -70,15 +78,118 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         let key = get_key(email);
         let mut conn = self.conn.write().await;
         let value: String = conn.get(key).map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)?;
-        let tuple: TwoFATuple = serde_json::from_str(&value).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        let login_attempt_id = LoginAttemptId::parse(tuple.0).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
-        let code = TwoFACode::parse(tuple.1).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let record: TwoFARecord = serde_json::from_str(&value).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let login_attempt_id = LoginAttemptId::parse(record.login_attempt_id).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+        let code = TwoFACode::parse(record.code).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
         Ok((login_attempt_id, code))
     }
+
+    async fn record_failed_attempt(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+
+        // The key is watched, so the transaction is retried if another request changes
+        // the code in between and no failure is lost
+        let outcome = redis::transaction(&mut *conn, &[&key], |conn, pipe| {
+            let mut record = match read_record(conn, &key, login_attempt_id)? {
+                Ok(record) => record,
+                Err(e) => return Ok(Some(Err(e))),
+            };
+
+            record.failed_attempts += 1;
+            if record.failed_attempts >= MAX_TWO_FA_CODE_ATTEMPTS {
+                let done: Option<()> = pipe.del(&key).ignore().query(conn)?;
+                return Ok(done.map(|_| Err(TwoFACodeStoreError::TooManyAttempts)));
+            }
+
+            // Keep the expiry the code was created with
+            let ttl: i64 = conn.ttl(&key)?;
+            let serialized = match serde_json::to_string(&record) {
+                Ok(serialized) => serialized,
+                Err(_) => return Ok(Some(Err(TwoFACodeStoreError::UnexpectedError))),
+            };
+            let done: Option<()> = pipe.set_ex(&key, serialized, ttl.max(1) as u64).ignore().query(conn)?;
+            Ok(done.map(Ok))
+        });
+
+        outcome.map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+    }
+
+    async fn replace_code(
+        &mut self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+        code: &TwoFACode,
+        cooldown_seconds: i64,
+        max_resends: u32,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let key = get_key(email);
+        let mut conn = self.conn.write().await;
+
+        // Watched like failed attempts, so two resends can't both get past the limits
+        let outcome = redis::transaction(&mut *conn, &[&key], |conn, pipe| {
+            let mut record = match read_record(conn, &key, login_attempt_id)? {
+                Ok(record) => record,
+                Err(e) => return Ok(Some(Err(e))),
+            };
+
+            if record.resends >= max_resends {
+                return Ok(Some(Err(TwoFACodeStoreError::TooManyResends)));
+            }
+            let now = Utc::now().timestamp();
+            if now - record.sent_at < cooldown_seconds {
+                return Ok(Some(Err(TwoFACodeStoreError::ResendThrottled)));
+            }
+
+            record.code = code.as_ref().to_string();
+            record.resends += 1;
+            record.sent_at = now;
+
+            // The new code gets the full lifetime
+            let serialized = match serde_json::to_string(&record) {
+                Ok(serialized) => serialized,
+                Err(_) => return Ok(Some(Err(TwoFACodeStoreError::UnexpectedError))),
+            };
+            let done: Option<()> = pipe.set_ex(&key, serialized, TEN_MINUTES_IN_SECONDS).ignore().query(conn)?;
+            Ok(done.map(Ok))
+        });
+
+        outcome.map_err(|_| TwoFACodeStoreError::UnexpectedError)?
+    }
+}
+
+// Reads the record stored under the key, if it belongs to the login attempt
+fn read_record(
+    conn: &mut Connection,
+    key: &str,
+    login_attempt_id: &LoginAttemptId,
+) -> RedisResult<Result<TwoFARecord, TwoFACodeStoreError>> {
+    let value: Option<String> = conn.get(key)?;
+    let record: TwoFARecord = match value.map(|value| serde_json::from_str(&value)) {
+        Some(Ok(record)) => record,
+        Some(Err(_)) => return Ok(Err(TwoFACodeStoreError::UnexpectedError)),
+        None => return Ok(Err(TwoFACodeStoreError::LoginAttemptIdNotFound)),
+    };
+    if record.login_attempt_id != login_attempt_id.as_ref() {
+        return Ok(Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
+    Ok(Ok(record))
 }
 
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFARecord {
+    login_attempt_id: String,
+    code: String,
+    // Wrong codes submitted for this login attempt
+    failed_attempts: u32,
+    resends: u32,
+    // Unix timestamp of when the current code was sent
+    sent_at: i64,
+}
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
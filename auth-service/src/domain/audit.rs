@@ -0,0 +1,347 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::domain::email::Email;
+use crate::domain::user::{User, UserId};
+
+// Previous hash of the first record in the log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// The kinds of security-relevant events the audit log records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEventType {
+    SignedUp,
+    LoginSucceeded,
+    LoginFailed,
+    TwoFASucceeded,
+    TwoFAFailed,
+    TwoFACodeResent,
+    Logout,
+    SessionRevoked,
+    RefreshTokenReused,
+    PasswordChanged,
+    PasswordResetRequested,
+    EmailVerified,
+    EmailChangeRequested,
+    EmailChanged,
+    MagicLinkRequested,
+    TotpEnabled,
+    RecoveryCodesRegenerated,
+    PasskeyRegistered,
+    AccountDeleted,
+    AccountRestored,
+    OAuthConsentGranted,
+    OAuthTokenIssued,
+    // Actions admins take on other users' accounts
+    UserStatusChanged,
+    User2FAChanged,
+    PasswordResetForced,
+    UserSessionsRevoked,
+    RoleGranted,
+    RoleRevoked,
+    // Changes admins make to the service itself
+    WebhookCreated,
+    WebhookDeleted,
+}
+
+impl AuditEventType {
+    pub const ALL: [AuditEventType; 30] = [
+        AuditEventType::SignedUp,
+        AuditEventType::LoginSucceeded,
+        AuditEventType::LoginFailed,
+        AuditEventType::TwoFASucceeded,
+        AuditEventType::TwoFAFailed,
+        AuditEventType::TwoFACodeResent,
+        AuditEventType::Logout,
+        AuditEventType::SessionRevoked,
+        AuditEventType::RefreshTokenReused,
+        AuditEventType::PasswordChanged,
+        AuditEventType::PasswordResetRequested,
+        AuditEventType::EmailVerified,
+        AuditEventType::EmailChangeRequested,
+        AuditEventType::EmailChanged,
+        AuditEventType::MagicLinkRequested,
+        AuditEventType::TotpEnabled,
+        AuditEventType::RecoveryCodesRegenerated,
+        AuditEventType::PasskeyRegistered,
+        AuditEventType::AccountDeleted,
+        AuditEventType::AccountRestored,
+        AuditEventType::OAuthConsentGranted,
+        AuditEventType::OAuthTokenIssued,
+        AuditEventType::UserStatusChanged,
+        AuditEventType::User2FAChanged,
+        AuditEventType::PasswordResetForced,
+        AuditEventType::UserSessionsRevoked,
+        AuditEventType::RoleGranted,
+        AuditEventType::RoleRevoked,
+        AuditEventType::WebhookCreated,
+        AuditEventType::WebhookDeleted,
+    ];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or_else(|| format!("Invalid audit event type: {}", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::SignedUp => "signup",
+            AuditEventType::LoginSucceeded => "login_succeeded",
+            AuditEventType::LoginFailed => "login_failed",
+            AuditEventType::TwoFASucceeded => "2fa_succeeded",
+            AuditEventType::TwoFAFailed => "2fa_failed",
+            AuditEventType::TwoFACodeResent => "2fa_code_resent",
+            AuditEventType::Logout => "logout",
+            AuditEventType::SessionRevoked => "session_revoked",
+            AuditEventType::RefreshTokenReused => "refresh_token_reused",
+            AuditEventType::PasswordChanged => "password_changed",
+            AuditEventType::PasswordResetRequested => "password_reset_requested",
+            AuditEventType::EmailVerified => "email_verified",
+            AuditEventType::EmailChangeRequested => "email_change_requested",
+            AuditEventType::EmailChanged => "email_changed",
+            AuditEventType::MagicLinkRequested => "magic_link_requested",
+            AuditEventType::TotpEnabled => "totp_enabled",
+            AuditEventType::RecoveryCodesRegenerated => "recovery_codes_regenerated",
+            AuditEventType::PasskeyRegistered => "passkey_registered",
+            AuditEventType::AccountDeleted => "account_deleted",
+            AuditEventType::AccountRestored => "account_restored",
+            AuditEventType::OAuthConsentGranted => "oauth_consent_granted",
+            AuditEventType::OAuthTokenIssued => "oauth_token_issued",
+            AuditEventType::UserStatusChanged => "user_status_changed",
+            AuditEventType::User2FAChanged => "user_2fa_changed",
+            AuditEventType::PasswordResetForced => "password_reset_forced",
+            AuditEventType::UserSessionsRevoked => "user_sessions_revoked",
+            AuditEventType::RoleGranted => "role_granted",
+            AuditEventType::RoleRevoked => "role_revoked",
+            AuditEventType::WebhookCreated => "webhook_created",
+            AuditEventType::WebhookDeleted => "webhook_deleted",
+        }
+    }
+}
+
+// Something that happened, before it is written to the audit log
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub event_type: AuditEventType,
+    // The user the event is about, when the account is known
+    pub user_id: Option<UserId>,
+    // The email involved, so events about unknown accounts can be traced too
+    pub email: Option<String>,
+    // Who acted on the user's account, when it wasn't the user: an admin's user ID or "api-token"
+    pub actor: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    // Short note on what happened, like why a login failed
+    pub details: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(event_type: AuditEventType) -> Self {
+        Self {
+            event_type,
+            user_id: None,
+            email: None,
+            actor: None,
+            ip_address: None,
+            user_agent: None,
+            details: None,
+        }
+    }
+
+    pub fn user(mut self, user: &User) -> Self {
+        self.user_id = Some(user.id.clone());
+        self.email = Some(user.email.as_ref().to_owned());
+        self
+    }
+
+    // For when only the user's ID is at hand
+    pub fn user_id(mut self, user_id: &UserId) -> Self {
+        self.user_id = Some(user_id.clone());
+        self
+    }
+
+    pub fn email(mut self, email: &Email) -> Self {
+        self.email = Some(email.as_ref().to_owned());
+        self
+    }
+
+    pub fn actor(mut self, actor: impl ToString) -> Self {
+        self.actor = Some(actor.to_string());
+        self
+    }
+
+    pub fn details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+// An event as written to the audit log. Each record's hash covers its contents
+// and the hash of the record before it, so changing, removing or reordering
+// records breaks the chain from that point on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub occurred_at: DateTime<Utc>,
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditRecord {
+    // Chains `event` onto the record before it, if any. The time is kept to the
+    // microsecond, as precisely as PostgreSQL stores it.
+    pub fn new(previous: Option<&AuditRecord>, occurred_at: DateTime<Utc>, event: AuditEvent) -> Self {
+        let (id, prev_hash) = match previous {
+            Some(previous) => (previous.id + 1, previous.hash.clone()),
+            None => (1, GENESIS_HASH.to_owned()),
+        };
+        let occurred_at = DateTime::from_timestamp_micros(occurred_at.timestamp_micros()).unwrap_or(occurred_at);
+        let hash = compute_hash(id, &occurred_at, &event, &prev_hash);
+        Self { id, occurred_at, event, prev_hash, hash }
+    }
+
+    // Whether the record's hash matches its contents
+    pub fn is_intact(&self) -> bool {
+        self.hash == compute_hash(self.id, &self.occurred_at, &self.event, &self.prev_hash)
+    }
+}
+
+// Which records to return from the audit log. Every condition that is set must hold.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLogFilter {
+    pub event_type: Option<AuditEventType>,
+    pub user_id: Option<UserId>,
+    pub email: Option<String>,
+    pub actor: Option<String>,
+    // Records from this time on
+    pub since: Option<DateTime<Utc>>,
+    // Records from before this time
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditLogFilter {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        let event = &record.event;
+        self.event_type.is_none_or(|event_type| event.event_type == event_type)
+            && self.user_id.as_ref().is_none_or(|user_id| event.user_id.as_ref() == Some(user_id))
+            && self.email.as_ref().is_none_or(|email| event.email.as_ref() == Some(email))
+            && self.actor.as_ref().is_none_or(|actor| event.actor.as_ref() == Some(actor))
+            && self.since.is_none_or(|since| record.occurred_at >= since)
+            && self.until.is_none_or(|until| record.occurred_at < until)
+    }
+}
+
+// Outcome of checking the audit log's hash chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainVerification {
+    // How many records, oldest first, were found intact
+    pub checked: i64,
+    // The first record that was changed or doesn't follow the one before it
+    pub first_invalid_id: Option<i64>,
+}
+
+// Checks records one after another, oldest first, for breaks in the chain
+#[derive(Debug, Default)]
+pub struct ChainVerifier {
+    previous: Option<(i64, String)>,
+    checked: i64,
+}
+
+impl ChainVerifier {
+    // Whether `record` is intact and follows the record checked before it
+    pub fn check(&mut self, record: &AuditRecord) -> bool {
+        let (expected_id, expected_prev_hash) = match &self.previous {
+            Some((id, hash)) => (id + 1, hash.as_str()),
+            None => (1, GENESIS_HASH),
+        };
+        if record.id != expected_id || record.prev_hash != expected_prev_hash || !record.is_intact() {
+            return false;
+        }
+        self.previous = Some((record.id, record.hash.clone()));
+        self.checked += 1;
+        true
+    }
+
+    // How many records were found intact
+    pub fn checked(&self) -> i64 {
+        self.checked
+    }
+
+    // The outcome, given the first record `check` rejected, if any
+    pub fn finish(self, first_invalid_id: Option<i64>) -> ChainVerification {
+        ChainVerification { checked: self.checked, first_invalid_id }
+    }
+}
+
+// Hex SHA-256 of the record's fields. They are hashed as a JSON array so no
+// field can bleed into the next.
+fn compute_hash(id: i64, occurred_at: &DateTime<Utc>, event: &AuditEvent, prev_hash: &str) -> String {
+    let fields = serde_json::json!([
+        id,
+        occurred_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+        event.event_type.as_str(),
+        event.user_id.as_ref().map(UserId::to_string),
+        event.email,
+        event.actor,
+        event.ip_address,
+        event.user_agent,
+        event.details,
+        prev_hash,
+    ]);
+    format!("{:x}", Sha256::digest(fields.to_string().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(length: usize) -> Vec<AuditRecord> {
+        let mut records: Vec<AuditRecord> = Vec::new();
+        for i in 0..length {
+            let event = AuditEvent::new(AuditEventType::LoginFailed).details(format!("attempt {}", i));
+            records.push(AuditRecord::new(records.last(), Utc::now(), event));
+        }
+        records
+    }
+
+    #[test]
+    fn test_audit_event_type_round_trip() {
+        for event_type in AuditEventType::ALL {
+            assert_eq!(AuditEventType::parse(event_type.as_str()).unwrap(), event_type);
+        }
+        assert!(AuditEventType::parse("login").is_err());
+    }
+
+    #[test]
+    fn test_records_are_chained() {
+        let records = chain(3);
+        assert_eq!(records[0].id, 1);
+        assert_eq!(records[0].prev_hash, GENESIS_HASH);
+        assert_eq!(records[1].prev_hash, records[0].hash);
+        assert_eq!(records[2].prev_hash, records[1].hash);
+
+        let mut verifier = ChainVerifier::default();
+        assert!(records.iter().all(|record| verifier.check(record)));
+        assert_eq!(verifier.checked(), 3);
+    }
+
+    #[test]
+    fn test_tampering_breaks_the_chain() {
+        // A changed record no longer matches its hash
+        let mut records = chain(3);
+        records[1].event.details = Some("nothing to see here".to_string());
+        let mut verifier = ChainVerifier::default();
+        assert!(verifier.check(&records[0]));
+        assert!(!verifier.check(&records[1]));
+
+        // A removed record leaves a gap
+        let mut records = chain(3);
+        records.remove(1);
+        let mut verifier = ChainVerifier::default();
+        assert!(verifier.check(&records[0]));
+        assert!(!verifier.check(&records[1]));
+    }
+}
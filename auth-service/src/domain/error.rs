@@ -1,3 +1,5 @@
+use crate::domain::user::UserStatus;
+
 #[derive(Debug)]
 pub enum AuthAPIError {
     UserAlreadyExists,
@@ -7,4 +9,47 @@ pub enum AuthAPIError {
     UnexpectedError,
     MissingToken,
     InvalidToken,
+    EmailNotVerified,
+    TooManyRequests,
+    SessionNotFound,
+    AccountPendingDeletion,
+    // An admin suspended, locked or otherwise blocked the account
+    AccountNotActive(UserStatus),
+    // Locked out after too many failed attempts, for this many seconds
+    TooManyAttempts(u64),
+    // The 2FA code was invalidated after too many wrong codes, so login has to start over
+    LoginAttemptExpired,
+    IdentityProviderNotFound,
+    // The identity provider didn't log the user in, or its answer couldn't be verified
+    FederatedLoginFailed,
+    // Logged in, but without the permission the endpoint requires
+    Forbidden,
+    UserNotFound,
+    RoleNotFound,
+    RoleNotGranted,
+    WebhookNotFound,
+}
+// Errors of the OAuth2 endpoints, reported with the error codes of RFC 6749 so
+// standard client libraries understand them
+#[derive(Debug)]
+pub enum OAuthError {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnsupportedGrantType,
+    InvalidToken,
+    ServerError,
+}
+
+impl OAuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant => "invalid_grant",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::InvalidToken => "invalid_token",
+            OAuthError::ServerError => "server_error",
+        }
+    }
 }
\ No newline at end of file
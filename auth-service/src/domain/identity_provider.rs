@@ -0,0 +1,66 @@
+use std::fmt;
+
+// An upstream OpenID Connect provider users can log in with, such as a company's
+// own IdP. Its endpoints are discovered from the issuer, so only the issuer and
+// the credentials of our client registration there are configured.
+#[derive(Clone, PartialEq)]
+pub struct IdentityProvider {
+    // Identifies the provider in the login and callback URLs
+    pub name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl IdentityProvider {
+    pub fn new(name: String, issuer: String, client_id: String, client_secret: String) -> Result<Self, String> {
+        let valid_name = (1..=32).contains(&name.len())
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid_name {
+            return Err(format!("Invalid identity provider name: {}", name));
+        }
+
+        // The discovery document is looked up relative to the issuer
+        let issuer = issuer.trim_end_matches('/').to_owned();
+        if !issuer.starts_with("https://") && !issuer.starts_with("http://") {
+            return Err(format!("Invalid issuer for identity provider {}: {}", name, issuer));
+        }
+
+        Ok(Self { name, issuer, client_id, client_secret })
+    }
+}
+
+// Keep the client secret out of logs
+impl fmt::Debug for IdentityProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdentityProvider")
+            .field("name", &self.name)
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(name: &str, issuer: &str) -> Result<IdentityProvider, String> {
+        IdentityProvider::new(name.to_string(), issuer.to_string(), "client".to_string(), "secret".to_string())
+    }
+
+    #[test]
+    fn test_new_identity_provider() {
+        let provider = provider("acme-sso", "https://sso.acme.example/").unwrap();
+        assert_eq!(provider.issuer, "https://sso.acme.example");
+        assert!(!format!("{:?}", provider).contains("secret"));
+    }
+
+    #[test]
+    fn test_new_rejects_invalid_values() {
+        assert!(provider("", "https://sso.acme.example").is_err());
+        assert!(provider("Acme", "https://sso.acme.example").is_err());
+        assert!(provider("acme/sso", "https://sso.acme.example").is_err());
+        assert!(provider("acme", "sso.acme.example").is_err());
+    }
+}
@@ -2,5 +2,15 @@ pub mod user;
 pub mod error;
 pub mod email;
 pub mod password;
+pub mod totp;
+pub mod recovery_code;
+pub mod refresh_token;
+pub mod session;
+pub mod webauthn;
+pub mod oauth;
+pub mod identity_provider;
+pub mod role;
+pub mod audit;
+pub mod webhook;
 pub mod email_client;
 pub use email_client::*;
\ No newline at end of file
@@ -1,3 +1,5 @@
+use rand::{distributions::Alphanumeric, Rng};
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Password(String);
 
@@ -11,6 +13,19 @@ impl Password {
 }
 
 
+// A random password nobody knows, for accounts that must not be logged into with
+// a password until the user sets one through a password reset
+impl Default for Password {
+    fn default() -> Self {
+        let password = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        Password(password)
+    }
+}
+
 impl AsRef<str> for Password {
     fn as_ref(&self) -> &str {
         &self.0
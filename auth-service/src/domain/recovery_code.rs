@@ -0,0 +1,96 @@
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha256};
+
+// Number of codes issued at enrollment and on every regeneration
+pub const RECOVERY_CODE_COUNT: usize = 10;
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+// Lowercase letters and digits, without the easily confused 0, o, 1 and l
+const RECOVERY_CODE_CHARSET: &[u8] = b"abcdefghijkmnpqrstuvwxyz23456789";
+
+// A single-use code in the form "xxxxx-xxxxx" that stands in for a second factor
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    // Accepts codes with or without the separator and in any case, as users tend to retype them
+    pub fn parse(code: String) -> Result<Self, String> {
+        let normalized: String = code
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if normalized.len() == RECOVERY_CODE_GROUP_LENGTH * 2
+            && normalized.bytes().all(|b| RECOVERY_CODE_CHARSET.contains(&b))
+        {
+            let (first, second) = normalized.split_at(RECOVERY_CODE_GROUP_LENGTH);
+            Ok(RecoveryCode(format!("{}-{}", first, second)))
+        } else {
+            Err("Invalid recovery code".to_string())
+        }
+    }
+
+    // A fresh set of codes for one user
+    pub fn generate_set() -> Vec<RecoveryCode> {
+        (0..RECOVERY_CODE_COUNT).map(|_| RecoveryCode::default()).collect()
+    }
+
+    // Codes carry ~50 bits of randomness, so a fast unsalted hash is enough and
+    // lets stores look them up directly
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| *RECOVERY_CODE_CHARSET.choose(&mut rng).unwrap() as char)
+                .collect()
+        };
+        RecoveryCode(format!("{}-{}", group(), group()))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_code_parses() {
+        let code = RecoveryCode::default();
+        assert_eq!(RecoveryCode::parse(code.as_ref().to_string()).unwrap(), code);
+    }
+
+    #[test]
+    fn test_parse_normalizes_input() {
+        let code = RecoveryCode::parse(" ABCDE-FGHJK ".to_string()).unwrap();
+        assert_eq!(code.as_ref(), "abcde-fghjk");
+        assert_eq!(RecoveryCode::parse("abcdefghjk".to_string()).unwrap(), code);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_code() {
+        assert!(RecoveryCode::parse("123456".to_string()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghj0".to_string()).is_err());
+        assert!(RecoveryCode::parse("abcde-fghjkm".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_generate_set() {
+        let codes = RecoveryCode::generate_set();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_ne!(codes[0], codes[1]);
+        assert_eq!(codes[0].hash(), codes[0].clone().hash());
+        assert_ne!(codes[0].hash(), codes[1].hash());
+    }
+}
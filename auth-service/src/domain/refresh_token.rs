@@ -0,0 +1,58 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+// An opaque, random token that can be exchanged for a new JWT auth token
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == REFRESH_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(RefreshToken(token))
+        } else {
+            Err("Invalid refresh token".to_string())
+        }
+    }
+
+    // Tokens are long and random, so stores keep a SHA-256 hash and look tokens up by it
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        let token = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(REFRESH_TOKEN_LENGTH)
+            .map(char::from)
+            .collect();
+        RefreshToken(token)
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_token_parses() {
+        let token = RefreshToken::default();
+        assert_eq!(RefreshToken::parse(token.as_ref().to_string()).unwrap(), token);
+        assert_ne!(RefreshToken::default(), token);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_token() {
+        assert!(RefreshToken::parse("too-short".to_string()).is_err());
+        assert!(RefreshToken::parse("!".repeat(REFRESH_TOKEN_LENGTH)).is_err());
+    }
+}
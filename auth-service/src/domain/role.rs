@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+// Role every deployment has. Admins can grant and revoke roles.
+pub const ADMIN_ROLE: &str = "admin";
+
+// Permission to grant and revoke roles through the admin API
+pub const MANAGE_ROLES_PERMISSION: &str = "roles:manage";
+
+// A named set of permissions that can be granted to users
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Role(String);
+
+impl Role {
+    pub fn parse(name: String) -> Result<Self, String> {
+        let valid = (1..=64).contains(&name.len())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_:".contains(c));
+        if valid {
+            Ok(Role(name))
+        } else {
+            Err(format!("Invalid role name: {}", name))
+        }
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// The roles granted to a user and the permissions they add up to. Both are
+// sorted, and each permission is listed once however many roles grant it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserRoles {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl UserRoles {
+    pub fn new(mut roles: Vec<String>, mut permissions: Vec<String>) -> Self {
+        roles.sort();
+        roles.dedup();
+        permissions.sort();
+        permissions.dedup();
+        Self { roles, permissions }
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_role() {
+        assert!(Role::parse(ADMIN_ROLE.to_string()).is_ok());
+        assert!(Role::parse("billing:viewer".to_string()).is_ok());
+        assert!(Role::parse("".to_string()).is_err());
+        assert!(Role::parse("Admin".to_string()).is_err());
+        assert!(Role::parse("a b".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_user_roles_merge_permissions() {
+        let roles = UserRoles::new(
+            vec!["support".to_string(), "admin".to_string()],
+            vec!["users:read".to_string(), MANAGE_ROLES_PERMISSION.to_string(), "users:read".to_string()],
+        );
+        assert_eq!(roles.roles, vec!["admin", "support"]);
+        assert_eq!(roles.permissions, vec![MANAGE_ROLES_PERMISSION, "users:read"]);
+        assert!(roles.has_permission(MANAGE_ROLES_PERMISSION));
+        assert!(!roles.has_permission("users:write"));
+    }
+}
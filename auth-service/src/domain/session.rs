@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::domain::user::UserId;
+
+// Identifies a login session. JWT auth tokens carry it as their `jti` claim.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        Uuid::parse_str(&id)
+            .map(|uuid| SessionId(uuid.to_string()))
+            .map_err(|_| "Invalid session ID".to_string())
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        SessionId(Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A login session, with enough about the client it was started from for the
+// user to recognize it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: UserId,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(user_id: UserId, user_agent: Option<String>, ip_address: Option<String>) -> Self {
+        Self {
+            id: SessionId::default(),
+            user_id,
+            user_agent,
+            ip_address,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_session_id_parses() {
+        let id = SessionId::default();
+        assert_eq!(SessionId::parse(id.as_ref().to_string()).unwrap(), id);
+        assert_ne!(SessionId::default(), id);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_session_id() {
+        assert!(SessionId::parse("not-a-session".to_string()).is_err());
+    }
+}
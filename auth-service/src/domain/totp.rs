@@ -0,0 +1,132 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::email::Email;
+
+// Name shown next to the account in authenticator apps
+const TOTP_ISSUER: &str = "LetsGetRusty";
+// RFC 4226 recommends secrets of at least 160 bits
+const TOTP_SECRET_BYTES: usize = 20;
+// Each code is valid for one 30 second time step
+const TOTP_STEP_SECONDS: u64 = 30;
+
+// A base32 encoded RFC 6238 shared secret
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpSecret(String);
+
+impl TotpSecret {
+    pub fn parse(secret: String) -> Result<Self, String> {
+        match Secret::Encoded(secret.clone()).to_bytes() {
+            Ok(bytes) if bytes.len() >= TOTP_SECRET_BYTES => Ok(TotpSecret(secret)),
+            _ => Err("Invalid TOTP secret".to_string()),
+        }
+    }
+
+    // URI that authenticator apps can import, usually rendered as a QR code
+    pub fn otpauth_uri(&self, email: &Email) -> Result<String, String> {
+        Ok(self.totp(email)?.get_url())
+    }
+
+    // Check a code against the current time step, allowing one step of clock skew.
+    // Returns the step the code belongs to, so callers can refuse it once it has been used.
+    pub fn verify(&self, email: &Email, code: &str) -> Option<u64> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        self.verify_at(email, code, now)
+    }
+
+    fn verify_at(&self, email: &Email, code: &str, time: u64) -> Option<u64> {
+        let mut totp = self.totp(email).ok()?;
+        // Steps are checked one at a time to find out which one matched
+        totp.skew = 0;
+        let current = time / TOTP_STEP_SECONDS;
+        [current + 1, current, current.saturating_sub(1)]
+            .into_iter()
+            .find(|step| totp.check(code, step * TOTP_STEP_SECONDS))
+    }
+
+    fn totp(&self, email: &Email) -> Result<TOTP, String> {
+        let bytes = Secret::Encoded(self.0.clone())
+            .to_bytes()
+            .map_err(|_| "Invalid TOTP secret".to_string())?;
+        TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            TOTP_STEP_SECONDS,
+            bytes,
+            Some(TOTP_ISSUER.to_string()),
+            email.as_ref().to_string(),
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = [0u8; TOTP_SECRET_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        TotpSecret(Secret::Raw(bytes.to_vec()).to_encoded().to_string())
+    }
+}
+
+impl AsRef<str> for TotpSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_code(secret: &TotpSecret, email: &Email) -> String {
+        secret.totp(email).unwrap().generate_current().unwrap()
+    }
+
+    #[test]
+    fn test_default_secret_parses() {
+        let secret = TotpSecret::default();
+        assert_eq!(TotpSecret::parse(secret.as_ref().to_string()).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_parse_rejects_short_or_invalid_secret() {
+        assert!(TotpSecret::parse("JBSWY3DP".to_string()).is_err());
+        assert!(TotpSecret::parse("not base32!".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let secret = TotpSecret::default();
+        let email = Email::parse("test@example.com".to_string()).unwrap();
+        let uri = secret.otpauth_uri(&email).unwrap();
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains(secret.as_ref()));
+    }
+
+    #[test]
+    fn test_verify() {
+        let secret = TotpSecret::default();
+        let email = Email::parse("test@example.com".to_string()).unwrap();
+        let code = current_code(&secret, &email);
+        assert!(secret.verify(&email, &code).is_some());
+        assert!(TotpSecret::default().verify(&email, &code).is_none());
+    }
+
+    #[test]
+    fn test_verify_returns_step_of_code() {
+        let secret = TotpSecret::default();
+        let email = Email::parse("test@example.com".to_string()).unwrap();
+        let time = 1_700_000_000;
+        let step = time / TOTP_STEP_SECONDS;
+        let code = secret.totp(&email).unwrap().generate(time);
+
+        assert_eq!(secret.verify_at(&email, &code, time), Some(step));
+        // One step of clock skew either way
+        assert_eq!(secret.verify_at(&email, &code, time + TOTP_STEP_SECONDS), Some(step));
+        assert_eq!(secret.verify_at(&email, &code, time - TOTP_STEP_SECONDS), Some(step));
+        assert_eq!(secret.verify_at(&email, &code, time + 2 * TOTP_STEP_SECONDS), None);
+    }
+}
@@ -1,15 +1,175 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
 use crate::domain::email::Email;
 use crate::domain::password::Password;
 
+// Stable identifier of a user. Unlike their email it never changes, so JWTs
+// identify users by it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        Uuid::parse_str(id)
+            .map(UserId)
+            .map_err(|_| "Invalid user ID".to_string())
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        UserId(Uuid::new_v4())
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(id: Uuid) -> Self {
+        UserId(id)
+    }
+}
+
+impl AsRef<Uuid> for UserId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 #[derive(Clone)]
 pub struct User {
+    pub id: UserId,
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub email_verified: bool,
+    // When the user asked for their account to be deleted, if they have
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub status: UserStatus,
+    // Why an admin set the status. Only shown to admins, never to the user.
+    pub status_reason: Option<String>,
+    // When the status was last changed, if it ever was
+    pub status_changed_at: Option<DateTime<Utc>>,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
-        Self { email, password, requires_2fa }
+    // New users start out unverified until they confirm their email address
+    pub fn new(email: Email, password: Password, two_fa_method: TwoFAMethod) -> Self {
+        Self {
+            id: UserId::default(),
+            email,
+            password,
+            two_fa_method,
+            email_verified: false,
+            deleted_at: None,
+            status: UserStatus::Active,
+            status_reason: None,
+            status_changed_at: None,
+        }
+    }
+
+    pub fn requires_2fa(&self) -> bool {
+        self.two_fa_method != TwoFAMethod::None
+    }
+
+    // Accounts pending deletion can't log in, but can still be restored
+    pub fn is_pending_deletion(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    // Only active users can log in or use their tokens
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
+}
+
+// Whether a user may use their account. Admins set the other statuses to block a
+// user without deleting them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserStatus {
+    Active,
+    Suspended,
+    Locked,
+    PendingVerification,
+}
+
+impl UserStatus {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "active" => Ok(UserStatus::Active),
+            "suspended" => Ok(UserStatus::Suspended),
+            "locked" => Ok(UserStatus::Locked),
+            "pending_verification" => Ok(UserStatus::PendingVerification),
+            _ => Err(format!("Invalid user status: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserStatus::Active => "active",
+            UserStatus::Suspended => "suspended",
+            UserStatus::Locked => "locked",
+            UserStatus::PendingVerification => "pending_verification",
+        }
+    }
+}
+
+// The second factor a user has configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TwoFAMethod {
+    None,
+    Email,
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "none" => Ok(TwoFAMethod::None),
+            "email" => Ok(TwoFAMethod::Email),
+            "totp" => Ok(TwoFAMethod::Totp),
+            _ => Err(format!("Invalid 2FA method: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TwoFAMethod::None => "none",
+            TwoFAMethod::Email => "email",
+            TwoFAMethod::Totp => "totp",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_fa_method_round_trip() {
+        for method in [TwoFAMethod::None, TwoFAMethod::Email, TwoFAMethod::Totp] {
+            assert_eq!(TwoFAMethod::parse(method.as_str()).unwrap(), method);
+        }
+        assert!(TwoFAMethod::parse("sms").is_err());
+    }
+
+    #[test]
+    fn test_user_status_round_trip() {
+        for status in [UserStatus::Active, UserStatus::Suspended, UserStatus::Locked, UserStatus::PendingVerification] {
+            assert_eq!(UserStatus::parse(status.as_str()).unwrap(), status);
+        }
+        assert!(UserStatus::parse("banned").is_err());
+    }
+
+    #[test]
+    fn test_user_id_round_trip() {
+        let id = UserId::default();
+        assert_eq!(UserId::parse(&id.to_string()).unwrap(), id);
+        assert!(UserId::parse("not-a-user").is_err());
     }
 }
\ No newline at end of file
@@ -0,0 +1,305 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::domain::user::UserId;
+use crate::utils::cbor::{self, Value};
+use crate::utils::constants::{WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID};
+
+// Name shown to the user when their authenticator asks to create a passkey
+pub const WEBAUTHN_RP_NAME: &str = "LetsGetRusty";
+// COSE algorithm identifier of ECDSA with P-256 and SHA-256, the one algorithm
+// every authenticator supports
+pub const COSE_ALGORITHM_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+
+// A passkey registered to a user. The ID is the base64url encoded credential ID
+// the authenticator chose, and the public key an uncompressed P-256 point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WebauthnCredential {
+    pub id: String,
+    pub user_id: UserId,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebauthnCredential {
+    pub fn new(id: String, user_id: UserId, public_key: Vec<u8>, sign_count: u32) -> Self {
+        Self { id, user_id, public_key, sign_count, created_at: Utc::now() }
+    }
+
+    // Authenticators that keep a signature counter must report a higher count every
+    // time. One that doesn't is most likely a cloned copy of the authenticator.
+    pub fn is_sign_count_valid(&self, sign_count: u32) -> bool {
+        (sign_count == 0 && self.sign_count == 0) || sign_count > self.sign_count
+    }
+
+    // Check an assertion signature, which covers the authenticator data followed by
+    // the SHA-256 hash of the client data
+    pub fn verify_signature(&self, authenticator_data: &[u8], client_data_json: &[u8], signature: &[u8]) -> bool {
+        let mut message = authenticator_data.to_vec();
+        message.extend_from_slice(&Sha256::digest(client_data_json));
+
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &self.public_key)
+            .verify(&message, signature)
+            .is_ok()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Ceremony {
+    Registration,
+    Authentication,
+}
+
+impl Ceremony {
+    fn client_data_type(&self) -> &'static str {
+        match self {
+            Ceremony::Registration => "webauthn.create",
+            Ceremony::Authentication => "webauthn.get",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+// Check the client data the browser passed to the authenticator and return the
+// challenge it was asked to sign
+pub fn verify_client_data(client_data_json: &[u8], ceremony: Ceremony) -> Result<String, String> {
+    let client_data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| "Invalid client data".to_string())?;
+
+    if client_data.kind != ceremony.client_data_type() {
+        return Err("Wrong client data type".to_string());
+    }
+    // A page on another origin can't get a signature we would accept, which is what
+    // makes passkeys phishing resistant
+    if client_data.origin != *WEBAUTHN_ORIGIN {
+        return Err("Wrong origin".to_string());
+    }
+
+    URL_SAFE_NO_PAD
+        .decode(client_data.challenge)
+        .ok()
+        .and_then(|challenge| String::from_utf8(challenge).ok())
+        .ok_or_else(|| "Invalid challenge".to_string())
+}
+
+// The credential a registration created
+#[derive(Debug, PartialEq)]
+pub struct AttestedCredential {
+    pub id: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AuthenticatorData {
+    pub flags: u8,
+    pub sign_count: u32,
+    pub attested_credential: Option<AttestedCredential>,
+}
+
+impl AuthenticatorData {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < 37 {
+            return Err("Authenticator data too short".to_string());
+        }
+        let (rp_id_hash, rest) = bytes.split_at(32);
+        if rp_id_hash != Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).as_slice() {
+            return Err("Wrong relying party".to_string());
+        }
+
+        let flags = rest[0];
+        let sign_count = u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]]);
+        if flags & FLAG_USER_PRESENT == 0 {
+            return Err("User not present".to_string());
+        }
+
+        let attested_credential = match flags & FLAG_ATTESTED_CREDENTIAL {
+            0 => None,
+            _ => Some(parse_attested_credential(&rest[5..])?),
+        };
+
+        Ok(Self { flags, sign_count, attested_credential })
+    }
+
+    // Whether the authenticator checked who the user is, e.g. with a fingerprint or PIN,
+    // rather than only that someone is there
+    pub fn user_verified(&self) -> bool {
+        self.flags & FLAG_USER_VERIFIED != 0
+    }
+}
+
+// Layout: 16 byte AAGUID, 2 byte credential ID length, credential ID, COSE public key
+fn parse_attested_credential(bytes: &[u8]) -> Result<AttestedCredential, String> {
+    let invalid = || "Invalid attested credential".to_string();
+
+    let id_length = bytes.get(16..18).ok_or_else(invalid)?;
+    let id_length = u16::from_be_bytes([id_length[0], id_length[1]]) as usize;
+    let id = bytes.get(18..18 + id_length).ok_or_else(invalid)?.to_vec();
+
+    let (cose_key, _) = cbor::decode(&bytes[18 + id_length..])?;
+    let public_key = parse_cose_key(&cose_key)?;
+
+    Ok(AttestedCredential { id, public_key })
+}
+
+// Convert an ES256 COSE key to an uncompressed P-256 point
+fn parse_cose_key(key: &Value) -> Result<Vec<u8>, String> {
+    const KEY_TYPE_EC2: i128 = 2;
+    const CURVE_P256: i128 = 1;
+
+    let int = |label| key.get_int(label).and_then(Value::as_integer);
+    if int(1) != Some(KEY_TYPE_EC2) || int(3) != Some(COSE_ALGORITHM_ES256 as i128) || int(-1) != Some(CURVE_P256) {
+        return Err("Only ES256 credentials are supported".to_string());
+    }
+
+    match (key.get_int(-2).and_then(Value::as_bytes), key.get_int(-3).and_then(Value::as_bytes)) {
+        (Some(x), Some(y)) if x.len() == 32 && y.len() == 32 => Ok([&[0x04], x, y].concat()),
+        _ => Err("Invalid ES256 key".to_string()),
+    }
+}
+
+// Read the authenticator data out of a registration's attestation object. We ask
+// for no attestation, so the attestation statement itself isn't checked.
+pub fn parse_attestation_object(bytes: &[u8]) -> Result<AuthenticatorData, String> {
+    let (attestation_object, _) = cbor::decode(bytes)?;
+    let authenticator_data = attestation_object
+        .get_text("authData")
+        .and_then(Value::as_bytes)
+        .ok_or_else(|| "Missing authenticator data".to_string())?;
+
+    AuthenticatorData::parse(authenticator_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    fn key_pair() -> EcdsaKeyPair {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap()
+    }
+
+    fn cose_key(public_key: &[u8]) -> Value {
+        Value::Map(vec![
+            (Value::Integer(1), Value::Integer(2)),
+            (Value::Integer(3), Value::Integer(-7)),
+            (Value::Integer(-1), Value::Integer(1)),
+            (Value::Integer(-2), Value::Bytes(public_key[1..33].to_vec())),
+            (Value::Integer(-3), Value::Bytes(public_key[33..].to_vec())),
+        ])
+    }
+
+    fn authenticator_data(flags: u8, sign_count: u32, attested: Option<(&[u8], &[u8])>) -> Vec<u8> {
+        let mut data = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        if let Some((id, public_key)) = attested {
+            data.extend_from_slice(&[0; 16]);
+            data.extend_from_slice(&(id.len() as u16).to_be_bytes());
+            data.extend_from_slice(id);
+            data.extend_from_slice(&cose_key(public_key).encode());
+        }
+        data
+    }
+
+    fn client_data(kind: &str, challenge: &str, origin: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": kind,
+            "challenge": URL_SAFE_NO_PAD.encode(challenge),
+            "origin": origin,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_verify_client_data() {
+        let data = client_data("webauthn.create", "challenge", &WEBAUTHN_ORIGIN);
+        assert_eq!(verify_client_data(&data, Ceremony::Registration).unwrap(), "challenge");
+        assert!(verify_client_data(&data, Ceremony::Authentication).is_err());
+
+        let data = client_data("webauthn.get", "challenge", "https://evil.example");
+        assert!(verify_client_data(&data, Ceremony::Authentication).is_err());
+        assert!(verify_client_data(b"not json", Ceremony::Authentication).is_err());
+    }
+
+    #[test]
+    fn test_parse_attestation_object() {
+        let key_pair = key_pair();
+        let public_key = key_pair.public_key().as_ref();
+        let data = authenticator_data(0x45, 0, Some((b"credential", public_key)));
+        let attestation_object = Value::Map(vec![
+            (Value::Text("fmt".to_string()), Value::Text("none".to_string())),
+            (Value::Text("attStmt".to_string()), Value::Map(vec![])),
+            (Value::Text("authData".to_string()), Value::Bytes(data)),
+        ]);
+
+        let parsed = parse_attestation_object(&attestation_object.encode()).unwrap();
+        assert!(parsed.user_verified());
+        assert_eq!(
+            parsed.attested_credential,
+            Some(AttestedCredential { id: b"credential".to_vec(), public_key: public_key.to_vec() })
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_rp_or_absent_user() {
+        let mut data = authenticator_data(0x01, 0, None);
+        assert!(AuthenticatorData::parse(&data).is_ok());
+
+        data[0] ^= 0xff;
+        assert!(AuthenticatorData::parse(&data).is_err());
+
+        assert!(AuthenticatorData::parse(&authenticator_data(0x00, 0, None)).is_err());
+        assert!(AuthenticatorData::parse(&data[..20]).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let key_pair = key_pair();
+        let credential = WebauthnCredential::new(
+            "credential".to_string(),
+            UserId::default(),
+            key_pair.public_key().as_ref().to_vec(),
+            0,
+        );
+        let data = authenticator_data(0x01, 1, None);
+        let client_data = client_data("webauthn.get", "challenge", &WEBAUTHN_ORIGIN);
+
+        let mut message = data.clone();
+        message.extend_from_slice(&Sha256::digest(&client_data));
+        let signature = key_pair.sign(&SystemRandom::new(), &message).unwrap();
+
+        assert!(credential.verify_signature(&data, &client_data, signature.as_ref()));
+        assert!(!credential.verify_signature(&data, b"{}", signature.as_ref()));
+    }
+
+    #[test]
+    fn test_sign_count() {
+        let mut credential = WebauthnCredential::new("credential".to_string(), UserId::default(), vec![], 0);
+        assert!(credential.is_sign_count_valid(0));
+        assert!(credential.is_sign_count_valid(1));
+
+        credential.sign_count = 5;
+        assert!(credential.is_sign_count_valid(6));
+        assert!(!credential.is_sign_count_valid(5));
+        assert!(!credential.is_sign_count_valid(0));
+    }
+}
@@ -0,0 +1,269 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use ring::hmac;
+use uuid::Uuid;
+
+use crate::utils::constants::{WEBHOOK_MAX_ATTEMPTS, WEBHOOK_RETRY_BASE_DELAY_SECONDS, WEBHOOK_RETRY_MAX_DELAY_SECONDS};
+
+// Prefix of signing secrets, so they are recognizable in configuration
+const WEBHOOK_SECRET_PREFIX: &str = "whsec_";
+const WEBHOOK_SECRET_BYTES: usize = 32;
+
+// The events webhooks can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEventType {
+    UserSignedUp,
+    UserLoggedIn,
+    UserLoggedOut,
+    UserPasswordChanged,
+    UserEmailChanged,
+    UserDeleted,
+    UserRestored,
+    UserStatusChanged,
+}
+
+impl WebhookEventType {
+    pub const ALL: [WebhookEventType; 8] = [
+        WebhookEventType::UserSignedUp,
+        WebhookEventType::UserLoggedIn,
+        WebhookEventType::UserLoggedOut,
+        WebhookEventType::UserPasswordChanged,
+        WebhookEventType::UserEmailChanged,
+        WebhookEventType::UserDeleted,
+        WebhookEventType::UserRestored,
+        WebhookEventType::UserStatusChanged,
+    ];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|event_type| event_type.as_str() == s)
+            .ok_or_else(|| format!("Invalid webhook event type: {}", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEventType::UserSignedUp => "user.signed_up",
+            WebhookEventType::UserLoggedIn => "user.logged_in",
+            WebhookEventType::UserLoggedOut => "user.logged_out",
+            WebhookEventType::UserPasswordChanged => "user.password_changed",
+            WebhookEventType::UserEmailChanged => "user.email_changed",
+            WebhookEventType::UserDeleted => "user.deleted",
+            WebhookEventType::UserRestored => "user.restored",
+            WebhookEventType::UserStatusChanged => "user.status_changed",
+        }
+    }
+}
+
+// An endpoint that is sent the events it subscribed to
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    // Shared with the receiver, who checks the signature of each delivery with it
+    pub secret: String,
+    pub event_types: Vec<WebhookEventType>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookSubscription {
+    // Subscribes `url` to the events with a new random signing secret
+    pub fn new(url: String, event_types: Vec<WebhookEventType>) -> Self {
+        let mut bytes = [0u8; WEBHOOK_SECRET_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self {
+            id: Uuid::new_v4(),
+            url,
+            secret: format!("{}{}", WEBHOOK_SECRET_PREFIX, URL_SAFE_NO_PAD.encode(bytes)),
+            event_types,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn subscribes_to(&self, event_type: WebhookEventType) -> bool {
+        self.event_types.contains(&event_type)
+    }
+
+    // Signature of a delivery's body sent at `timestamp`, in Unix seconds. The
+    // timestamp is signed too, so receivers can refuse replays of old deliveries.
+    pub fn sign(&self, timestamp: i64, payload: &str) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, self.secret.as_bytes());
+        let tag = hmac::sign(&key, format!("{}.{}", timestamp, payload).as_bytes());
+        tag.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    // Waiting for its first attempt or a retry
+    Pending,
+    Succeeded,
+    // Given up on after `WEBHOOK_MAX_ATTEMPTS` failed attempts
+    DeadLettered,
+}
+
+impl DeliveryStatus {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "succeeded" => Ok(DeliveryStatus::Succeeded),
+            "dead_lettered" => Ok(DeliveryStatus::DeadLettered),
+            _ => Err(format!("Invalid delivery status: {}", s)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Succeeded => "succeeded",
+            DeliveryStatus::DeadLettered => "dead_lettered",
+        }
+    }
+}
+
+// An event on its way to one subscription, with the outcome of its last attempt
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription_id: Uuid,
+    // Shared by the deliveries of the event to every subscription, so receivers
+    // can tell retries apart from new events
+    pub event_id: Uuid,
+    pub event_type: WebhookEventType,
+    // The JSON body, fixed when the event happened
+    pub payload: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    // When the next attempt is due, while the delivery is pending
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub last_response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookDelivery {
+    pub fn new(subscription_id: Uuid, event_id: Uuid, event_type: WebhookEventType, payload: String) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4(),
+            subscription_id,
+            event_id,
+            event_type,
+            payload,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_attempt_at: None,
+            last_response_status: None,
+            last_error: None,
+            created_at: now,
+        }
+    }
+
+    // Records an attempt that got a response with `Ok(status)`, or failed with
+    // `Err(error)` before one arrived. Anything but a 2xx response is retried
+    // with exponential backoff until the attempts run out.
+    pub fn record_attempt(&mut self, outcome: Result<u16, String>, now: DateTime<Utc>) {
+        self.attempts += 1;
+        self.last_attempt_at = Some(now);
+        let succeeded = match outcome {
+            Ok(status) => {
+                self.last_response_status = Some(status as i32);
+                self.last_error = None;
+                (200..300).contains(&status)
+            }
+            Err(error) => {
+                self.last_response_status = None;
+                self.last_error = Some(error);
+                false
+            }
+        };
+
+        (self.status, self.next_attempt_at) = if succeeded {
+            (DeliveryStatus::Succeeded, None)
+        } else if self.attempts >= WEBHOOK_MAX_ATTEMPTS {
+            (DeliveryStatus::DeadLettered, None)
+        } else {
+            (DeliveryStatus::Pending, Some(now + retry_delay(self.attempts)))
+        };
+    }
+}
+
+// 30 seconds after the first failed attempt, doubling after each one after that
+fn retry_delay(failed_attempts: i32) -> Duration {
+    let doublings = (failed_attempts - 1).clamp(0, 20) as u32;
+    let seconds = WEBHOOK_RETRY_BASE_DELAY_SECONDS.saturating_mul(2i64.pow(doublings));
+    Duration::seconds(seconds.min(WEBHOOK_RETRY_MAX_DELAY_SECONDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivery() -> WebhookDelivery {
+        WebhookDelivery::new(Uuid::new_v4(), Uuid::new_v4(), WebhookEventType::UserLoggedIn, "{}".to_string())
+    }
+
+    #[test]
+    fn test_webhook_event_type_round_trip() {
+        for event_type in WebhookEventType::ALL {
+            assert_eq!(WebhookEventType::parse(event_type.as_str()).unwrap(), event_type);
+        }
+        assert!(WebhookEventType::parse("user.updated").is_err());
+    }
+
+    #[test]
+    fn test_sign() {
+        let subscription = WebhookSubscription::new("https://example.com/hook".to_string(), vec![]);
+        assert!(subscription.secret.starts_with(WEBHOOK_SECRET_PREFIX));
+
+        let signature = subscription.sign(1700000000, "{}");
+        assert_eq!(signature.len(), 64);
+        let key = hmac::Key::new(hmac::HMAC_SHA256, subscription.secret.as_bytes());
+        assert!(hmac::verify(&key, b"1700000000.{}", &hex_decode(&signature)).is_ok());
+
+        // The timestamp is covered too, and every subscription has its own secret
+        assert_ne!(subscription.sign(1700000001, "{}"), signature);
+        let other = WebhookSubscription::new("https://example.com/hook".to_string(), vec![]);
+        assert_ne!(other.sign(1700000000, "{}"), signature);
+    }
+
+    #[test]
+    fn test_failed_attempts_back_off() {
+        let mut delivery = delivery();
+        let now = Utc::now();
+
+        delivery.record_attempt(Ok(500), now);
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.next_attempt_at, Some(now + Duration::seconds(WEBHOOK_RETRY_BASE_DELAY_SECONDS)));
+        assert_eq!(delivery.last_response_status, Some(500));
+
+        delivery.record_attempt(Err("connection refused".to_string()), now);
+        assert_eq!(delivery.next_attempt_at, Some(now + Duration::seconds(2 * WEBHOOK_RETRY_BASE_DELAY_SECONDS)));
+        assert_eq!(delivery.last_response_status, None);
+        assert_eq!(delivery.last_error.as_deref(), Some("connection refused"));
+
+        delivery.record_attempt(Ok(204), now);
+        assert_eq!(delivery.status, DeliveryStatus::Succeeded);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.next_attempt_at, None);
+    }
+
+    #[test]
+    fn test_deliveries_are_dead_lettered_after_max_attempts() {
+        let mut delivery = delivery();
+        for _ in 0..WEBHOOK_MAX_ATTEMPTS {
+            assert_eq!(delivery.status, DeliveryStatus::Pending);
+            delivery.record_attempt(Ok(410), Utc::now());
+        }
+        assert_eq!(delivery.status, DeliveryStatus::DeadLettered);
+        assert_eq!(delivery.next_attempt_at, None);
+        assert!(retry_delay(WEBHOOK_MAX_ATTEMPTS) <= Duration::seconds(WEBHOOK_RETRY_MAX_DELAY_SECONDS));
+    }
+
+    fn hex_decode(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+}
@@ -1,16 +1,21 @@
 use std::error::Error;
+use std::net::SocketAddr;
 use crate::app_state::AppState;
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
 use crate::utils::tracing::{make_span_with_request_id, on_request, on_response};
+use crate::utils::constants::MAGIC_LINK_LOGIN_ENABLED;
 
 use axum::{
-    http::{Method, StatusCode},
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    http::{header, Method, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
-use crate::domain::error::AuthAPIError;
+use crate::domain::error::{AuthAPIError, OAuthError};
+use crate::domain::user::UserStatus;
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, postgres::PgPoolOptions};
 use redis::{Client, RedisResult};
@@ -30,6 +35,12 @@ pub struct ErrorResponse {
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        // Lockouts tell the client how long to wait before trying again
+        let retry_after = match self {
+            AuthAPIError::TooManyAttempts(seconds) => Some(seconds),
+            _ => None,
+        };
+
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
@@ -40,17 +51,64 @@ impl IntoResponse for AuthAPIError {
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
+            AuthAPIError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::AccountPendingDeletion => (StatusCode::FORBIDDEN, "Account is scheduled for deletion"),
+            AuthAPIError::AccountNotActive(status) => (StatusCode::FORBIDDEN, match status {
+                UserStatus::Suspended => "Account is suspended",
+                UserStatus::Locked => "Account is locked",
+                UserStatus::PendingVerification => "Account is pending verification",
+                UserStatus::Active => "Account is not active",
+            }),
+            AuthAPIError::TooManyAttempts(_) => (StatusCode::TOO_MANY_REQUESTS, "Too many failed attempts"),
+            AuthAPIError::LoginAttemptExpired => (StatusCode::GONE, "Too many incorrect codes, please log in again"),
+            AuthAPIError::IdentityProviderNotFound => (StatusCode::NOT_FOUND, "Identity provider not found"),
+            AuthAPIError::FederatedLoginFailed => (StatusCode::UNAUTHORIZED, "Login with identity provider failed"),
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Insufficient permissions"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::RoleNotFound => (StatusCode::NOT_FOUND, "Role not found"),
+            AuthAPIError::RoleNotGranted => (StatusCode::NOT_FOUND, "Role not granted to user"),
+            AuthAPIError::WebhookNotFound => (StatusCode::NOT_FOUND, "Webhook not found"),
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
         });
-        (status, body).into_response()
+
+        match retry_after {
+            Some(seconds) => (status, [(header::RETRY_AFTER, seconds.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            OAuthError::InvalidClient | OAuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            OAuthError::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = Json(ErrorResponse {
+            error: self.code().to_string(),
+        });
+
+        // Resources protected by access tokens tell the client which scheme they expect (RFC 6750 section 3)
+        match self {
+            OAuthError::InvalidToken => (
+                status,
+                [(header::WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#)],
+                body,
+            )
+                .into_response(),
+            _ => (status, body).into_response(),
+        }
     }
 }
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<IntoMakeServiceWithConnectInfo<Router, SocketAddr>, AddExtension<Router, ConnectInfo<SocketAddr>>>,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
@@ -68,20 +126,78 @@ impl Application {
         ];
 
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
 
-        let router = Router::new()
+        let mut router = Router::new()
         .nest_service("/", ServeDir::new("assets"))
         .route("/signup", post(routes::signup))
         .route("/login", post(routes::login))
         .route("/logout", post(routes::logout))
+        .route("/logout-all", post(routes::logout_all))
+        .route("/sessions", get(routes::list_sessions))
+        .route("/sessions/:id", delete(routes::revoke_session))
         .route("/verify-2fa", post(routes::verify_2fa))
+        .route("/resend-2fa", post(routes::resend_2fa))
         .route("/verify-token", post(routes::verify_token))
+        .route("/token/refresh", post(routes::refresh_token))
+        .route("/.well-known/jwks.json", get(routes::jwks))
+        .route("/change-password", post(routes::change_password))
+        .route("/change-email", post(routes::request_email_change))
+        .route("/change-email/confirm", post(routes::confirm_email_change))
+        .route("/account/delete", post(routes::delete_account))
+        .route("/account/restore", post(routes::restore_account))
+        .route("/password-reset/request", post(routes::request_password_reset))
+        .route("/password-reset/confirm", post(routes::confirm_password_reset))
+        .route("/verify-email", post(routes::verify_email))
+        .route("/verify-email/resend", post(routes::resend_verification_email))
+        .route("/2fa/totp/enroll", post(routes::enroll_totp))
+        .route("/2fa/totp/confirm", post(routes::confirm_totp))
+        .route("/webauthn/register/start", post(routes::start_webauthn_registration))
+        .route("/webauthn/register/finish", post(routes::finish_webauthn_registration))
+        .route("/webauthn/login/start", post(routes::start_webauthn_login))
+        .route("/webauthn/login/finish", post(routes::finish_webauthn_login))
+        .route("/authorize", get(routes::authorize).post(routes::authorize_consent))
+        .route("/token", post(routes::exchange_token))
+        .route("/userinfo", get(routes::userinfo).post(routes::userinfo))
+        .route("/login/:provider", get(routes::start_federated_login))
+        .route("/callback/:provider", get(routes::finish_federated_login))
+        .route("/.well-known/openid-configuration", get(routes::openid_configuration))
+        .route(
+            "/2fa/recovery-codes",
+            get(routes::get_recovery_codes_status).post(routes::regenerate_recovery_codes),
+        );
+
+        // Deployments that turn magic links off don't expose the routes at all
+        if *MAGIC_LINK_LOGIN_ENABLED {
+            router = router
+                .route("/login/magic-link", post(routes::request_magic_link))
+                .route("/login/magic-link/consume", post(routes::consume_magic_link));
+        }
+
+        // Everything under /admin is only for admins, see `require_admin`
+        let admin_router = Router::new()
+            .route("/users", get(routes::list_users))
+            .route("/users/:id", get(routes::get_user))
+            .route("/users/:id/status", post(routes::set_user_status))
+            .route("/users/:id/2fa", post(routes::set_user_2fa))
+            .route("/users/:id/password-reset", post(routes::force_password_reset))
+            .route("/users/:id/sessions", delete(routes::revoke_user_sessions))
+            .route("/users/:id/roles", get(routes::list_user_roles).post(routes::grant_role))
+            .route("/users/:id/roles/:role", delete(routes::revoke_role))
+            .route("/audit-log", get(routes::query_audit_log))
+            .route("/audit-log/verify", get(routes::verify_audit_log))
+            .route("/webhooks", get(routes::list_webhooks).post(routes::create_webhook))
+            .route("/webhooks/:id", delete(routes::delete_webhook))
+            .route("/webhooks/:id/deliveries", get(routes::list_webhook_deliveries))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), routes::require_admin));
+
+        let router = router
+        .nest("/admin", admin_router)
         .with_state(app_state)
         .layer(cors)
         .layer( // New!
@@ -96,7 +212,8 @@ impl Application {
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Connect info gives handlers the client's address, e.g. to record it on sessions
+        let server = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>());
 
         // Create a new Application instance and return it
 
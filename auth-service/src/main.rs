@@ -1,41 +1,67 @@
 use auth_service::{Application, get_postgres_pool, get_redis_client};
-use auth_service::data_stores::data_store::{BannedTokenStoreType, TwoFACodeStore, UserStore};
+use auth_service::data_stores::data_store::{BannedTokenStoreType, FailedAttemptStore, PasswordResetTokenStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TokenVersionStore, TotpSecretStore, TwoFACodeStore, UserStore, WebauthnCredentialStore, OAuthClientStore, HashmapIdentityProviderStore, IdentityProviderStore, RoleStore, AuditLog, WebhookStore};
 use auth_service::data_stores::redis_two_fa_code_store::RedisTwoFACodeStore;
 use auth_service::data_stores::postgres_user_store::PostgresUserStore;
+use auth_service::data_stores::postgres_oauth_client_store::PostgresOAuthClientStore;
+use auth_service::data_stores::postgres_role_store::PostgresRoleStore;
+use auth_service::data_stores::postgres_audit_log::PostgresAuditLog;
+use auth_service::data_stores::postgres_webhook_store::PostgresWebhookStore;
+use auth_service::data_stores::postgres_totp_secret_store::PostgresTotpSecretStore;
+use auth_service::data_stores::postgres_recovery_code_store::PostgresRecoveryCodeStore;
+use auth_service::data_stores::postgres_refresh_token_store::PostgresRefreshTokenStore;
+use auth_service::data_stores::postgres_session_store::PostgresSessionStore;
+use auth_service::data_stores::postgres_token_version_store::PostgresTokenVersionStore;
+use auth_service::data_stores::postgres_webauthn_credential_store::PostgresWebauthnCredentialStore;
 use auth_service::data_stores::redis_banned_token_store::RedisBannedTokenStore;
+use auth_service::data_stores::redis_failed_attempt_store::RedisFailedAttemptStore;
+use auth_service::data_stores::redis_password_reset_token_store::RedisPasswordResetTokenStore;
 use auth_service::app_state::AppState;
+use auth_service::services::account_purge::run_account_purge;
+use auth_service::services::webhook_delivery::run_webhook_delivery;
 use auth_service::services::mock_email_client::MockEmailClient;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use axum::response::Html;
-use auth_service::utils::constants::{prod, DATABASE_URL, REDIS_HOST_NAME};
+use auth_service::utils::constants::{load_identity_providers, prod, DATABASE_URL, REDIS_HOST_NAME};
+use auth_service::utils::auth::reload_key_ring_on_sighup;
 use auth_service::utils::tracing::init_tracing;
 use sqlx::PgPool;
 
 #[tokio::main]
 async fn main() {
-    init_tracing();
+    init_tracing().expect("Failed to initialize tracing");
+    tokio::spawn(reload_key_ring_on_sighup());
     let pg_pool = configure_postgresql().await;
 
 
-    let user_store = Arc::new(RwLock::new(Box::new(PostgresUserStore::new(pg_pool)) as Box<dyn UserStore + Send + Sync>));
+    let user_store = Arc::new(RwLock::new(Box::new(PostgresUserStore::new(pg_pool.clone())) as Box<dyn UserStore + Send + Sync>));
     let banned_token_store = Arc::new(RwLock::new(BannedTokenStoreType::Redis(RedisBannedTokenStore::new(Arc::new(RwLock::new(configure_redis()))))));
     let two_fa_code_store = Arc::new(RwLock::new(Box::new(RedisTwoFACodeStore::new(Arc::new(RwLock::new(configure_redis())))) as Box<dyn TwoFACodeStore + Send + Sync>));
-    let email_client = Arc::new(RwLock::new(Box::new(MockEmailClient::default()) as Box<dyn auth_service::domain::EmailClient + Send + Sync>));
-    let app_state = AppState::new(user_store, banned_token_store, two_fa_code_store, email_client);
+    let email_client = Arc::new(RwLock::new(Box::new(MockEmailClient) as Box<dyn auth_service::domain::EmailClient + Send + Sync>));
+    let password_reset_token_store = Arc::new(RwLock::new(Box::new(RedisPasswordResetTokenStore::new(Arc::new(RwLock::new(configure_redis())))) as Box<dyn PasswordResetTokenStore + Send + Sync>));
+    let totp_secret_store = Arc::new(RwLock::new(Box::new(PostgresTotpSecretStore::new(pg_pool.clone())) as Box<dyn TotpSecretStore + Send + Sync>));
+    let recovery_code_store = Arc::new(RwLock::new(Box::new(PostgresRecoveryCodeStore::new(pg_pool.clone())) as Box<dyn RecoveryCodeStore + Send + Sync>));
+    let refresh_token_store = Arc::new(RwLock::new(Box::new(PostgresRefreshTokenStore::new(pg_pool.clone())) as Box<dyn RefreshTokenStore + Send + Sync>));
+    let token_version_store = Arc::new(RwLock::new(Box::new(PostgresTokenVersionStore::new(pg_pool.clone(), Arc::new(RwLock::new(configure_redis())))) as Box<dyn TokenVersionStore + Send + Sync>));
+    let session_store = Arc::new(RwLock::new(Box::new(PostgresSessionStore::new(pg_pool.clone())) as Box<dyn SessionStore + Send + Sync>));
+    let webauthn_credential_store = Arc::new(RwLock::new(Box::new(PostgresWebauthnCredentialStore::new(pg_pool.clone())) as Box<dyn WebauthnCredentialStore + Send + Sync>));
+    let oauth_client_store = Arc::new(RwLock::new(Box::new(PostgresOAuthClientStore::new(pg_pool.clone())) as Box<dyn OAuthClientStore + Send + Sync>));
+    let role_store = Arc::new(RwLock::new(Box::new(PostgresRoleStore::new(pg_pool.clone())) as Box<dyn RoleStore + Send + Sync>));
+    let audit_log = Arc::new(RwLock::new(Box::new(PostgresAuditLog::new(pg_pool.clone())) as Box<dyn AuditLog + Send + Sync>));
+    let webhook_store = Arc::new(RwLock::new(Box::new(PostgresWebhookStore::new(pg_pool)) as Box<dyn WebhookStore + Send + Sync>));
+    let failed_attempt_store = Arc::new(RwLock::new(Box::new(RedisFailedAttemptStore::new(Arc::new(RwLock::new(configure_redis())))) as Box<dyn FailedAttemptStore + Send + Sync>));
+    let identity_provider_store = Arc::new(RwLock::new(Box::new(configure_identity_providers().await) as Box<dyn IdentityProviderStore + Send + Sync>));
+    let app_state = AppState::new(user_store, banned_token_store, two_fa_code_store, email_client, password_reset_token_store, totp_secret_store, recovery_code_store, refresh_token_store, session_store, token_version_store, failed_attempt_store, webauthn_credential_store, oauth_client_store, identity_provider_store, role_store, audit_log, webhook_store);
 
-    let app = Application::build(app_state, &prod::APP_ADDRESS)
+    tokio::spawn(run_account_purge(app_state.clone()));
+    tokio::spawn(run_webhook_delivery(app_state.clone()));
+
+    let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
 
     app.run().await.expect("Failed to run app");
 }
 
-async fn hello_handler() -> Html<&'static str> {
-    // TODO: Update this to a custom message!
-    Html("<h1>Hello, World! This is my first real attempt at Rust from scratch.</h1>")
-}
-
 async fn configure_postgresql() -> PgPool {
     // Create a new database connection pool
     let pg_pool = get_postgres_pool(&DATABASE_URL)
@@ -51,6 +77,17 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
+async fn configure_identity_providers() -> HashmapIdentityProviderStore {
+    let mut store = HashmapIdentityProviderStore::default();
+    for provider in load_identity_providers() {
+        store
+            .add_provider(&provider)
+            .await
+            .expect("Identity provider names must be unique");
+    }
+    store
+}
+
 fn configure_redis() -> redis::Connection {
     get_redis_client(REDIS_HOST_NAME.to_owned())
         .expect("Failed to get Redis client")
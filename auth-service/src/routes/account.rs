@@ -0,0 +1,157 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::domain::password::Password;
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::remove_session_cookies;
+use crate::utils::audit::record_event;
+use crate::utils::auth::authenticate;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::lockout::AttemptCounters;
+use crate::utils::constants::ACCOUNT_DELETION_GRACE_PERIOD_SECONDS;
+use crate::utils::webhooks::publish_event;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Delete account", skip_all)]
+pub async fn delete_account(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<DeleteAccountRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let user = match authenticate(&jar, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // Deleting an account needs the password, not just a session that may have been left open
+    let password = match Password::parse(request.password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    // Guesses share the login counters, so a session left open can't be used to
+    // try passwords without limit
+    let attempts = AttemptCounters::new("login", &user.email, &client);
+    if let Err(e) = attempts.validate_password(&state, &user.email, &password).await {
+        return (jar, Err(e));
+    }
+    if state.user_store.write().await.mark_deleted(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    record_event(&state, &client, AuditEvent::new(AuditEventType::AccountDeleted).user(&user)).await;
+    publish_event(&state, WebhookEventType::UserDeleted, &user).await;
+
+    // Log out everywhere, including this client
+    if state.token_version_store.write().await.bump_version(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    if state.session_store.write().await.revoke_all_sessions(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    if state.refresh_token_store.write().await.revoke_all_families(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    if state.two_fa_code_store.write().await.remove_code(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    if state.password_reset_token_store.write().await.remove_token(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let jar = match remove_session_cookies(&state, jar.clone()).await {
+        Ok(jar) => jar,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // The account has already been deleted, so a failed notification doesn't fail the request
+    let grace_period_days = *ACCOUNT_DELETION_GRACE_PERIOD_SECONDS / 86400;
+    let content = format!(
+        "Your account has been deleted and its data will be removed in {} days. Until then you can restore it with your email and password.",
+        grace_period_days
+    );
+    if let Err(e) = state
+        .email_client
+        .read()
+        .await
+        .send_email(&user.email, "Your account was deleted", &content)
+        .await
+    {
+        tracing::error!("Failed to send account deletion notification: {}", e);
+    }
+
+    let response = Json(AccountResponse {
+        message: "Account scheduled for deletion".to_string(),
+    });
+
+    (jar, Ok((StatusCode::OK, response)))
+}
+
+#[tracing::instrument(name = "Restore account", skip_all, err(Debug))]
+pub async fn restore_account(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<RestoreAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let password = Password::parse(request.password).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    // Restoring takes the password without a session, so it is limited like login
+    // and shares its counters
+    let attempts = AttemptCounters::new("login", &email, &client);
+    attempts.check(&state).await?;
+
+    let mut user_store = state.user_store.write().await;
+
+    // Purged accounts are gone for good and look like any unknown email
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => {
+            drop(user_store);
+            attempts.record_failure(&state).await?;
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+    };
+
+    // A wrong password and an account that isn't deleted get the same error, so the
+    // route can't be used to find out whether a password is right
+    if user_store.validate_user(&email, &password).await.is_err() || !user.is_pending_deletion() {
+        drop(user_store);
+        attempts.record_failure(&state).await?;
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    user_store
+        .restore_user(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+    attempts.clear(&state).await?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::AccountRestored).user(&user)).await;
+    publish_event(&state, WebhookEventType::UserRestored, &user).await;
+
+    let response = Json(AccountResponse {
+        message: "Account restored".to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct RestoreAccountRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountResponse {
+    pub message: String,
+}
@@ -0,0 +1,306 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::UserStoreError;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::password::Password;
+use crate::domain::role::ADMIN_ROLE;
+use crate::domain::user::{TwoFAMethod, User, UserId, UserStatus};
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::{end_all_sessions, send_password_reset_token};
+use crate::utils::audit::record_event;
+use crate::utils::auth::{authenticate_claims, is_admin_api_token};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::{DEFAULT_ADMIN_PAGE_SIZE, MAX_ADMIN_PAGE_SIZE};
+use crate::utils::webhooks::publish_event;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+// Who is making a request to the admin API
+#[derive(Clone, Debug)]
+pub enum Administrator {
+    // A script authenticated with the admin API token, which may do anything
+    ApiToken,
+    // A logged-in user with the admin role, with the permissions of their token
+    User { id: UserId, permissions: Vec<String> },
+}
+
+impl Administrator {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        match self {
+            Administrator::ApiToken => true,
+            Administrator::User { permissions, .. } => permissions.iter().any(|p| p == permission),
+        }
+    }
+}
+
+impl std::fmt::Display for Administrator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Administrator::ApiToken => write!(f, "api-token"),
+            Administrator::User { id, .. } => write!(f, "{}", id),
+        }
+    }
+}
+
+// Guards the admin API. Requests either carry the admin API token as a bearer
+// token, or the auth cookie of a user with the admin role. Handlers find out who
+// made the request through the `Administrator` extension.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AuthAPIError> {
+    let bearer_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let administrator = match bearer_token {
+        Some(token) if is_admin_api_token(token) => Administrator::ApiToken,
+        Some(_) => return Err(AuthAPIError::InvalidToken),
+        // Access tokens issued to OAuth2 clients aren't accepted as auth cookies, so
+        // a client can never act as an admin
+        None => {
+            let (user, claims) = authenticate_claims(&jar, &state).await?;
+            if !claims.roles.iter().any(|role| role == ADMIN_ROLE) {
+                return Err(AuthAPIError::Forbidden);
+            }
+            Administrator::User { id: user.id, permissions: claims.permissions }
+        }
+    };
+
+    request.extensions_mut().insert(administrator);
+    Ok(next.run(request).await)
+}
+
+#[tracing::instrument(name = "List users", skip_all, err(Debug))]
+pub async fn list_users(
+    State(state): State<AppState>,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE_SIZE).clamp(1, MAX_ADMIN_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let search = query.search.as_deref().map(str::trim).filter(|search| !search.is_empty());
+
+    let (users, total) = state
+        .user_store
+        .read()
+        .await
+        .list_users(search, limit, offset)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let users = users.iter().map(AdminUserResponse::from).collect();
+    Ok((StatusCode::OK, Json(UsersResponse { users, total, limit, offset })))
+}
+
+#[tracing::instrument(name = "Get user", skip_all, err(Debug))]
+pub async fn get_user(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &id).await?;
+    Ok((StatusCode::OK, Json(AdminUserResponse::from(&user))))
+}
+
+// Users who aren't active are logged out everywhere, and can't log in again
+// until an admin makes them active
+#[tracing::instrument(name = "Set user status", skip_all, err(Debug))]
+pub async fn set_user_status(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(request): Json<SetUserStatusRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &id).await?;
+    let status = UserStatus::parse(&request.status).map_err(|_| AuthAPIError::MalformedInput)?;
+    let reason = request.reason.map(|reason| reason.trim().to_owned()).filter(|reason| !reason.is_empty());
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_status(&user.id, status, reason.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if status != UserStatus::Active {
+        end_all_sessions(&state, &user).await?;
+    }
+    tracing::info!(admin = %admin, user = %user.id, status = status.as_str(), "Set user status");
+    let details = match reason {
+        Some(reason) => format!("{}: {}", status.as_str(), reason),
+        None => status.as_str().to_owned(),
+    };
+    let event = AuditEvent::new(AuditEventType::UserStatusChanged).user(&user).actor(&admin).details(details);
+    record_event(&state, &client, event).await;
+
+    let user = find_user(&state, &id).await?;
+    publish_event(&state, WebhookEventType::UserStatusChanged, &user).await;
+    Ok((StatusCode::OK, Json(AdminUserResponse::from(&user))))
+}
+
+// Turning 2FA on makes the user confirm logins with emailed codes. Users who
+// already have a second factor keep theirs. Turning it off removes it, e.g. for
+// users who lost their authenticator and recovery codes.
+#[tracing::instrument(name = "Set user 2FA", skip_all, err(Debug))]
+pub async fn set_user_2fa(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(request): Json<SetUser2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &id).await?;
+
+    let method = match (request.requires_2fa, user.two_fa_method) {
+        (true, TwoFAMethod::None) => TwoFAMethod::Email,
+        (true, method) => method,
+        (false, _) => TwoFAMethod::None,
+    };
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&user.email, method)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    tracing::info!(admin = %admin, user = %user.id, method = method.as_str(), "Set 2FA method");
+    let event = AuditEvent::new(AuditEventType::User2FAChanged).user(&user).actor(&admin).details(method.as_str());
+    record_event(&state, &client, event).await;
+
+    let user = find_user(&state, &id).await?;
+    Ok((StatusCode::OK, Json(AdminUserResponse::from(&user))))
+}
+
+// The user's password stops working and they are logged out everywhere, then
+// emailed a password reset token to choose a new one
+#[tracing::instrument(name = "Force password reset", skip_all, err(Debug))]
+pub async fn force_password_reset(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &id).await?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .update_password(&user.email, Password::default())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    end_all_sessions(&state, &user).await?;
+    send_password_reset_token(&state, &user.email).await?;
+    tracing::info!(admin = %admin, user = %user.id, "Forced password reset");
+    record_event(&state, &client, AuditEvent::new(AuditEventType::PasswordResetForced).user(&user).actor(&admin)).await;
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Revoke user sessions", skip_all, err(Debug))]
+pub async fn revoke_user_sessions(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &id).await?;
+
+    end_all_sessions(&state, &user).await?;
+    tracing::info!(admin = %admin, user = %user.id, "Revoked all sessions");
+    record_event(&state, &client, AuditEvent::new(AuditEventType::UserSessionsRevoked).user(&user).actor(&admin)).await;
+
+    Ok(StatusCode::OK)
+}
+
+// Looks up the user the ID in the path belongs to
+pub(crate) async fn find_user(state: &AppState, id: &str) -> Result<User, AuthAPIError> {
+    let user_id = UserId::parse(id).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&user_id)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            _ => AuthAPIError::UnexpectedError,
+        })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListUsersQuery {
+    // Only list users whose email contains this, ignoring case
+    pub search: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsersResponse {
+    pub users: Vec<AdminUserResponse>,
+    // How many users match the search, across all pages
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUserResponse {
+    pub id: String,
+    pub email: String,
+    #[serde(rename = "emailVerified")]
+    pub email_verified: bool,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
+    pub status: String,
+    #[serde(rename = "statusReason")]
+    pub status_reason: Option<String>,
+    #[serde(rename = "statusChangedAt")]
+    pub status_changed_at: Option<String>,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: Option<String>,
+}
+
+impl From<&User> for AdminUserResponse {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            email: user.email.as_ref().to_owned(),
+            email_verified: user.email_verified,
+            requires_2fa: user.requires_2fa(),
+            two_fa_method: user.two_fa_method.as_str().to_owned(),
+            status: user.status.as_str().to_owned(),
+            status_reason: user.status_reason.clone(),
+            status_changed_at: user.status_changed_at.map(|changed_at| changed_at.to_rfc3339()),
+            deleted_at: user.deleted_at.map(|deleted_at| deleted_at.to_rfc3339()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetUserStatusRequest {
+    // One of active, suspended, locked and pending_verification
+    pub status: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetUser2FARequest {
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+}
@@ -0,0 +1,155 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::audit::{AuditEventType, AuditLogFilter, AuditRecord};
+use crate::domain::user::UserId;
+use crate::utils::constants::{DEFAULT_ADMIN_PAGE_SIZE, MAX_ADMIN_PAGE_SIZE};
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+// Newest events first
+#[tracing::instrument(name = "Query audit log", skip_all, err(Debug))]
+pub async fn query_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE_SIZE).clamp(1, MAX_ADMIN_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let filter = AuditLogFilter {
+        event_type: query
+            .event_type
+            .map(|event_type| AuditEventType::parse(&event_type))
+            .transpose()
+            .map_err(|_| AuthAPIError::MalformedInput)?,
+        user_id: query
+            .user_id
+            .map(|user_id| UserId::parse(&user_id))
+            .transpose()
+            .map_err(|_| AuthAPIError::MalformedInput)?,
+        email: query.email.map(|email| email.trim().to_owned()).filter(|email| !email.is_empty()),
+        actor: query.actor,
+        since: parse_time(query.since)?,
+        until: parse_time(query.until)?,
+    };
+
+    let (records, total) = state
+        .audit_log
+        .read()
+        .await
+        .query(&filter, limit, offset)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let events = records.iter().map(AuditEventResponse::from).collect();
+    Ok((StatusCode::OK, Json(AuditLogResponse { events, total, limit, offset })))
+}
+
+// Walks the whole hash chain, so it takes a while on a large log
+#[tracing::instrument(name = "Verify audit log", skip_all, err(Debug))]
+pub async fn verify_audit_log(State(state): State<AppState>) -> Result<impl IntoResponse, AuthAPIError> {
+    let verification = state
+        .audit_log
+        .read()
+        .await
+        .verify()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if let Some(id) = verification.first_invalid_id {
+        tracing::error!(record = id, "Audit log hash chain is broken");
+    }
+
+    let response = AuditLogVerificationResponse {
+        valid: verification.first_invalid_id.is_none(),
+        checked: verification.checked,
+        first_invalid_id: verification.first_invalid_id,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+fn parse_time(time: Option<String>) -> Result<Option<DateTime<Utc>>, AuthAPIError> {
+    time.map(|time| DateTime::parse_from_rfc3339(&time).map(|time| time.with_timezone(&Utc)))
+        .transpose()
+        .map_err(|_| AuthAPIError::MalformedInput)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    // An admin's user ID, or "api-token"
+    pub actor: Option<String>,
+    // RFC 3339 times. Events from `since` on and from before `until` are returned.
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogResponse {
+    pub events: Vec<AuditEventResponse>,
+    // How many events match the filter, across all pages
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEventResponse {
+    pub id: i64,
+    #[serde(rename = "occurredAt")]
+    pub occurred_at: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    #[serde(rename = "userId")]
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    pub actor: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    pub details: Option<String>,
+    #[serde(rename = "prevHash")]
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl From<&AuditRecord> for AuditEventResponse {
+    fn from(record: &AuditRecord) -> Self {
+        let event = &record.event;
+        Self {
+            id: record.id,
+            occurred_at: record.occurred_at.to_rfc3339(),
+            event_type: event.event_type.as_str().to_owned(),
+            user_id: event.user_id.as_ref().map(UserId::to_string),
+            email: event.email.clone(),
+            actor: event.actor.clone(),
+            ip_address: event.ip_address.clone(),
+            user_agent: event.user_agent.clone(),
+            details: event.details.clone(),
+            prev_hash: record.prev_hash.clone(),
+            hash: record.hash.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogVerificationResponse {
+    // Whether every record is intact and follows the one before it
+    pub valid: bool,
+    // How many records, oldest first, were found intact
+    pub checked: i64,
+    #[serde(rename = "firstInvalidId")]
+    pub first_invalid_id: Option<i64>,
+}
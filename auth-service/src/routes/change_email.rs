@@ -0,0 +1,171 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::UserStoreError;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::domain::password::Password;
+use crate::domain::user::{User, UserId};
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::end_all_sessions;
+use crate::utils::audit::record_event;
+use crate::utils::auth::{authenticate, generate_email_change_token, validate_email_change_token};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::lockout::AttemptCounters;
+use crate::utils::webhooks::publish_event;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Request email change", skip_all, err(Debug))]
+pub async fn request_email_change(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let new_email = Email::parse(request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if new_email == user.email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    // A password that doesn't meet the policy can't be the right one
+    let password = Password::parse(request.password).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    // Guesses share the login counters, so a session left open can't be used to
+    // try passwords without limit
+    AttemptCounters::new("login", &user.email, &client)
+        .validate_password(&state, &user.email, &password)
+        .await?;
+
+    match state.user_store.read().await.get_user(&new_email).await {
+        Err(UserStoreError::UserNotFound) => {}
+        Ok(_) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    // Nothing changes until the new address proves it can receive email
+    let token = generate_email_change_token(&user.id, &user.email, &new_email)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let content = format!(
+        "Use the following token to confirm your new email address. It expires in 1 hour: {}",
+        token
+    );
+
+    let email_client = state.email_client.read().await;
+    email_client
+        .send_email(&new_email, "Confirm your new email", &content)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // The confirmation has already gone out, so a failed notice doesn't fail the request
+    let notice = format!(
+        "A request was made to change your email address to {}. If this wasn't you, change your password immediately.",
+        new_email.as_ref()
+    );
+    if let Err(e) = email_client
+        .send_email(&user.email, "Your email is being changed", &notice)
+        .await
+    {
+        tracing::error!("Failed to send email change notice: {}", e);
+    }
+    drop(email_client);
+
+    let event = AuditEvent::new(AuditEventType::EmailChangeRequested).user(&user).details(new_email.as_ref());
+    record_event(&state, &client, event).await;
+
+    let response = Json(ChangeEmailResponse {
+        message: "A confirmation email has been sent to the new address".to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm email change", skip_all, err(Debug))]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<ConfirmEmailChangeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = validate_email_change_token(&request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let user_id = UserId::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    let old_email = Email::parse(claims.from).map_err(|_| AuthAPIError::InvalidToken)?;
+    let new_email = Email::parse(claims.email).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let user = {
+        let mut user_store = state.user_store.write().await;
+
+        let user = user_store.get_user_by_id(&user_id).await.map_err(|_| AuthAPIError::InvalidToken)?;
+
+        // Tokens are single-use: once the email has changed they are no longer accepted
+        if user.email != old_email {
+            return Err(AuthAPIError::InvalidToken);
+        }
+
+        // Blocked and deleted accounts keep the address they had when they were blocked
+        if user.is_pending_deletion() {
+            return Err(AuthAPIError::AccountPendingDeletion);
+        }
+        if !user.is_active() {
+            return Err(AuthAPIError::AccountNotActive(user.status));
+        }
+
+        user_store
+            .update_email(&user_id, &new_email)
+            .await
+            .map_err(|e| match e {
+                UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+                _ => AuthAPIError::UnexpectedError,
+            })?;
+
+        // Following the link proved ownership of the new address
+        user_store
+            .mark_email_verified(&new_email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        User { email: new_email.clone(), ..user }
+    };
+
+    // Magic links, sessions and pending 2FA logins started through the old address
+    // must not keep working, so the user is logged out everywhere
+    end_all_sessions(&state, &user).await?;
+    if state.two_fa_code_store.write().await.remove_code(&old_email).await.is_err() {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+    if state.password_reset_token_store.write().await.remove_token(&old_email).await.is_err() {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    // The event is about the new address, the old one is kept in the details
+    let event = AuditEvent::new(AuditEventType::EmailChanged)
+        .user_id(&user_id)
+        .email(&new_email)
+        .details(old_email.as_ref());
+    record_event(&state, &client, event).await;
+    publish_event(&state, WebhookEventType::UserEmailChanged, &user).await;
+
+    let response = Json(ChangeEmailResponse {
+        message: "Email has been changed".to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeEmailResponse {
+    pub message: String,
+}
@@ -0,0 +1,105 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::password::Password;
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::start_session;
+use crate::utils::audit::record_event;
+use crate::utils::auth::authenticate;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::lockout::AttemptCounters;
+use crate::utils::webhooks::publish_event;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Change password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<ChangePasswordRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let user = match authenticate(&jar, &state).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // A current password that doesn't meet the policy can't be the right one
+    let current_password = match Password::parse(request.current_password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    let new_password = match Password::parse(request.new_password) {
+        Ok(password) if password != current_password => password,
+        _ => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    // Guesses share the login counters, so a session left open can't be used to
+    // try passwords without limit
+    let attempts = AttemptCounters::new("login", &user.email, &client);
+    if let Err(e) = attempts.validate_password(&state, &user.email, &current_password).await {
+        return (jar, Err(e));
+    }
+    if state.user_store.write().await.update_password(&user.email, new_password).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Log out everywhere: invalidate every JWT issued so far and end every session,
+    // so a stolen session can't outlive the old password
+    if state.token_version_store.write().await.bump_version(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    if state.session_store.write().await.revoke_all_sessions(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    if state.refresh_token_store.write().await.revoke_all_families(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // A pending 2FA login was started with the old password, so it must not complete
+    if state.two_fa_code_store.write().await.remove_code(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // ...except for the client that changed the password, which gets a fresh session
+    let (auth_cookie, refresh_cookie) = match start_session(&state, &user, client.clone()).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
+    };
+    record_event(&state, &client, AuditEvent::new(AuditEventType::PasswordChanged).user(&user).details("change")).await;
+    publish_event(&state, WebhookEventType::UserPasswordChanged, &user).await;
+    let jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    // The password has already been changed, so a failed notification doesn't fail the request
+    let content = "Your password was just changed. If this wasn't you, reset your password immediately.";
+    if let Err(e) = state
+        .email_client
+        .read()
+        .await
+        .send_email(&user.email, "Your password was changed", content)
+        .await
+    {
+        tracing::error!("Failed to send password change notification: {}", e);
+    }
+
+    let response = Json(ChangePasswordResponse {
+        message: "Password has been changed".to_string(),
+    });
+
+    (jar, Ok((StatusCode::OK, response)))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
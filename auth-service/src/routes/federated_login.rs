@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Redirect},
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::Deserialize;
+
+use crate::app_state::AppState;
+use crate::data_stores::data_store::{IdentityProviderStoreError, UserStoreError};
+use crate::domain::email::Email;
+use crate::domain::error::AuthAPIError;
+use crate::domain::identity_provider::IdentityProvider;
+use crate::domain::oauth::CodeVerifier;
+use crate::domain::password::Password;
+use crate::domain::user::{TwoFAMethod, User};
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::{end_all_sessions, start_2fa_attempt, start_session};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::utils::audit::record_event;
+use crate::utils::auth::{generate_federated_login_cookie, validate_federated_login_token};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::{FEDERATED_LOGIN_COOKIE_NAME, OIDC_ISSUER};
+use crate::utils::federation::{authorization_url, discover, exchange_code, validate_id_token, FederationError};
+use crate::utils::webhooks::publish_event;
+
+// Sends the user to log in at an identity provider
+#[tracing::instrument(name = "Start federated login", skip_all, err(Debug))]
+pub async fn start_federated_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(provider): Path<String>,
+    Query(request): Query<FederatedLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let provider = get_provider(&state, &provider).await?;
+    let metadata = discover(&provider).await.map_err(|e| login_failed(&provider, e))?;
+
+    let login_state = uuid::Uuid::new_v4().to_string();
+    let nonce = uuid::Uuid::new_v4().to_string();
+    let code_verifier = CodeVerifier::default();
+    let return_to = request.return_to.filter(|return_to| is_allowed_return_to(return_to));
+
+    let cookie = generate_federated_login_cookie(&provider.name, &login_state, &nonce, &code_verifier, return_to)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let url = authorization_url(&provider, &metadata, &callback_uri(&provider), &login_state, &nonce, &code_verifier);
+
+    Ok((jar.add(cookie), Redirect::to(&url)))
+}
+
+// Where the identity provider sends the user back to. Users are matched to
+// accounts by the email the provider verified, and get one if they have none yet.
+#[tracing::instrument(name = "Finish federated login", skip_all)]
+pub async fn finish_federated_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Path(provider): Path<String>,
+    Query(callback): Query<FederatedLoginCallback>,
+) -> (CookieJar, Result<Redirect, AuthAPIError>) {
+    // A login can only be finished once, whatever the outcome
+    let login_token = jar.get(FEDERATED_LOGIN_COOKIE_NAME).map(|cookie| cookie.value().to_owned());
+    let removal_cookie = Cookie::build((FEDERATED_LOGIN_COOKIE_NAME, "")).path("/").removal().build();
+    let jar = jar.add(removal_cookie);
+
+    let (user, return_to) = match authenticate_with_provider(&state, &provider, login_token, callback).await {
+        Ok(authenticated) => authenticated,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // Deleted accounts can only be restored, see `restore_account`
+    if user.is_pending_deletion() {
+        return (jar, Err(AuthAPIError::AccountPendingDeletion));
+    }
+
+    if !user.is_active() {
+        return (jar, Err(AuthAPIError::AccountNotActive(user.status)));
+    }
+
+    // The provider only vouches for the email address, the second factor is still
+    // required. The login page picks up the attempt and asks for the code.
+    if user.two_fa_method != TwoFAMethod::None {
+        let login_attempt_id = match start_2fa_attempt(&state, &user.email, user.two_fa_method).await {
+            Ok(login_attempt_id) => login_attempt_id,
+            Err(e) => return (jar, Err(e)),
+        };
+        let mut params = vec![
+            ("login_attempt_id", login_attempt_id.as_ref().to_owned()),
+            ("email", user.email.as_ref().to_owned()),
+        ];
+        params.extend(return_to.map(|return_to| ("return_to", return_to)));
+        let query = serde_urlencoded::to_string(params).unwrap_or_default();
+        return (jar, Ok(Redirect::to(&format!("/?{}", query))));
+    }
+
+    let (auth_cookie, refresh_cookie) = match start_session(&state, &user, client.clone()).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
+    };
+    let details = format!("federated:{}", provider);
+    record_event(&state, &client, AuditEvent::new(AuditEventType::LoginSucceeded).user(&user).details(details)).await;
+    publish_event(&state, WebhookEventType::UserLoggedIn, &user).await;
+    let jar = jar.add(auth_cookie).add(refresh_cookie);
+    (jar, Ok(Redirect::to(return_to.as_deref().unwrap_or("/"))))
+}
+
+// Checks the callback belongs to a login this browser started, redeems the code
+// and returns the user the provider logged in, with where to send them afterwards
+async fn authenticate_with_provider(
+    state: &AppState,
+    provider: &str,
+    login_token: Option<String>,
+    callback: FederatedLoginCallback,
+) -> Result<(User, Option<String>), AuthAPIError> {
+    let login_token = login_token.ok_or(AuthAPIError::MissingToken)?;
+    let login = validate_federated_login_token(&login_token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Without this check anyone could log a victim into the attacker's account by
+    // sending them to a callback URL with the attacker's code
+    if login.provider != provider || callback.state.as_deref() != Some(login.state.as_str()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    let provider = get_provider(state, provider).await?;
+    if let Some(error) = callback.error {
+        tracing::warn!(provider = %provider.name, error = %error, "Identity provider refused login");
+        return Err(AuthAPIError::FederatedLoginFailed);
+    }
+    let code = callback.code.ok_or(AuthAPIError::MalformedInput)?;
+    let code_verifier = CodeVerifier::parse(login.code_verifier).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let metadata = discover(&provider).await.map_err(|e| login_failed(&provider, e))?;
+    let id_token = exchange_code(&provider, &metadata, &code, &callback_uri(&provider), &code_verifier)
+        .await
+        .map_err(|e| login_failed(&provider, e))?;
+    let claims = validate_id_token(&provider, &metadata, &id_token, &login.nonce)
+        .await
+        .map_err(|e| login_failed(&provider, e))?;
+
+    // Accounts are matched by email, so addresses the provider hasn't verified can't be trusted
+    let email = match (claims.email, claims.email_verified) {
+        (Some(email), Some(true)) => Email::parse(email).map_err(|_| AuthAPIError::FederatedLoginFailed)?,
+        _ => return Err(AuthAPIError::EmailNotVerified),
+    };
+
+    let user = get_or_create_user(state, email).await?;
+    Ok((user, login.return_to))
+}
+
+async fn get_or_create_user(state: &AppState, email: Email) -> Result<User, AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    match user_store.get_user(&email).await {
+        Ok(mut user) => {
+            // Logging in at the provider proved ownership of the address. Whoever
+            // signed up with it before may not have owned it, so the password they
+            // chose stops working and they are logged out everywhere.
+            if !user.email_verified {
+                user_store
+                    .update_password(&user.email, Password::default())
+                    .await
+                    .map_err(|_| AuthAPIError::UnexpectedError)?;
+                end_all_sessions(state, &user).await?;
+                user_store
+                    .mark_email_verified(&user.email)
+                    .await
+                    .map_err(|_| AuthAPIError::UnexpectedError)?;
+                user.email_verified = true;
+            }
+            Ok(user)
+        }
+        Err(UserStoreError::UserNotFound) => {
+            // Nobody knows the password of accounts created here. Users who want
+            // to log in with a password too can set one with a password reset.
+            let mut user = User::new(email, Password::default(), TwoFAMethod::None);
+            user.email_verified = true;
+            user_store
+                .add_user(user.clone())
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            Ok(user)
+        }
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+async fn get_provider(state: &AppState, name: &str) -> Result<IdentityProvider, AuthAPIError> {
+    state
+        .identity_provider_store
+        .read()
+        .await
+        .get_provider(name)
+        .await
+        .map_err(|e| match e {
+            IdentityProviderStoreError::ProviderNotFound => AuthAPIError::IdentityProviderNotFound,
+            _ => AuthAPIError::UnexpectedError,
+        })
+}
+
+fn callback_uri(provider: &IdentityProvider) -> String {
+    format!("{}/callback/{}", *OIDC_ISSUER, provider.name)
+}
+
+// Only logins started by another app through /authorize are continued there, as
+// on the login page, so the parameter can't be used to send users to other sites
+fn is_allowed_return_to(return_to: &str) -> bool {
+    return_to.starts_with("/authorize?")
+}
+
+fn login_failed(provider: &IdentityProvider, e: FederationError) -> AuthAPIError {
+    tracing::warn!(provider = %provider.name, "Login with identity provider failed: {:?}", e);
+    AuthAPIError::FederatedLoginFailed
+}
+
+#[derive(Deserialize)]
+pub struct FederatedLoginRequest {
+    pub return_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FederatedLoginCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    // Set instead of the code when the provider didn't log the user in
+    pub error: Option<String>,
+}
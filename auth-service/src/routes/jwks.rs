@@ -0,0 +1,10 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+
+use crate::utils::auth;
+
+// Public keys other services can use to verify our JWTs locally. Keys that are
+// only HS256 secrets are never published.
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks() -> impl IntoResponse {
+    (StatusCode::OK, Json(auth::jwks()))
+}
@@ -4,16 +4,24 @@ use crate::domain::password::Password;
 use crate::data_stores::data_store::{LoginAttemptId, TwoFACode};
 use serde::{Deserialize, Serialize};
 use axum_extra::extract::CookieJar;
-use crate::utils::auth::generate_auth_cookie;
+use crate::routes::start_session;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::webhook::WebhookEventType;
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::webhooks::publish_event;
+use crate::utils::lockout::AttemptCounters;
+use crate::utils::constants::REQUIRE_EMAIL_VERIFICATION;
 
 use crate::{
     AppState,
-    domain::{error::AuthAPIError, user::User},
+    domain::{error::AuthAPIError, user::{TwoFAMethod, User}},
 };
 
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     // Parse email
@@ -28,50 +36,83 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::MalformedInput)),
     };
     
+    // Refuse locked out accounts and clients before spending an Argon2 verification on them
+    let attempts = AttemptCounters::new("login", &email, &client);
+    if let Err(e) = attempts.check(&state).await {
+        record_event(&state, &client, AuditEvent::new(AuditEventType::LoginFailed).email(&email).details("locked_out")).await;
+        return (jar, Err(e));
+    }
+
     let user_store = state.user_store.read().await;
 
-    // Get user and validate credentials
+    // Get user and validate credentials. Guesses at unknown emails count too, so
+    // they can't be used to probe for accounts without limit.
     let user = match user_store.get_user(&email).await {
         Ok(user) => user,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+        Err(_) => {
+            drop(user_store);
+            record_event(&state, &client, AuditEvent::new(AuditEventType::LoginFailed).email(&email).details("unknown_account")).await;
+            return match attempts.record_failure(&state).await {
+                Ok(()) => (jar, Err(AuthAPIError::InvalidCredentials)),
+                Err(e) => (jar, Err(e)),
+            };
+        }
     };
 
     // Validate password
-    if let Err(_) = user_store.validate_user(&email, &password).await {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    if user_store.validate_user(&email, &password).await.is_err() {
+        drop(user_store);
+        record_event(&state, &client, AuditEvent::new(AuditEventType::LoginFailed).user(&user).details("incorrect_password")).await;
+        return match attempts.record_failure(&state).await {
+            Ok(()) => (jar, Err(AuthAPIError::IncorrectCredentials)),
+            Err(e) => (jar, Err(e)),
+        };
     }
-    
+
+    // Release the lock before issuing tokens
+    drop(user_store);
+
+    // Deleted accounts can only be restored, see `restore_account`. Unverified
+    // emails are only checked after the password so accounts can't be probed for.
+    let refusal = if user.is_pending_deletion() {
+        Some((AuthAPIError::AccountPendingDeletion, "pending_deletion"))
+    } else if !user.is_active() {
+        Some((AuthAPIError::AccountNotActive(user.status), user.status.as_str()))
+    } else if *REQUIRE_EMAIL_VERIFICATION && !user.email_verified {
+        Some((AuthAPIError::EmailNotVerified, "email_not_verified"))
+    } else {
+        None
+    };
+    if let Some((e, reason)) = refusal {
+        record_event(&state, &client, AuditEvent::new(AuditEventType::LoginFailed).user(&user).details(reason)).await;
+        return (jar, Err(e));
+    }
+
+    if let Err(e) = attempts.clear(&state).await {
+        return (jar, Err(e));
+    }
+
     // Handle request based on user's 2FA configuration
-    match user.requires_2fa {
-        true => handle_2fa(&state, &user.email, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&state, &user, client, "password", jar).await,
+        method => handle_2fa(&state, &user.email, method, jar).await,
     }
 }
 
 // New!
-async fn handle_2fa(
+pub(crate) async fn handle_2fa(
     state: &AppState,
     email: &Email,
+    method: TwoFAMethod,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    // Generate a real login attempt ID and 2FA code
-    let login_attempt_id = LoginAttemptId::default();
-    let two_fa_code = TwoFACode::default();
-    
-    // Store the 2FA code in the store
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    if let Err(_) = two_fa_code_store.add_code(email, &login_attempt_id, &two_fa_code).await {
-        return (jar, Err(AuthAPIError::UnexpectedError));
-    }
-
-    // Send 2FA code to user
-    let email_client = state.email_client.read().await;
-    if let Err(_) = email_client.send_email(email, "2FA code", &two_fa_code.as_ref()).await {
-        return (jar, Err(AuthAPIError::UnexpectedError));
-    }
+    let login_attempt_id = match start_2fa_attempt(state, email, method).await {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
     
     (jar, Ok((StatusCode::PARTIAL_CONTENT, Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse { 
         message: "2FA required".to_string(), 
@@ -79,21 +120,64 @@ async fn handle_2fa(
     })))))
 }
 
-// New!
-async fn handle_no_2fa(
+// Starts a login attempt that has to be completed with the second factor
+pub(crate) async fn start_2fa_attempt(
+    state: &AppState,
     email: &Email,
+    method: TwoFAMethod,
+) -> Result<LoginAttemptId, AuthAPIError> {
+    // Generate a real login attempt ID and 2FA code
+    let login_attempt_id = LoginAttemptId::default();
+    let two_fa_code = TwoFACode::default();
+
+    // Store the 2FA code in the store
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_code(email, &login_attempt_id, &two_fa_code)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Send 2FA code to user. TOTP users read their code from an authenticator app instead,
+    // so their stored code is never used; the login attempt ID still ties verification to this login.
+    if method == TwoFAMethod::Email {
+        send_2fa_code(state, email, &two_fa_code).await?;
+    }
+
+    Ok(login_attempt_id)
+}
+
+pub(crate) async fn send_2fa_code(state: &AppState, email: &Email, code: &TwoFACode) -> Result<(), AuthAPIError> {
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "2FA code", code.as_ref())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// New! `login_method` is how the user proved who they are, for the audit log.
+pub(crate) async fn handle_no_2fa(
+    state: &AppState,
+    user: &User,
+    client: ClientInfo,
+    login_method: &str,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    // Generate auth cookie only when 2FA is not required
-    let auth_cookie = match generate_auth_cookie(email) {
-        Ok(cookie) => cookie,
-        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    // Start a session only when 2FA is not required
+    let (auth_cookie, refresh_cookie) = match start_session(state, user, client.clone()).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
     };
+    record_event(state, &client, AuditEvent::new(AuditEventType::LoginSucceeded).user(user).details(login_method)).await;
+    publish_event(state, WebhookEventType::UserLoggedIn, user).await;
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
     (updated_jar, Ok((StatusCode::OK, Json(LoginResponse::RegularAuth))))
 }
 
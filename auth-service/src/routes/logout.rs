@@ -4,13 +4,17 @@ use axum_extra::extract::{cookie, CookieJar};
 use crate::{
     app_state::AppState,
     domain::error::AuthAPIError,
-    data_stores::data_store::{BannedTokenStoreType, BannedTokenStore},
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    data_stores::data_store::BannedTokenStore,
+    domain::{audit::{AuditEvent, AuditEventType}, session::SessionId, user::UserId},
+    routes::revoke_refresh_token,
+    domain::webhook::WebhookEventType,
+    utils::{audit::record_event, auth::validate_token, client_info::ClientInfo, constants::JWT_COOKIE_NAME, webhooks::publish_event},
 };
 
 pub async fn logout(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let cookie = match jar.get(JWT_COOKIE_NAME) {
         Some(cookie) => cookie,
@@ -19,19 +23,26 @@ pub async fn logout(
 
     // Validate token first - only valid tokens should be allowed to logout
     let token = cookie.value().to_owned();
-    {
+    let claims = {
         let banned_store = state.banned_token_store.read().await;
-        if let Err(_) = validate_token(&token, &banned_store).await {
-            return (jar, Err(AuthAPIError::InvalidToken));
+        match validate_token(&token, &banned_store, &state.session_store, &state.token_version_store, &state.user_store).await {
+            Ok(claims) => claims,
+            Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
         }
+    };
+
+    // End the session so it no longer shows up or accepts tokens
+    let user_id = UserId::parse(&claims.sub).ok();
+    if let (Some(user_id), Ok(session_id)) = (&user_id, SessionId::parse(claims.jti)) {
+        // If the session is already gone, that's fine - we can still proceed
+        let _ = state.session_store.write().await.revoke_session(user_id, &session_id).await;
     }
 
     // Ban the token by storing it in the banned token store
     {
         let mut banned_store = state.banned_token_store.write().await;
-        if let Err(_) = banned_store.store_token(token).await {
-            // If token is already banned, that's fine - we can still proceed
-        }
+        // If token is already banned, that's fine - we can still proceed
+        let _ = banned_store.store_token(token).await;
     }
 
     // Remove the cookie by creating a removal cookie
@@ -41,5 +52,20 @@ pub async fn logout(
         .build();
     let jar = jar.add(removal_cookie);
 
+    // Revoke the refresh token too, otherwise it could mint a new session
+    let jar = match revoke_refresh_token(&state, jar.clone()).await {
+        Ok(jar) => jar,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    if let Some(user_id) = &user_id {
+        record_event(&state, &client, AuditEvent::new(AuditEventType::Logout).user_id(user_id)).await;
+        // The token only carries the user's ID, receivers get their email too
+        match state.user_store.read().await.get_user_by_id(user_id).await {
+            Ok(user) => publish_event(&state, WebhookEventType::UserLoggedOut, &user).await,
+            Err(e) => tracing::error!("Failed to look up user for logout webhook: {:?}", e),
+        }
+    }
+
     (jar, Ok(StatusCode::OK))
 }
\ No newline at end of file
@@ -0,0 +1,140 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::BannedTokenStore;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::domain::user::{TwoFAMethod, UserId};
+use crate::routes::{handle_2fa, handle_no_2fa};
+use crate::utils::audit::record_event;
+use crate::utils::auth::{generate_magic_link_token, validate_magic_link_token};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::lockout::AttemptCounters;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Request magic link", skip_all, err(Debug))]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Every request counts, whether an email goes out or not, so the route can't be
+    // used to flood an inbox and the limit doesn't tell which emails have accounts
+    let attempts = AttemptCounters::new("magic_link", &email, &client);
+    attempts.check(&state).await?;
+    attempts.record_failure(&state).await?;
+
+    let response = Json(MagicLinkResponse {
+        message: "If an account exists for this email, a login link has been sent".to_string(),
+    });
+
+    // Respond the same way for unknown emails so the route can't be used to enumerate accounts
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) if !user.is_pending_deletion() && user.is_active() => user,
+        _ => return Ok((StatusCode::OK, response)),
+    };
+
+    let token_version = state
+        .token_version_store
+        .read()
+        .await
+        .get_version(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let token = generate_magic_link_token(&user.id, token_version).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let content = format!(
+        "Use the following link to log in. It expires in 10 minutes and can only be used once: {}",
+        token
+    );
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(&email, "Your login link", &content)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::MagicLinkRequested).user(&user)).await;
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Consume magic link", skip_all)]
+pub async fn consume_magic_link(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<ConsumeMagicLinkRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let claims = match validate_magic_link_token(&request.token) {
+        Ok(claims) => claims,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+    let user_id = match UserId::parse(&claims.sub) {
+        Ok(user_id) => user_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Hold the write lock while checking and banning the token so it can only be used once
+    {
+        let mut banned_token_store = state.banned_token_store.write().await;
+        match banned_token_store.is_token_banned(&request.token).await {
+            Ok(false) => {}
+            Ok(true) => return (jar, Err(AuthAPIError::InvalidToken)),
+            Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        }
+        if banned_token_store.store_token(request.token).await.is_err() {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    let user = match state.user_store.read().await.get_user_by_id(&user_id).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Links sent before the user logged out everywhere or changed their password no longer work
+    match state.token_version_store.read().await.get_version(&user.id).await {
+        Ok(version) if version == claims.ver => {}
+        Ok(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    // Deleted accounts can only be restored, see `restore_account`
+    if user.is_pending_deletion() {
+        return (jar, Err(AuthAPIError::AccountPendingDeletion));
+    }
+
+    if !user.is_active() {
+        return (jar, Err(AuthAPIError::AccountNotActive(user.status)));
+    }
+
+    // Following the link proved ownership of the address
+    if !user.email_verified && state.user_store.write().await.mark_email_verified(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // The link only replaces the password, the second factor is still required
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&state, &user, client, "magic_link", jar).await,
+        method => handle_2fa(&state, &user.email, method, jar).await,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConsumeMagicLinkRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
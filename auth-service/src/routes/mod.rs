@@ -1,12 +1,52 @@
+mod account;
+mod admin;
+mod audit_log;
+mod change_email;
+mod change_password;
+mod federated_login;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth;
+mod oidc;
+mod password_reset;
+mod recovery_codes;
+mod refresh_token;
+mod resend_2fa;
+mod roles;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
+mod webauthn;
+mod webhooks;
 
 // re-export items from sub-modules
+pub use account::*;
+pub use admin::*;
+pub use audit_log::*;
+pub use change_email::*;
+pub use change_password::*;
+pub use federated_login::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use oauth::*;
+pub use oidc::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh_token::*;
+pub use resend_2fa::*;
+pub use roles::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
 pub use verify_2fa::*;
-pub use verify_token::*;
\ No newline at end of file
+pub use verify_email::*;
+pub use verify_token::*;
+pub use webauthn::*;
+pub use webhooks::*;
\ No newline at end of file
@@ -0,0 +1,326 @@
+use axum::{
+    extract::{Query, RawQuery, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::{BannedTokenStore, OAuthClientStoreError};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::error::OAuthError;
+use crate::domain::oauth::{has_scope, CodeChallenge, CodeVerifier, OAuthClient, OPENID_SCOPE};
+use crate::domain::session::Session;
+use crate::domain::user::{User, UserId};
+use crate::utils::audit::record_event;
+use crate::utils::auth::{
+    authenticate, generate_access_token, generate_authorization_code, generate_id_token, issues_id_tokens,
+    validate_authorization_code, TOKEN_TTL_SECONDS,
+};
+use crate::utils::client_info::ClientInfo;
+use crate::app_state::AppState;
+
+// Template of the consent screen, with `{{name}}` placeholders
+const CONSENT_TEMPLATE_PATH: &str = "assets/consent.html";
+
+#[tracing::instrument(name = "Authorize", skip_all)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    RawQuery(query): RawQuery,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Response, OAuthError> {
+    let (client, code_challenge) = match validate_authorization_request(&state, &request).await? {
+        Ok(validated) => validated,
+        Err(redirect) => return Ok(redirect),
+    };
+
+    // Users log in on the usual page, which sends them back here afterwards
+    let user = match authenticate(&jar, &state).await {
+        Ok(user) => user,
+        Err(_) => {
+            let return_to = format!("/authorize?{}", query.unwrap_or_default());
+            let login_query = serde_urlencoded::to_string([("return_to", return_to)])
+                .map_err(|_| OAuthError::ServerError)?;
+            return Ok(Redirect::to(&format!("/?{}", login_query)).into_response());
+        }
+    };
+
+    let template = tokio::fs::read_to_string(CONSENT_TEMPLATE_PATH)
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+    let page = [
+        ("client_name", client.name.as_str()),
+        ("client_id", client.id.as_str()),
+        ("email", user.email.as_ref()),
+        ("redirect_uri", request.redirect_uri.as_str()),
+        ("code_challenge", code_challenge.as_ref()),
+        ("state", request.state.as_deref().unwrap_or_default()),
+        ("scope", request.scope.as_deref().unwrap_or_default()),
+        ("nonce", request.nonce.as_deref().unwrap_or_default()),
+    ]
+    .iter()
+    .fold(template, |page, (name, value)| {
+        page.replace(&format!("{{{{{}}}}}", name), &escape_html(value))
+    });
+
+    // The consent screen must not be framed by another site to trick users into approving
+    Ok(([(header::X_FRAME_OPTIONS, "DENY")], Html(page)).into_response())
+}
+
+// Handles the consent form. Cross-site posts of the form don't carry the auth
+// cookie because it is SameSite=Lax, so other sites can't approve on the user's behalf.
+#[tracing::instrument(name = "Authorize consent", skip_all)]
+pub async fn authorize_consent(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client_info: ClientInfo,
+    Form(consent): Form<ConsentRequest>,
+) -> Result<Response, OAuthError> {
+    let request = consent.request;
+    let (client, code_challenge) = match validate_authorization_request(&state, &request).await? {
+        Ok(validated) => validated,
+        Err(redirect) => return Ok(redirect),
+    };
+
+    let user = authenticate(&jar, &state).await.map_err(|_| OAuthError::InvalidRequest)?;
+
+    if consent.decision != "approve" {
+        return Ok(redirect_to_client(&request, &[("error", "access_denied")]));
+    }
+
+    let token_version = state
+        .token_version_store
+        .read()
+        .await
+        .get_version(&user.id)
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+    let code = generate_authorization_code(
+        &user.id,
+        token_version,
+        &client,
+        &request.redirect_uri,
+        &code_challenge,
+        non_empty(request.scope.clone()),
+        non_empty(request.nonce.clone()),
+    )
+    .map_err(|_| OAuthError::ServerError)?;
+    let event = AuditEvent::new(AuditEventType::OAuthConsentGranted).user(&user).details(client.id.as_str());
+    record_event(&state, &client_info, event).await;
+
+    Ok(redirect_to_client(&request, &[("code", &code)]))
+}
+
+#[tracing::instrument(name = "Exchange authorization code", skip_all, err(Debug))]
+pub async fn exchange_token(
+    State(state): State<AppState>,
+    client_info: ClientInfo,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    if required(request.grant_type)? != "authorization_code" {
+        return Err(OAuthError::UnsupportedGrantType);
+    }
+    let code = required(request.code)?;
+    let client_id = required(request.client_id)?;
+    let redirect_uri = required(request.redirect_uri)?;
+    let code_verifier = CodeVerifier::parse(required(request.code_verifier)?).map_err(|_| OAuthError::InvalidRequest)?;
+
+    let client = get_client(&state, &client_id).await.map_err(|e| match e {
+        OAuthError::InvalidRequest => OAuthError::InvalidClient,
+        e => e,
+    })?;
+
+    let claims = validate_authorization_code(&code).map_err(|_| OAuthError::InvalidGrant)?;
+    if claims.client_id != client.id || claims.redirect_uri != redirect_uri {
+        return Err(OAuthError::InvalidGrant);
+    }
+    // Only whoever started the authorization knows the verifier, so an intercepted code is useless
+    if code_verifier.challenge().as_ref() != claims.code_challenge {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    // Hold the write lock while checking and banning the code so it can only be redeemed once
+    {
+        let mut banned_token_store = state.banned_token_store.write().await;
+        match banned_token_store.is_token_banned(&code).await {
+            Ok(false) => {}
+            Ok(true) => return Err(OAuthError::InvalidGrant),
+            Err(_) => return Err(OAuthError::ServerError),
+        }
+        banned_token_store
+            .store_token(code)
+            .await
+            .map_err(|_| OAuthError::ServerError)?;
+    }
+
+    let user_id = UserId::parse(&claims.sub).map_err(|_| OAuthError::InvalidGrant)?;
+    let user = get_authorizing_user(&state, &user_id, claims.ver).await?;
+
+    // Every access token gets a session of its own, so it shows up in the user's
+    // sessions and can be revoked like any other login
+    let session = Session::new(user.id.clone(), client_info.user_agent.clone(), client_info.ip_address.clone());
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(&session, TOKEN_TTL_SECONDS)
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+    let access_token = generate_access_token(&user.id, &session.id, claims.ver, &client.id, claims.scope.clone())
+        .map_err(|_| OAuthError::ServerError)?;
+    let event = AuditEvent::new(AuditEventType::OAuthTokenIssued).user(&user).details(client.id.as_str());
+    record_event(&state, &client_info, event).await;
+
+    // OpenID Connect logins also tell the client who the user is
+    let id_token = if has_scope(claims.scope.as_deref(), OPENID_SCOPE) && issues_id_tokens() {
+        Some(generate_id_token(&user, &client.id, claims.nonce).map_err(|_| OAuthError::ServerError)?)
+    } else {
+        None
+    };
+
+    let response = Json(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: TOKEN_TTL_SECONDS,
+        scope: claims.scope,
+        id_token,
+    });
+
+    // Responses carrying tokens must not be cached (RFC 6749 section 5.1)
+    Ok((StatusCode::OK, [(header::CACHE_CONTROL, "no-store")], response))
+}
+
+// Checks the client and redirect URI, then the rest of the request. Until the
+// redirect URI is known to belong to the client, errors are returned to the user
+// agent. Afterwards they are reported to the client through the redirect.
+async fn validate_authorization_request(
+    state: &AppState,
+    request: &AuthorizeRequest,
+) -> Result<Result<(OAuthClient, CodeChallenge), Response>, OAuthError> {
+    let client = get_client(state, &request.client_id).await?;
+    if !client.allows_redirect_uri(&request.redirect_uri) {
+        return Err(OAuthError::InvalidRequest);
+    }
+
+    if request.response_type.as_deref() != Some("code") {
+        return Ok(Err(redirect_to_client(request, &[("error", "unsupported_response_type")])));
+    }
+
+    // PKCE is required, and only with SHA-256 since the plain method protects nothing
+    let code_challenge = match (request.code_challenge.clone(), request.code_challenge_method.as_deref()) {
+        (Some(code_challenge), Some("S256")) => CodeChallenge::parse(code_challenge).ok(),
+        _ => None,
+    };
+    match code_challenge {
+        Some(code_challenge) => Ok(Ok((client, code_challenge))),
+        None => Ok(Err(redirect_to_client(request, &[("error", "invalid_request")]))),
+    }
+}
+
+async fn get_client(state: &AppState, client_id: &str) -> Result<OAuthClient, OAuthError> {
+    state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(client_id)
+        .await
+        .map_err(|e| match e {
+            OAuthClientStoreError::ClientNotFound => OAuthError::InvalidRequest,
+            _ => OAuthError::ServerError,
+        })
+}
+
+// The user the code was issued to, as long as they can still log in
+async fn get_authorizing_user(state: &AppState, user_id: &UserId, token_version: i64) -> Result<User, OAuthError> {
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(user_id)
+        .await
+        .map_err(|_| OAuthError::InvalidGrant)?;
+    if user.is_pending_deletion() {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    // Codes issued before the user logged out everywhere or changed their password no longer work
+    match state.token_version_store.read().await.get_version(user_id).await {
+        Ok(version) if version == token_version => Ok(user),
+        Ok(_) => Err(OAuthError::InvalidGrant),
+        Err(_) => Err(OAuthError::ServerError),
+    }
+}
+
+// Redirects to the client with the given parameters and the request's state
+fn redirect_to_client(request: &AuthorizeRequest, params: &[(&str, &str)]) -> Response {
+    let mut params = params.to_vec();
+    if let Some(state) = request.state.as_deref().filter(|state| !state.is_empty()) {
+        params.push(("state", state));
+    }
+
+    let separator = if request.redirect_uri.contains('?') { '&' } else { '?' };
+    let query = serde_urlencoded::to_string(params).unwrap_or_default();
+    Redirect::to(&format!("{}{}{}", request.redirect_uri, separator, query)).into_response()
+}
+
+fn required(value: Option<String>) -> Result<String, OAuthError> {
+    non_empty(value).ok_or(OAuthError::InvalidRequest)
+}
+
+// Empty form fields count as missing
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[derive(Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub state: Option<String>,
+    pub scope: Option<String>,
+    // OpenID Connect clients send a nonce to find in the ID token
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConsentRequest {
+    #[serde(flatten)]
+    pub request: AuthorizeRequest,
+    // "approve" or "deny"
+    pub decision: String,
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub code_verifier: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Only issued when the scope includes "openid"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
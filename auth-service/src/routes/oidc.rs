@@ -0,0 +1,112 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::AppState;
+use crate::domain::error::OAuthError;
+use crate::domain::oauth::OPENID_SCOPE;
+use crate::domain::user::UserId;
+use crate::utils::auth::{issues_id_tokens, signing_algorithm, validate_access_token};
+use crate::utils::constants::OIDC_ISSUER;
+
+// Discovery document OpenID Connect client libraries configure themselves from.
+// ID tokens are signed with the active JWT key, so the openid scope is only
+// offered when the service uses RS256 or EdDSA.
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+pub async fn openid_configuration() -> impl IntoResponse {
+    let issuer = OIDC_ISSUER.as_str();
+    let (id_token_signing_algorithms, scopes) = if issues_id_tokens() {
+        (vec![signing_algorithm()], vec![OPENID_SCOPE.to_owned()])
+    } else {
+        (vec![], vec![])
+    };
+    let configuration = OpenIdConfiguration {
+        issuer: issuer.to_owned(),
+        authorization_endpoint: format!("{}/authorize", issuer),
+        token_endpoint: format!("{}/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        response_types_supported: vec!["code".to_owned()],
+        grant_types_supported: vec!["authorization_code".to_owned()],
+        subject_types_supported: vec!["public".to_owned()],
+        id_token_signing_alg_values_supported: id_token_signing_algorithms,
+        scopes_supported: scopes,
+        claims_supported: ["sub", "iss", "aud", "exp", "iat", "nonce", "email", "email_verified"]
+            .map(str::to_owned)
+            .to_vec(),
+        code_challenge_methods_supported: vec!["S256".to_owned()],
+        // Clients are public, PKCE is what proves who redeems a code
+        token_endpoint_auth_methods_supported: vec!["none".to_owned()],
+    };
+
+    (StatusCode::OK, Json(configuration))
+}
+
+// Claims about the user an access token was issued to
+#[tracing::instrument(name = "Userinfo", skip_all, err(Debug))]
+pub async fn userinfo(State(state): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, OAuthError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(OAuthError::InvalidToken)?;
+
+    let claims = validate_access_token(
+        token,
+        &*state.banned_token_store.read().await,
+        &state.session_store,
+        &state.token_version_store,
+        &state.user_store,
+    )
+    .await
+    .map_err(|_| OAuthError::InvalidToken)?;
+
+    let user_id = UserId::parse(&claims.sub).map_err(|_| OAuthError::InvalidToken)?;
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&user_id)
+        .await
+        .map_err(|_| OAuthError::InvalidToken)?;
+    if user.is_pending_deletion() {
+        return Err(OAuthError::InvalidToken);
+    }
+
+    let response = Json(UserinfoResponse {
+        sub: user.id.to_string(),
+        email: user.email.as_ref().to_owned(),
+        email_verified: user.email_verified,
+    });
+
+    Ok((StatusCode::OK, [(header::CACHE_CONTROL, "no-store")], response))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub scopes_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserinfoResponse {
+    pub sub: String,
+    pub email: String,
+    pub email_verified: bool,
+}
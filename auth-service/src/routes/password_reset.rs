@@ -0,0 +1,189 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::{cookie, CookieJar};
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::{BannedTokenStore, PasswordResetToken};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::domain::password::Password;
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::revoke_refresh_token;
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::lockout::AttemptCounters;
+use crate::utils::constants::JWT_COOKIE_NAME;
+use crate::utils::webhooks::publish_event;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Request password reset", skip_all, err(Debug))]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Every request counts, whether an email goes out or not, so the route can't be
+    // used to flood an inbox and the limit doesn't tell which emails have accounts
+    let attempts = AttemptCounters::new("password_reset", &email, &client);
+    attempts.check(&state).await?;
+    attempts.record_failure(&state).await?;
+
+    let response = Json(PasswordResetResponse {
+        message: "If an account exists for this email, a password reset token has been sent".to_string(),
+    });
+
+    // Respond the same way for unknown emails so the route can't be used to enumerate accounts
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => {
+            let event = AuditEvent::new(AuditEventType::PasswordResetRequested).email(&email).details("unknown_account");
+            record_event(&state, &client, event).await;
+            return Ok((StatusCode::OK, response));
+        }
+    };
+
+    send_password_reset_token(&state, &email).await?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::PasswordResetRequested).user(&user)).await;
+
+    Ok((StatusCode::OK, response))
+}
+
+// Emails the user a new password reset token, replacing any previous one
+pub(crate) async fn send_password_reset_token(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    let token = PasswordResetToken::default();
+
+    state
+        .password_reset_token_store
+        .write()
+        .await
+        .add_token(email, &token)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let content = format!(
+        "Use the following token to reset your password. It expires in 15 minutes: {}",
+        token.as_ref()
+    );
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "Password reset", &content)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    // Parse the new password before touching the token so a rejected password doesn't burn it
+    let password = match Password::parse(request.new_password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let token = match PasswordResetToken::parse(request.token) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Hold the write lock while checking and removing the token so it can only be used once
+    {
+        let mut token_store = state.password_reset_token_store.write().await;
+        match token_store.get_token_hash(&email).await {
+            Ok(hash) if token.matches_hash(&hash) => {}
+            _ => return (jar, Err(AuthAPIError::InvalidToken)),
+        }
+        if token_store.remove_token(&email).await.is_err() {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    let user = {
+        let mut user_store = state.user_store.write().await;
+        if user_store.update_password(&email, password).await.is_err() {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+        match user_store.get_user(&email).await {
+            Ok(user) => user,
+            Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        }
+    };
+
+    record_event(&state, &client, AuditEvent::new(AuditEventType::PasswordChanged).user(&user).details("reset")).await;
+    publish_event(&state, WebhookEventType::UserPasswordChanged, &user).await;
+
+    // A pending 2FA login was started with the old password, so it must not complete
+    if state.two_fa_code_store.write().await.remove_code(&email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Invalidate every JWT issued so far, including ones we have never seen
+    if state.token_version_store.write().await.bump_version(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Sessions started with the old password are logged out everywhere
+    if state.session_store.write().await.revoke_all_sessions(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Refresh tokens issued before the reset must not be able to mint new sessions
+    if state.refresh_token_store.write().await.revoke_all_families(&email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Ban the session the reset was performed from, if any
+    let jar = match jar.get(JWT_COOKIE_NAME) {
+        Some(cookie) => {
+            let token = cookie.value().to_owned();
+            // If token is already banned, that's fine - we can still proceed
+            let _ = state.banned_token_store.write().await.store_token(token).await;
+
+            let removal_cookie = cookie::Cookie::build((JWT_COOKIE_NAME, ""))
+                .path("/")
+                .removal()
+                .build();
+            jar.add(removal_cookie)
+        }
+        None => jar,
+    };
+    let jar = match revoke_refresh_token(&state, jar.clone()).await {
+        Ok(jar) => jar,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let response = Json(PasswordResetResponse {
+        message: "Password has been reset".to_string(),
+    });
+
+    (jar, Ok((StatusCode::OK, response)))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub email: String,
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
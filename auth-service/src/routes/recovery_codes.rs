@@ -0,0 +1,81 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::{email::Email, recovery_code::RecoveryCode};
+use crate::utils::audit::record_event;
+use crate::utils::auth::authenticate;
+use crate::utils::client_info::ClientInfo;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Get recovery codes status", skip_all, err(Debug))]
+pub async fn get_recovery_codes_status(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&jar, &state).await?.email;
+
+    let remaining = state
+        .recovery_code_store
+        .read()
+        .await
+        .remaining_codes(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::OK, Json(RecoveryCodesStatusResponse { remaining })))
+}
+
+#[tracing::instrument(name = "Regenerate recovery codes", skip_all, err(Debug))]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&jar, &state).await?.email;
+
+    // Recovery codes only stand in for a second factor, so there is nothing to issue without one
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if !user.requires_2fa() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::RecoveryCodesRegenerated).user(&user)).await;
+
+    Ok((StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes })))
+}
+
+// Replaces the user's recovery codes with a fresh set. The plaintext codes are
+// only ever returned here, so callers must hand them to the user.
+pub(crate) async fn issue_recovery_codes(state: &AppState, email: &Email) -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_set();
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .replace_codes(email, &codes)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(codes.iter().map(|code| code.as_ref().to_owned()).collect())
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct RecoveryCodesStatusResponse {
+    pub remaining: usize,
+}
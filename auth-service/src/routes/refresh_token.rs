@@ -0,0 +1,163 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::CookieJar;
+
+use crate::data_stores::data_store::{RefreshTokenStoreError, SessionStoreError};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::{email::Email, refresh_token::RefreshToken, session::SessionId};
+use crate::utils::audit::record_event;
+use crate::utils::auth::{create_refresh_cookie, generate_auth_cookie, REFRESH_TOKEN_TTL_SECONDS};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::REFRESH_TOKEN_COOKIE_NAME;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Refresh token", skip_all)]
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let token = match jar.get(REFRESH_TOKEN_COOKIE_NAME) {
+        Some(cookie) => cookie.value().to_owned(),
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(token) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let new_token = RefreshToken::default();
+
+    let rotated = state
+        .refresh_token_store
+        .write()
+        .await
+        .rotate_token(&token, &new_token, REFRESH_TOKEN_TTL_SECONDS)
+        .await;
+
+    let (email, session_id) = match rotated {
+        Ok(session) => session,
+        Err(RefreshTokenStoreError::TokenReused(email, session_id)) => {
+            tracing::warn!("Refresh token reused, revoked its token family");
+            end_reused_session(&state, &client, &email, &session_id).await;
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(RefreshTokenStoreError::UnexpectedError) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Refreshing keeps the session alive, unless it has been revoked in the meantime
+    match state
+        .session_store
+        .write()
+        .await
+        .extend_session(&session_id, REFRESH_TOKEN_TTL_SECONDS)
+        .await
+    {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => {
+            let _ = state.refresh_token_store.write().await.revoke_family(&new_token).await;
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(SessionStoreError::UnexpectedError) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    // Token families are keyed by email, but auth tokens identify users by ID
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // Blocked and deleted users can't keep their sessions going, even once they're let back in
+    let refused = if user.is_pending_deletion() {
+        Some(AuthAPIError::AccountPendingDeletion)
+    } else if !user.is_active() {
+        Some(AuthAPIError::AccountNotActive(user.status))
+    } else {
+        None
+    };
+    if let Some(e) = refused {
+        let _ = state.refresh_token_store.write().await.revoke_family(&new_token).await;
+        return (jar, Err(e));
+    }
+
+    let token_version = match state.token_version_store.read().await.get_version(&user.id).await {
+        Ok(token_version) => token_version,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    // Roles granted since the last refresh show up in the new token
+    let roles = match state.role_store.read().await.get_user_roles(&user.id).await {
+        Ok(roles) => roles,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let auth_cookie = match generate_auth_cookie(&user.id, &session_id, token_version, &roles) {
+        Ok(cookie) => cookie,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    let jar = jar.add(auth_cookie).add(create_refresh_cookie(&new_token));
+
+    (jar, Ok(StatusCode::OK))
+}
+
+// Whoever presented the reused token may hold the family's latest token too, or
+// auth tokens minted from it. Ending the session invalidates all of them.
+async fn end_reused_session(state: &AppState, client: &ClientInfo, email: &Email, session_id: &SessionId) {
+    let event = AuditEvent::new(AuditEventType::RefreshTokenReused);
+    let user = match state.user_store.read().await.get_user(email).await {
+        Ok(user) => user,
+        // Purged users have no sessions left to end
+        Err(_) => return record_event(state, client, event.email(email)).await,
+    };
+
+    match state.session_store.write().await.revoke_session(&user.id, session_id).await {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => tracing::error!("Failed to revoke session of reused refresh token: {:?}", e),
+    }
+    record_event(state, client, event.user(&user)).await;
+}
+
+// Starts a new refresh token family for a fresh session and returns its cookie
+pub(crate) async fn issue_refresh_token(
+    state: &AppState,
+    email: &Email,
+    session_id: &SessionId,
+) -> Result<Cookie<'static>, AuthAPIError> {
+    let token = RefreshToken::default();
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .create_family(email, session_id, &token, REFRESH_TOKEN_TTL_SECONDS)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(create_refresh_cookie(&token))
+}
+
+// Revokes the family of the refresh token in the jar, if any, and removes its cookie
+pub(crate) async fn revoke_refresh_token(state: &AppState, jar: CookieJar) -> Result<CookieJar, AuthAPIError> {
+    let token = match jar.get(REFRESH_TOKEN_COOKIE_NAME) {
+        Some(cookie) => cookie.value().to_owned(),
+        None => return Ok(jar),
+    };
+
+    // Unknown or already revoked tokens have nothing left to revoke
+    if let Ok(token) = RefreshToken::parse(token) {
+        match state.refresh_token_store.write().await.revoke_family(&token).await {
+            Ok(()) | Err(RefreshTokenStoreError::TokenNotFound) => {}
+            Err(_) => return Err(AuthAPIError::UnexpectedError),
+        }
+    }
+
+    let removal_cookie = Cookie::build((REFRESH_TOKEN_COOKIE_NAME, ""))
+        .path("/")
+        .removal()
+        .build();
+
+    Ok(jar.add(removal_cookie))
+}
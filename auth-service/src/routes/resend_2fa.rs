@@ -0,0 +1,75 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
+
+use crate::data_stores::data_store::{LoginAttemptId, TwoFACode, TwoFACodeStoreError};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::domain::user::TwoFAMethod;
+use crate::routes::{send_2fa_code, TwoFactorAuthResponse};
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::{MAX_TWO_FA_CODE_RESENDS, TWO_FA_CODE_RESEND_COOLDOWN_SECONDS};
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Resend 2FA code", skip_all, err(Debug))]
+pub async fn resend_2fa(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<Resend2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id =
+        LoginAttemptId::parse(request.login_attempt_id).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Only emailed codes can be resent, authenticator apps make their own. Users
+    // without emailed codes look like unknown emails, so accounts can't be probed for.
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    if user.two_fa_method != TwoFAMethod::Email {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    // A new code rather than the old one, in case the old one went somewhere it shouldn't
+    let two_fa_code = TwoFACode::default();
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .replace_code(
+            &email,
+            &login_attempt_id,
+            &two_fa_code,
+            TWO_FA_CODE_RESEND_COOLDOWN_SECONDS,
+            MAX_TWO_FA_CODE_RESENDS,
+        )
+        .await
+        .map_err(|e| match e {
+            TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::IncorrectCredentials,
+            TwoFACodeStoreError::ResendThrottled | TwoFACodeStoreError::TooManyResends => {
+                AuthAPIError::TooManyRequests
+            }
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    send_2fa_code(&state, &email, &two_fa_code).await?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::TwoFACodeResent).user(&user)).await;
+
+    let response = Json(TwoFactorAuthResponse {
+        message: "A new 2FA code has been sent".to_string(),
+        login_attempt_id: login_attempt_id.as_ref().to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct Resend2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+}
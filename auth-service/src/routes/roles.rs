@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::RoleStoreError;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::role::{Role, UserRoles, MANAGE_ROLES_PERMISSION};
+use crate::domain::user::UserId;
+use crate::routes::{find_user, Administrator};
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "List user roles", skip_all, err(Debug))]
+pub async fn list_user_roles(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    require_manage_roles(&admin)?;
+    let user_id = find_user(&state, &id).await?.id;
+
+    let roles = get_user_roles(&state, &user_id).await?;
+    Ok((StatusCode::OK, Json(roles)))
+}
+
+// The user gets the role's permissions with their next auth token, when they
+// log in or refresh
+#[tracing::instrument(name = "Grant role", skip_all, err(Debug))]
+pub async fn grant_role(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(request): Json<GrantRoleRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    require_manage_roles(&admin)?;
+    let user = find_user(&state, &id).await?;
+    let user_id = user.id.clone();
+    // No role can be defined with an invalid name
+    let role = Role::parse(request.role).map_err(|_| AuthAPIError::RoleNotFound)?;
+
+    state
+        .role_store
+        .write()
+        .await
+        .grant_role(&user_id, &role)
+        .await
+        .map_err(|e| match e {
+            RoleStoreError::RoleNotFound => AuthAPIError::RoleNotFound,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+    tracing::info!(admin = %admin, user = %user_id, role = role.as_ref(), "Granted role");
+    let event = AuditEvent::new(AuditEventType::RoleGranted).user(&user).actor(&admin).details(role.as_ref());
+    record_event(&state, &client, event).await;
+
+    let roles = get_user_roles(&state, &user_id).await?;
+    Ok((StatusCode::OK, Json(roles)))
+}
+
+// Auth tokens carry the roles they were issued with, so the user's token version
+// is bumped to stop their current tokens from granting the revoked permissions.
+// Refreshing issues tokens without them.
+#[tracing::instrument(name = "Revoke role", skip_all, err(Debug))]
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path((id, role)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    require_manage_roles(&admin)?;
+    let user = find_user(&state, &id).await?;
+    let user_id = user.id.clone();
+    let role = Role::parse(role).map_err(|_| AuthAPIError::RoleNotGranted)?;
+
+    state
+        .role_store
+        .write()
+        .await
+        .revoke_role(&user_id, &role)
+        .await
+        .map_err(|e| match e {
+            RoleStoreError::RoleNotGranted => AuthAPIError::RoleNotGranted,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+    tracing::info!(admin = %admin, user = %user_id, role = role.as_ref(), "Revoked role");
+    let event = AuditEvent::new(AuditEventType::RoleRevoked).user(&user).actor(&admin).details(role.as_ref());
+    record_event(&state, &client, event).await;
+
+    state
+        .token_version_store
+        .write()
+        .await
+        .bump_version(&user_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let roles = get_user_roles(&state, &user_id).await?;
+    Ok((StatusCode::OK, Json(roles)))
+}
+
+// Admins only manage roles if their token grants the permission
+fn require_manage_roles(admin: &Administrator) -> Result<(), AuthAPIError> {
+    if admin.has_permission(MANAGE_ROLES_PERMISSION) {
+        Ok(())
+    } else {
+        Err(AuthAPIError::Forbidden)
+    }
+}
+
+async fn get_user_roles(state: &AppState, user_id: &UserId) -> Result<UserRoles, AuthAPIError> {
+    state
+        .role_store
+        .read()
+        .await
+        .get_user_roles(user_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GrantRoleRequest {
+    pub role: String,
+}
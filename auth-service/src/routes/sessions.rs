@@ -0,0 +1,218 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::SessionStoreError;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::session::{Session, SessionId};
+use crate::domain::user::User;
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::{issue_refresh_token, revoke_refresh_token};
+use crate::utils::audit::record_event;
+use crate::utils::auth::{authenticate_session, generate_auth_cookie, REFRESH_TOKEN_TTL_SECONDS};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::JWT_COOKIE_NAME;
+use crate::utils::webhooks::publish_event;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "List sessions", skip_all, err(Debug))]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (user, current_session_id) = authenticate_session(&jar, &state).await?;
+
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: session.id == current_session_id,
+            id: session.id.as_ref().to_owned(),
+            user_agent: session.user_agent,
+            ip_address: session.ip_address,
+            created_at: session.created_at.to_rfc3339(),
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(SessionsResponse { sessions })))
+}
+
+#[tracing::instrument(name = "Revoke session", skip_all)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (user, current_session_id) = match authenticate_session(&jar, &state).await {
+        Ok(session) => session,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let session_id = match SessionId::parse(id) {
+        Ok(session_id) => session_id,
+        Err(_) => return (jar, Err(AuthAPIError::SessionNotFound)),
+    };
+
+    // Sessions of other users are reported as missing too
+    match state.session_store.write().await.revoke_session(&user.id, &session_id).await {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => return (jar, Err(AuthAPIError::SessionNotFound)),
+        Err(SessionStoreError::UnexpectedError) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+    let event = AuditEvent::new(AuditEventType::SessionRevoked).user(&user).details(session_id.as_ref());
+    record_event(&state, &client, event).await;
+
+    // Revoking the current session logs this client out
+    if session_id != current_session_id {
+        return (jar, Ok(StatusCode::OK));
+    }
+
+    match remove_session_cookies(&state, jar.clone()).await {
+        Ok(jar) => (jar, Ok(StatusCode::OK)),
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+#[tracing::instrument(name = "Log out everywhere", skip_all)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (user, _) = match authenticate_session(&jar, &state).await {
+        Ok(session) => session,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    if state.session_store.write().await.revoke_all_sessions(&user.id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    if state.refresh_token_store.write().await.revoke_all_families(&user.email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    record_event(&state, &client, AuditEvent::new(AuditEventType::SessionRevoked).user(&user).details("all")).await;
+    publish_event(&state, WebhookEventType::UserLoggedOut, &user).await;
+
+    match remove_session_cookies(&state, jar.clone()).await {
+        Ok(jar) => (jar, Ok(StatusCode::OK)),
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+// Registers a new session for a successful login and returns its auth and
+// refresh token cookies
+pub(crate) async fn start_session(
+    state: &AppState,
+    user: &User,
+    client: ClientInfo,
+) -> Result<(Cookie<'static>, Cookie<'static>), AuthAPIError> {
+    let session = Session::new(user.id.clone(), client.user_agent, client.ip_address);
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(&session, REFRESH_TOKEN_TTL_SECONDS)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let token_version = state
+        .token_version_store
+        .read()
+        .await
+        .get_version(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let roles = state
+        .role_store
+        .read()
+        .await
+        .get_user_roles(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let auth_cookie = generate_auth_cookie(&user.id, &session.id, token_version, &roles)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let refresh_cookie = issue_refresh_token(state, &user.email, &session.id).await?;
+
+    Ok((auth_cookie, refresh_cookie))
+}
+
+// Logs the user out everywhere. Their auth tokens stop being valid, their refresh
+// tokens can't start new sessions, and a pending 2FA login can't be completed.
+pub(crate) async fn end_all_sessions(state: &AppState, user: &User) -> Result<(), AuthAPIError> {
+    state
+        .token_version_store
+        .write()
+        .await
+        .bump_version(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .revoke_all_sessions(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_all_families(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Removes the auth cookie and revokes the refresh token the client holds
+pub(crate) async fn remove_session_cookies(state: &AppState, jar: CookieJar) -> Result<CookieJar, AuthAPIError> {
+    let removal_cookie = Cookie::build((JWT_COOKIE_NAME, ""))
+        .path("/")
+        .removal()
+        .build();
+
+    revoke_refresh_token(state, jar.add(removal_cookie)).await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    // Whether this is the session the request was made from
+    pub current: bool,
+}
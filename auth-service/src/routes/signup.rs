@@ -2,34 +2,63 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 use crate::domain::email::Email;
 use crate::domain::password::Password;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::webhook::WebhookEventType;
+use crate::routes::{issue_recovery_codes, send_verification_email};
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::webhooks::publish_event;
 
 use crate::{
     AppState,
-    domain::{error::AuthAPIError, user::User},
+    domain::{error::AuthAPIError, user::{TwoFAMethod, User}},
 };
 
 #[tracing::instrument(name = "Signup", skip_all, err(Debug))]
 pub async fn signup(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
     let password = Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    let mut user_store = state.user_store.write().await;
+    let user = {
+        let mut user_store = state.user_store.write().await;
 
-    // TODO: early return AuthAPIError::UserAlreadyExists if email exists in user_store.
-    if user_store.get_user(&email).await.is_ok() {
-        return Err(AuthAPIError::UserAlreadyExists);
-    }
+        // TODO: early return AuthAPIError::UserAlreadyExists if email exists in user_store.
+        if user_store.get_user(&email).await.is_ok() {
+            return Err(AuthAPIError::UserAlreadyExists);
+        }
+
+        // Signing up with 2FA enables email codes. TOTP is enrolled once logged in.
+        let two_fa_method = if request.requires_2fa { TwoFAMethod::Email } else { TwoFAMethod::None };
+        let user = User::new(email.clone(), password, two_fa_method);
+
+        // TODO: instead of using unwrap, early return AuthAPIError::UnexpectedError if add_user() fails.
+        user_store.add_user(user.clone()).await.map_err(|_| AuthAPIError::UnexpectedError)?;
+        user
+    };
 
-    let user = User::new(email, password, request.requires_2fa);
+    // Signing up with email 2FA enrolls it, so the user gets their recovery codes straight away.
+    // They are only shown in this response, so nothing after this may fail the request.
+    let recovery_codes = if request.requires_2fa {
+        Some(issue_recovery_codes(&state, &email).await?)
+    } else {
+        None
+    };
 
-    // TODO: instead of using unwrap, early return AuthAPIError::UnexpectedError if add_user() fails.
-    user_store.add_user(user).await.map_err(|_| AuthAPIError::UnexpectedError)?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::SignedUp).user(&user)).await;
+    publish_event(&state, WebhookEventType::UserSignedUp, &user).await;
+
+    // The account exists either way, and the user can ask for another email at /verify-email/resend
+    if let Err(e) = send_verification_email(&state, &email).await {
+        tracing::error!("Failed to send verification email: {:?}", e);
+    }
 
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
@@ -46,6 +75,8 @@ pub struct SignupRequest {
 #[derive(Serialize, Debug, Deserialize, PartialEq)]
 pub struct SignupResponse {
     pub message: String,
+    #[serde(rename = "recoveryCodes", skip_serializing_if = "Option::is_none")]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
 // Test comment
\ No newline at end of file
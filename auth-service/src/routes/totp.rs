@@ -0,0 +1,111 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::TwoFACode;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::{totp::TotpSecret, user::TwoFAMethod};
+use crate::routes::issue_recovery_codes;
+use crate::utils::audit::record_event;
+use crate::utils::auth::authenticate;
+use crate::utils::client_info::ClientInfo;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Enroll TOTP", skip_all, err(Debug))]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticate(&jar, &state).await?.email;
+
+    // The new secret stays pending, so an existing authenticator keeps working until this one is confirmed
+    let secret = TotpSecret::default();
+    let otpauth_uri = secret.otpauth_uri(&email).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .totp_secret_store
+        .write()
+        .await
+        .set_pending_secret(&email, &secret)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(TotpEnrollResponse {
+        secret: secret.as_ref().to_owned(),
+        otpauth_uri,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm TOTP", skip_all, err(Debug))]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<TotpConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+    let email = user.email.clone();
+
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let mut totp_secret_store = state.totp_secret_store.write().await;
+
+    let secret = totp_secret_store
+        .get_pending_secret(&email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let step = secret
+        .verify(&email, code.as_ref())
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    totp_secret_store
+        .activate_pending_secret(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    // The code that confirmed the secret can't be used again to log in
+    totp_secret_store
+        .record_used_step(&email, step)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(totp_secret_store);
+
+    let recovery_codes = issue_recovery_codes(&state, &email).await?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::TotpEnabled).user(&user)).await;
+
+    let response = Json(TotpConfirmResponse {
+        message: "Authenticator app enabled".to_string(),
+        recovery_codes,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct TotpEnrollResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct TotpConfirmRequest {
+    pub code: String,
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct TotpConfirmResponse {
+    pub message: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -1,122 +1,220 @@
 use axum::{extract::State, response::IntoResponse, http::StatusCode, Json};
 use crate::{AuthAPIError, AppState};
 use crate::domain::email::Email;
-use crate::data_stores::data_store::{LoginAttemptId, TwoFACode};
-use serde::Deserialize;
-use crate::utils::constants::JWT_COOKIE_NAME;
-use crate::utils::auth::generate_auth_cookie;
+use crate::domain::recovery_code::RecoveryCode;
+use crate::domain::user::TwoFAMethod;
+use crate::data_stores::data_store::{LoginAttemptId, TotpSecretStoreError, TwoFACode, TwoFACodeStoreError};
+use serde::{Deserialize, Serialize};
+use crate::routes::{start_session, verify_passkey_assertion, PasskeyAssertion};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::webhook::WebhookEventType;
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::webhooks::publish_event;
+use crate::utils::lockout::AttemptCounters;
 use axum_extra::extract::CookieJar;
 
+#[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<Verify2FARequest>
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
 
-    println!("=== 2FA VERIFICATION START ===");
-    println!("Request: {:?}", request);
-    
-    println!("Request email: '{}'", request.email);
-    println!("Request loginAttemptId: '{}'", request.loginAttemptId);
-    println!("Request twoFactorCode: '{}'", request.two_factor_code);
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id) {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
 
-    let email = match Email::parse(request.email) {
-        Ok(email) => {
-            println!("✅ Email parsed successfully: {}", email.as_ref());
-            email
+    // The code field also accepts a recovery code in place of the second factor, and
+    // a passkey assertion can be sent instead of a code
+    let submitted_code = match (request.two_factor_code, request.passkey) {
+        (Some(code), None) => match TwoFACode::parse(code.clone()) {
+            Ok(two_fa_code) => SubmittedCode::TwoFA(two_fa_code),
+            Err(_) => match RecoveryCode::parse(code) {
+                Ok(recovery_code) => SubmittedCode::Recovery(recovery_code),
+                Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+            },
         },
-        Err(_) => {
-            println!("❌ Email parsing failed");
-            return (jar, Err(AuthAPIError::InvalidCredentials))
-        }
+        (None, Some(assertion)) => SubmittedCode::Passkey(assertion),
+        _ => return (jar, Err(AuthAPIError::MalformedInput)),
     };
 
-    let login_attempt_id = match LoginAttemptId::parse(request.loginAttemptId) {
-        Ok(login_attempt_id) => {
-            println!("✅ Login attempt ID parsed successfully: {}", login_attempt_id.as_ref());
-            login_attempt_id
-        },
-        Err(_) => {
-            println!("❌ Login attempt ID parsing failed");
-            return (jar, Err(AuthAPIError::InvalidCredentials))
+    // Six digit codes could be enumerated without a limit on guesses
+    let attempts = AttemptCounters::new("2fa", &email, &client);
+    if let Err(e) = attempts.check(&state).await {
+        return (jar, Err(e));
+    }
+
+    // Look up which second factor the user has configured
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(failed_attempt(&state, &attempts).await)),
+    };
+
+    // The login attempt must be the one started when the password was checked
+    let stored_code = match state.two_fa_code_store.read().await.get_code(&email).await {
+        Ok((stored_login_attempt_id, stored_code)) if stored_login_attempt_id == login_attempt_id => stored_code,
+        _ => {
+            tracing::debug!("No pending 2FA login attempt matches");
+            return (jar, Err(failed_attempt(&state, &attempts).await))
         }
     };
 
-    let two_fa_code = match TwoFACode::parse(request.two_factor_code) {
-        Ok(two_fa_code) => {
-            println!("✅ 2FA code parsed successfully: {}", two_fa_code.as_ref());
-            two_fa_code
+    // The account may have been blocked since the password was checked. This is
+    // checked before the code so a refused attempt doesn't burn a recovery code.
+    if !user.is_active() {
+        tracing::warn!(status = user.status.as_str(), "Refused 2FA for an account that is not active");
+        return (jar, Err(AuthAPIError::AccountNotActive(user.status)));
+    }
+
+    let code_matches = match (&submitted_code, user.two_fa_method) {
+        // Recovery codes are burned as soon as they match, so each one works only once
+        (SubmittedCode::Recovery(recovery_code), _) => state
+            .recovery_code_store
+            .write()
+            .await
+            .use_code(&email, recovery_code)
+            .await
+            .is_ok(),
+        // Any of the user's passkeys can stand in for their second factor
+        (SubmittedCode::Passkey(assertion), _) => match verify_passkey_assertion(&state, assertion).await {
+            Ok((credential, _)) => credential.user_id == user.id,
+            Err(AuthAPIError::UnexpectedError) => return (jar, Err(AuthAPIError::UnexpectedError)),
+            Err(_) => false,
         },
-        Err(_) => {
-            println!("❌ 2FA code parsing failed");
-            return (jar, Err(AuthAPIError::InvalidCredentials))
-        }
+        // Each code is accepted only once, so one seen over a shoulder can't be replayed
+        (SubmittedCode::TwoFA(two_fa_code), TwoFAMethod::Totp) => {
+            let mut totp_secret_store = state.totp_secret_store.write().await;
+            let step = match totp_secret_store.get_secret(&email).await {
+                Ok(secret) => secret.verify(&email, two_fa_code.as_ref()),
+                Err(_) => None,
+            };
+            match step {
+                Some(step) => match totp_secret_store.record_used_step(&email, step).await {
+                    Ok(()) => true,
+                    Err(TotpSecretStoreError::CodeAlreadyUsed) => false,
+                    Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+                },
+                None => false,
+            }
+        },
+        (SubmittedCode::TwoFA(two_fa_code), _) => stored_code == *two_fa_code,
     };
 
-    // Verify the 2FA code against stored data
-    println!("🔍 Checking 2FA code in store...");
-    {
-        let two_fa_store = state.two_fa_code_store.read().await;
-        match two_fa_store.get_code(&email).await {
-            Ok((stored_login_attempt_id, stored_code)) => {
-                println!("✅ Found stored code for email: {}", email.as_ref());
-                println!("   Stored login attempt ID: {}", stored_login_attempt_id.as_ref());
-                println!("   Stored 2FA code: {}", stored_code.as_ref());
-                println!("   Provided login attempt ID: {}", login_attempt_id.as_ref());
-                println!("   Provided 2FA code: {}", two_fa_code.as_ref());
-                
-                if stored_login_attempt_id != login_attempt_id || stored_code != two_fa_code {
-                    println!("❌ 2FA verification failed - credentials don't match");
-                    return (jar, Err(AuthAPIError::IncorrectCredentials));
-                }
-                println!("✅ 2FA credentials match!");
+    if !code_matches {
+        record_event(&state, &client, AuditEvent::new(AuditEventType::TwoFAFailed).user(&user).details(submitted_code.kind())).await;
+
+        // Each login attempt only gets a few guesses before its code is thrown away
+        let recorded = state
+            .two_fa_code_store
+            .write()
+            .await
+            .record_failed_attempt(&email, &login_attempt_id)
+            .await;
+        let error = failed_attempt(&state, &attempts).await;
+        return match recorded {
+            Err(TwoFACodeStoreError::TooManyAttempts) => {
+                tracing::warn!("Too many incorrect codes, 2FA code invalidated");
+                (jar, Err(AuthAPIError::LoginAttemptExpired))
             },
-            Err(_) => {
-                println!("❌ No stored 2FA code found for email: {}", email.as_ref());
-                return (jar, Err(AuthAPIError::IncorrectCredentials))
-            }
-        }
+            // The login attempt was completed or replaced in the meantime
+            Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => (jar, Err(error)),
+            Err(_) => (jar, Err(AuthAPIError::UnexpectedError)),
+        };
     }
 
     // Remove the 2FA code from store after successful verification
-    println!("🗑️ Removing 2FA code from store...");
-    {
-        let mut two_fa_store = state.two_fa_code_store.write().await;
-        if let Err(_) = two_fa_store.remove_code(&email).await {
-            println!("❌ Failed to remove 2FA code from store");
-            return (jar, Err(AuthAPIError::UnexpectedError));
-        }
-        println!("✅ 2FA code removed from store");
+    if state.two_fa_code_store.write().await.remove_code(&email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
-    // Generate auth cookie for successful 2FA verification
-    println!("🍪 Generating auth cookie...");
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => {
-            println!("✅ Auth cookie generated successfully");
-            println!("   Cookie name: {}", cookie.name());
-            println!("   Cookie domain: {:?}", cookie.domain());
-            println!("   Cookie path: {:?}", cookie.path());
-            cookie
-        },
-        Err(_) => {
-            println!("❌ Failed to generate auth cookie");
-            return (jar, Err(AuthAPIError::UnexpectedError))
-        }
+    if let Err(e) = attempts.clear(&state).await {
+        return (jar, Err(e));
+    }
+
+    // Report how many recovery codes are left so the UI can warn when they run low
+    let recovery_codes_remaining = match state.recovery_code_store.read().await.remaining_codes(&email).await {
+        Ok(remaining) => remaining,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    // Start a session for the successful 2FA verification
+    let (auth_cookie, refresh_cookie) = match start_session(&state, &user, client.clone()).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(e)),
     };
 
-    let updated_jar = jar.add(auth_cookie);
-    println!("✅ 2FA verification successful! Returning 200 OK");
-    println!("=== 2FA VERIFICATION END ===");
-    (updated_jar, Ok((StatusCode::OK, Json("2FA verification successful"))))
+    record_event(&state, &client, AuditEvent::new(AuditEventType::TwoFASucceeded).user(&user).details(submitted_code.kind())).await;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::LoginSucceeded).user(&user).details("2fa")).await;
+    publish_event(&state, WebhookEventType::UserLoggedIn, &user).await;
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+    let response = Json(Verify2FAResponse {
+        message: "2FA verification successful".to_string(),
+        recovery_codes_remaining,
+    });
+    (updated_jar, Ok((StatusCode::OK, response)))
+}
+
+// Counts a failed verification against the account and client, and returns the error to report
+async fn failed_attempt(state: &AppState, attempts: &AttemptCounters) -> AuthAPIError {
+    match attempts.record_failure(state).await {
+        Ok(()) => AuthAPIError::IncorrectCredentials,
+        Err(e) => e,
+    }
+}
+
+enum SubmittedCode {
+    TwoFA(TwoFACode),
+    Recovery(RecoveryCode),
+    Passkey(PasskeyAssertion),
+}
+
+impl SubmittedCode {
+    // What was used as the second factor, for the audit log
+    fn kind(&self) -> &'static str {
+        match self {
+            SubmittedCode::TwoFA(_) => "code",
+            SubmittedCode::Recovery(_) => "recovery_code",
+            SubmittedCode::Passkey(_) => "passkey",
+        }
+    }
 }
 
 // TODO: implement the Verify2FARequest struct. See the verify-2fa route contract in step 1 for the expected JSON body.
 
-#[derive(Debug, Deserialize)]
+#[derive(Deserialize)]
 pub struct Verify2FARequest {
     pub email: String,
-    pub loginAttemptId: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
     #[serde(rename = "2FACode")]
-    pub two_factor_code: String
+    pub two_factor_code: Option<String>,
+    pub passkey: Option<PasskeyAssertion>,
+}
+
+// The code may be a recovery code, which is only ever stored hashed, so it is
+// kept out of debug output and logs
+impl std::fmt::Debug for Verify2FARequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Verify2FARequest")
+            .field("email", &self.email)
+            .field("login_attempt_id", &self.login_attempt_id)
+            .field("two_factor_code", &self.two_factor_code.as_ref().map(|_| "[redacted]"))
+            .field("passkey", &self.passkey)
+            .finish()
+    }
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct Verify2FAResponse {
+    pub message: String,
+    #[serde(rename = "recoveryCodesRemaining")]
+    pub recovery_codes_remaining: usize,
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::UserStoreError;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::utils::audit::record_event;
+use crate::utils::auth::{generate_email_verification_token, validate_email_verification_token};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::VERIFICATION_EMAIL_COOLDOWN_SECONDS;
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+#[tracing::instrument(name = "Verify email", skip_all, err(Debug))]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = validate_email_verification_token(&request.token)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let mut user_store = state.user_store.write().await;
+
+    let user = user_store.get_user(&email).await.map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Tokens are single-use: once the email is verified they are no longer accepted
+    if user.email_verified {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    user_store
+        .mark_email_verified(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    drop(user_store);
+    record_event(&state, &client, AuditEvent::new(AuditEventType::EmailVerified).user(&user)).await;
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email verified successfully!".to_string(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Resend verification email", skip_all, err(Debug))]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let response = Json(VerifyEmailResponse {
+        message: "If this email needs verifying, a verification email has been sent".to_string(),
+    });
+
+    // Unknown and already verified emails get the same response so accounts can't be enumerated
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(user) if !user.email_verified => {}
+        _ => return Ok((StatusCode::OK, response)),
+    }
+
+    send_verification_email(&state, &email).await?;
+
+    Ok((StatusCode::OK, response))
+}
+
+// Sends a new verification email, subject to the resend cooldown
+pub(crate) async fn send_verification_email(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .user_store
+        .write()
+        .await
+        .record_verification_email_sent(email, VERIFICATION_EMAIL_COOLDOWN_SECONDS)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::VerificationEmailThrottled => AuthAPIError::TooManyRequests,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    let token = generate_email_verification_token(email).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let content = format!(
+        "Use the following token to verify your email address. It expires in 24 hours: {}",
+        token
+    );
+
+    state
+        .email_client
+        .read()
+        .await
+        .send_email(email, "Verify your email", &content)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, Debug, Deserialize, PartialEq)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
@@ -1,7 +1,7 @@
 use axum::{response::IntoResponse, http::StatusCode, Json, extract::State};
-use crate::utils::auth::validate_token;
+use crate::utils::auth::{validate_token, ValidateTokenError};
 use crate::domain::error::AuthAPIError;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use crate::app_state::AppState;
 
 #[derive(Deserialize)]
@@ -9,13 +9,31 @@ pub struct VerifyTokenRequest {
     pub token: String,
 }
 
+// Who the token was issued to and what they may do, so other services can make
+// authorization decisions without decoding the token themselves
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyTokenResponse {
+    #[serde(rename = "userId")]
+    pub user_id: String,
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
 pub async fn verify_token(
     State(state): State<AppState>,
     Json(body): Json<VerifyTokenRequest>
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let banned_store = state.banned_token_store.read().await;
-    match validate_token(&body.token, &banned_store).await {
-        Ok(_claims) => Ok(StatusCode::OK),
-        Err(_) => Err(AuthAPIError::InvalidToken),
+    match validate_token(&body.token, &banned_store, &state.session_store, &state.token_version_store, &state.user_store).await {
+        Ok(claims) => Ok((
+            StatusCode::OK,
+            Json(VerifyTokenResponse {
+                user_id: claims.sub,
+                roles: claims.roles,
+                permissions: claims.permissions,
+            }),
+        )),
+        Err(ValidateTokenError::AccountNotActive(status)) => Err(AuthAPIError::AccountNotActive(status)),
+        Err(ValidateTokenError::TokenError(_)) => Err(AuthAPIError::InvalidToken),
     }
 }
\ No newline at end of file
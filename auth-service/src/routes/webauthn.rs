@@ -0,0 +1,398 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::data_stores::data_store::{BannedTokenStore, WebauthnCredentialStoreError};
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::email::Email;
+use crate::domain::webauthn::{
+    parse_attestation_object, verify_client_data, AuthenticatorData, Ceremony, WebauthnCredential,
+    COSE_ALGORITHM_ES256, WEBAUTHN_RP_NAME,
+};
+use crate::routes::handle_no_2fa;
+use crate::utils::audit::record_event;
+use crate::utils::auth::{
+    authenticate, generate_webauthn_challenge, validate_webauthn_challenge, WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::{REQUIRE_EMAIL_VERIFICATION, WEBAUTHN_RP_ID};
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+#[tracing::instrument(name = "Start passkey registration", skip_all, err(Debug))]
+pub async fn start_webauthn_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let challenge = generate_webauthn_challenge(Ceremony::Registration, Some(&user.id))
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Stops the user from registering the same authenticator twice
+    let exclude_credentials = state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credentials(&user.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .into_iter()
+        .map(|credential| CredentialDescriptor::new(credential.id))
+        .collect();
+
+    let options = PublicKeyCreationOptions {
+        challenge: URL_SAFE_NO_PAD.encode(challenge),
+        rp: RelyingParty {
+            id: WEBAUTHN_RP_ID.clone(),
+            name: WEBAUTHN_RP_NAME.to_string(),
+        },
+        user: WebauthnUser {
+            id: URL_SAFE_NO_PAD.encode(user.id.as_ref().as_bytes()),
+            name: user.email.as_ref().to_owned(),
+            display_name: user.email.as_ref().to_owned(),
+        },
+        pub_key_cred_params: vec![CredentialParameters {
+            kind: PUBLIC_KEY_CREDENTIAL_TYPE.to_string(),
+            alg: COSE_ALGORITHM_ES256,
+        }],
+        timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+        attestation: "none".to_string(),
+        exclude_credentials,
+        authenticator_selection: AuthenticatorSelection {
+            resident_key: "preferred".to_string(),
+            user_verification: "required".to_string(),
+        },
+    };
+
+    Ok((StatusCode::OK, Json(RegistrationOptionsResponse { public_key: options })))
+}
+
+#[tracing::instrument(name = "Finish passkey registration", skip_all, err(Debug))]
+pub async fn finish_webauthn_registration(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<RegisterPasskeyRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = authenticate(&jar, &state).await?;
+
+    let client_data_json = decode_base64url(&request.client_data_json)?;
+    let attestation_object = decode_base64url(&request.attestation_object)?;
+
+    let challenge = verify_client_data(&client_data_json, Ceremony::Registration)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let claims = validate_webauthn_challenge(&challenge, Ceremony::Registration)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Challenges can't be passed on to register a passkey for another account
+    if claims.sub != Some(user.id.to_string()) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    use_challenge(&state, challenge).await?;
+
+    let authenticator_data =
+        parse_attestation_object(&attestation_object).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let attested_credential = authenticator_data
+        .attested_credential
+        .ok_or(AuthAPIError::InvalidCredentials)?;
+
+    let id = URL_SAFE_NO_PAD.encode(attested_credential.id);
+    if id != request.id {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let credential = WebauthnCredential::new(
+        id.clone(),
+        user.id.clone(),
+        attested_credential.public_key,
+        authenticator_data.sign_count,
+    );
+    state
+        .webauthn_credential_store
+        .write()
+        .await
+        .add_credential(&credential)
+        .await
+        .map_err(|e| match e {
+            WebauthnCredentialStoreError::CredentialAlreadyExists => AuthAPIError::InvalidCredentials,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+    record_event(&state, &client, AuditEvent::new(AuditEventType::PasskeyRegistered).user(&user).details(id.clone())).await;
+
+    let response = Json(RegisterPasskeyResponse {
+        message: "Passkey registered".to_string(),
+        id,
+    });
+
+    Ok((StatusCode::CREATED, response))
+}
+
+#[tracing::instrument(name = "Start passkey login", skip_all, err(Debug))]
+pub async fn start_webauthn_login(
+    State(state): State<AppState>,
+    Json(request): Json<PasskeyLoginStartRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    // Without an email the authenticator offers the passkeys it holds for this site.
+    // Unknown emails get no credentials rather than an error, the same as a user
+    // without passkeys.
+    let allow_credentials = match request.email {
+        Some(email) => {
+            let email = Email::parse(email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+            match state.user_store.read().await.get_user(&email).await {
+                Ok(user) => state
+                    .webauthn_credential_store
+                    .read()
+                    .await
+                    .get_credentials(&user.id)
+                    .await
+                    .map_err(|_| AuthAPIError::UnexpectedError)?
+                    .into_iter()
+                    .map(|credential| CredentialDescriptor::new(credential.id))
+                    .collect(),
+                Err(_) => vec![],
+            }
+        }
+        None => vec![],
+    };
+
+    // The credential identifies the user, so login challenges aren't bound to one
+    let challenge = generate_webauthn_challenge(Ceremony::Authentication, None)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let options = PublicKeyRequestOptions {
+        challenge: URL_SAFE_NO_PAD.encode(challenge),
+        rp_id: WEBAUTHN_RP_ID.clone(),
+        timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+        allow_credentials,
+        user_verification: "required".to_string(),
+    };
+
+    Ok((StatusCode::OK, Json(AuthenticationOptionsResponse { public_key: options })))
+}
+
+#[tracing::instrument(name = "Finish passkey login", skip_all)]
+pub async fn finish_webauthn_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(assertion): Json<PasskeyAssertion>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (credential, authenticator_data) = match verify_passkey_assertion(&state, &assertion).await {
+        Ok(verified) => verified,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let user = match state.user_store.read().await.get_user_by_id(&credential.user_id).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    // Deleted accounts can only be restored, see `restore_account`
+    if user.is_pending_deletion() {
+        return (jar, Err(AuthAPIError::AccountPendingDeletion));
+    }
+
+    if !user.is_active() {
+        return (jar, Err(AuthAPIError::AccountNotActive(user.status)));
+    }
+
+    if *REQUIRE_EMAIL_VERIFICATION && !user.email_verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    // A passkey that verified the user with a PIN or biometric is already two factors.
+    // Without that it's only one, and 2FA can't make up for it because the same
+    // passkey would be accepted as the second factor.
+    if !authenticator_data.user_verified() {
+        tracing::warn!(credential = %credential.id, "Passkey login without user verification");
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+    handle_no_2fa(&state, &user, client, "passkey", jar).await
+}
+
+// Checks a passkey assertion against the challenge it answers and the credential
+// that made it, and returns the credential. Each challenge can only be answered once.
+pub(crate) async fn verify_passkey_assertion(
+    state: &AppState,
+    assertion: &PasskeyAssertion,
+) -> Result<(WebauthnCredential, AuthenticatorData), AuthAPIError> {
+    let client_data_json = decode_base64url(&assertion.client_data_json)?;
+    let authenticator_data = decode_base64url(&assertion.authenticator_data)?;
+    let signature = decode_base64url(&assertion.signature)?;
+
+    let challenge = verify_client_data(&client_data_json, Ceremony::Authentication)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    validate_webauthn_challenge(&challenge, Ceremony::Authentication).map_err(|_| AuthAPIError::InvalidToken)?;
+    use_challenge(state, challenge).await?;
+
+    let credential = state
+        .webauthn_credential_store
+        .read()
+        .await
+        .get_credential(&assertion.id)
+        .await
+        .map_err(|e| match e {
+            WebauthnCredentialStoreError::CredentialNotFound => AuthAPIError::IncorrectCredentials,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    if !credential.verify_signature(&authenticator_data, &client_data_json, &signature) {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    let authenticator_data =
+        AuthenticatorData::parse(&authenticator_data).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if !credential.is_sign_count_valid(authenticator_data.sign_count) {
+        tracing::warn!(credential = %credential.id, "Passkey sign count went backwards, it may have been cloned");
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    state
+        .webauthn_credential_store
+        .write()
+        .await
+        .update_sign_count(&credential.id, authenticator_data.sign_count)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((credential, authenticator_data))
+}
+
+// Hold the write lock while checking and banning the challenge so it can only be answered once
+async fn use_challenge(state: &AppState, challenge: String) -> Result<(), AuthAPIError> {
+    let mut banned_token_store = state.banned_token_store.write().await;
+    match banned_token_store.is_token_banned(&challenge).await {
+        Ok(false) => {}
+        Ok(true) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    banned_token_store
+        .store_token(challenge)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+fn decode_base64url(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+// Fields are base64url encoded without padding, as browsers' `toJSON` produces them
+#[derive(Deserialize)]
+pub struct RegisterPasskeyRequest {
+    pub id: String,
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasskeyLoginStartRequest {
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasskeyAssertion {
+    pub id: String,
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegisterPasskeyResponse {
+    pub message: String,
+    pub id: String,
+}
+
+// Options to pass to `navigator.credentials.create()`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistrationOptionsResponse {
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCreationOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingParty,
+    pub user: WebauthnUser,
+    #[serde(rename = "pubKeyCredParams")]
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    // Milliseconds
+    pub timeout: i64,
+    pub attestation: String,
+    #[serde(rename = "excludeCredentials")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "authenticatorSelection")]
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+// Options to pass to `navigator.credentials.get()`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticationOptionsResponse {
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyRequestOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyRequestOptions {
+    pub challenge: String,
+    #[serde(rename = "rpId")]
+    pub rp_id: String,
+    // Milliseconds
+    pub timeout: i64,
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingParty {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebauthnUser {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+impl CredentialDescriptor {
+    fn new(id: String) -> Self {
+        Self { kind: PUBLIC_KEY_CREDENTIAL_TYPE.to_string(), id }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthenticatorSelection {
+    #[serde(rename = "residentKey")]
+    pub resident_key: String,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::data_stores::data_store::WebhookStoreError;
+use crate::domain::audit::{AuditEvent, AuditEventType};
+use crate::domain::webhook::{WebhookDelivery, WebhookEventType, WebhookSubscription};
+use crate::routes::Administrator;
+use crate::utils::audit::record_event;
+use crate::utils::client_info::ClientInfo;
+use crate::utils::constants::{DEFAULT_ADMIN_PAGE_SIZE, MAX_ADMIN_PAGE_SIZE};
+use crate::{app_state::AppState, domain::error::AuthAPIError};
+
+// The signing secret is only ever returned here, receivers have to store it
+#[tracing::instrument(name = "Create webhook", skip_all, err(Debug))]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let url = reqwest::Url::parse(request.url.trim()).map_err(|_| AuthAPIError::MalformedInput)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AuthAPIError::MalformedInput);
+    }
+
+    let mut event_types: Vec<WebhookEventType> = Vec::new();
+    for event in &request.events {
+        let event_type = WebhookEventType::parse(event).map_err(|_| AuthAPIError::MalformedInput)?;
+        if !event_types.contains(&event_type) {
+            event_types.push(event_type);
+        }
+    }
+    if event_types.is_empty() {
+        return Err(AuthAPIError::MalformedInput);
+    }
+
+    let subscription = WebhookSubscription::new(url.to_string(), event_types);
+    state
+        .webhook_store
+        .write()
+        .await
+        .add_subscription(&subscription)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    tracing::info!(admin = %admin, webhook = %subscription.id, "Created webhook");
+    let details = format!("{}: {}", subscription.id, subscription.url);
+    record_event(&state, &client, AuditEvent::new(AuditEventType::WebhookCreated).actor(&admin).details(details)).await;
+
+    let response = CreateWebhookResponse {
+        webhook: WebhookResponse::from(&subscription),
+        secret: subscription.secret.clone(),
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+// Oldest first
+#[tracing::instrument(name = "List webhooks", skip_all, err(Debug))]
+pub async fn list_webhooks(State(state): State<AppState>) -> Result<impl IntoResponse, AuthAPIError> {
+    let subscriptions = state
+        .webhook_store
+        .read()
+        .await
+        .list_subscriptions()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let webhooks = subscriptions.iter().map(WebhookResponse::from).collect();
+    Ok((StatusCode::OK, Json(WebhooksResponse { webhooks })))
+}
+
+// Deliveries that haven't been sent yet are dropped along with their history
+#[tracing::instrument(name = "Delete webhook", skip_all, err(Debug))]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(admin): Extension<Administrator>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let id = parse_webhook_id(&id)?;
+
+    state
+        .webhook_store
+        .write()
+        .await
+        .delete_subscription(&id)
+        .await
+        .map_err(webhook_error)?;
+    tracing::info!(admin = %admin, webhook = %id, "Deleted webhook");
+    record_event(&state, &client, AuditEvent::new(AuditEventType::WebhookDeleted).actor(&admin).details(id.to_string())).await;
+
+    Ok(StatusCode::OK)
+}
+
+// Newest deliveries first, including the ones that are still being retried
+#[tracing::instrument(name = "List webhook deliveries", skip_all, err(Debug))]
+pub async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<WebhookDeliveriesQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let id = parse_webhook_id(&id)?;
+    let limit = query.limit.unwrap_or(DEFAULT_ADMIN_PAGE_SIZE).clamp(1, MAX_ADMIN_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let webhook_store = state.webhook_store.read().await;
+    webhook_store.get_subscription(&id).await.map_err(webhook_error)?;
+    let (deliveries, total) = webhook_store
+        .list_deliveries(&id, limit, offset)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let deliveries = deliveries.iter().map(WebhookDeliveryResponse::from).collect();
+    Ok((StatusCode::OK, Json(WebhookDeliveriesResponse { deliveries, total, limit, offset })))
+}
+
+fn parse_webhook_id(id: &str) -> Result<Uuid, AuthAPIError> {
+    Uuid::parse_str(id).map_err(|_| AuthAPIError::WebhookNotFound)
+}
+
+fn webhook_error(e: WebhookStoreError) -> AuthAPIError {
+    match e {
+        WebhookStoreError::SubscriptionNotFound => AuthAPIError::WebhookNotFound,
+        _ => AuthAPIError::UnexpectedError,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateWebhookRequest {
+    // Where events are posted to, over HTTP or HTTPS
+    pub url: String,
+    // The event types to send, like user.logged_in
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: WebhookResponse,
+    // Receivers check the Webhook-Signature header of each delivery with it
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhooksResponse {
+    pub webhooks: Vec<WebhookResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+impl From<&WebhookSubscription> for WebhookResponse {
+    fn from(subscription: &WebhookSubscription) -> Self {
+        Self {
+            id: subscription.id.to_string(),
+            url: subscription.url.clone(),
+            events: subscription.event_types.iter().map(|event_type| event_type.as_str().to_owned()).collect(),
+            created_at: subscription.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveriesQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveriesResponse {
+    pub deliveries: Vec<WebhookDeliveryResponse>,
+    // How many deliveries the webhook has, across all pages
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookDeliveryResponse {
+    pub id: String,
+    #[serde(rename = "eventId")]
+    pub event_id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    // One of pending, succeeded and dead_lettered
+    pub status: String,
+    pub attempts: i32,
+    #[serde(rename = "nextAttemptAt")]
+    pub next_attempt_at: Option<String>,
+    #[serde(rename = "lastAttemptAt")]
+    pub last_attempt_at: Option<String>,
+    // The receiver's response to the last attempt, if it got that far
+    #[serde(rename = "lastResponseStatus")]
+    pub last_response_status: Option<i32>,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+impl From<&WebhookDelivery> for WebhookDeliveryResponse {
+    fn from(delivery: &WebhookDelivery) -> Self {
+        Self {
+            id: delivery.id.to_string(),
+            event_id: delivery.event_id.to_string(),
+            event_type: delivery.event_type.as_str().to_owned(),
+            status: delivery.status.as_str().to_owned(),
+            attempts: delivery.attempts,
+            next_attempt_at: delivery.next_attempt_at.map(|next_attempt_at| next_attempt_at.to_rfc3339()),
+            last_attempt_at: delivery.last_attempt_at.map(|last_attempt_at| last_attempt_at.to_rfc3339()),
+            last_response_status: delivery.last_response_status,
+            last_error: delivery.last_error.clone(),
+            created_at: delivery.created_at.to_rfc3339(),
+        }
+    }
+}
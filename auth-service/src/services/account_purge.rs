@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use crate::app_state::AppState;
+use crate::data_stores::data_store::UserStoreError;
+use crate::utils::constants::{ACCOUNT_DELETION_GRACE_PERIOD_SECONDS, ACCOUNT_PURGE_INTERVAL_SECONDS};
+
+// Hard-deletes accounts that were deleted more than `grace_period_seconds` ago,
+// along with everything kept about them outside the users table. Returns how
+// many accounts were purged.
+#[tracing::instrument(name = "Purge deleted accounts", skip_all)]
+pub async fn purge_deleted_accounts(state: &AppState, grace_period_seconds: i64) -> Result<usize, UserStoreError> {
+    let users = state
+        .user_store
+        .write()
+        .await
+        .purge_deleted_users(grace_period_seconds)
+        .await?;
+
+    // The users are gone at this point, so a failed cleanup can't be retried on the
+    // next run. Every key left behind has a TTL and expires on its own.
+    for user in &users {
+        if state.session_store.write().await.revoke_all_sessions(&user.id).await.is_err() {
+            tracing::error!("Failed to remove sessions of a purged account");
+        }
+        if state.two_fa_code_store.write().await.remove_code(&user.email).await.is_err() {
+            tracing::error!("Failed to remove 2FA code of a purged account");
+        }
+        if state.password_reset_token_store.write().await.remove_token(&user.email).await.is_err() {
+            tracing::error!("Failed to remove password reset token of a purged account");
+        }
+        if state.token_version_store.write().await.remove_version(&user.id).await.is_err() {
+            tracing::error!("Failed to remove token version of a purged account");
+        }
+    }
+
+    Ok(users.len())
+}
+
+// Periodically purges accounts whose deletion grace period has ended
+pub async fn run_account_purge(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(ACCOUNT_PURGE_INTERVAL_SECONDS));
+
+    loop {
+        interval.tick().await;
+        match purge_deleted_accounts(&state, *ACCOUNT_DELETION_GRACE_PERIOD_SECONDS).await {
+            Ok(0) => {}
+            Ok(purged) => tracing::info!(purged, "Purged deleted accounts"),
+            Err(e) => tracing::error!("Failed to purge deleted accounts: {:?}", e),
+        }
+    }
+}
@@ -1 +1,3 @@
-pub mod mock_email_client;
\ No newline at end of file
+pub mod account_purge;
+pub mod mock_email_client;
+pub mod webhook_delivery;
\ No newline at end of file
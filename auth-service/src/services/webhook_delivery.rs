@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use lazy_static::lazy_static;
+
+use crate::app_state::AppState;
+use crate::data_stores::data_store::WebhookStoreError;
+use crate::domain::webhook::{DeliveryStatus, WebhookDelivery, WebhookSubscription};
+use crate::utils::constants::{
+    WEBHOOK_DELIVERY_BATCH_SIZE, WEBHOOK_DELIVERY_INTERVAL_SECONDS, WEBHOOK_DELIVERY_LEASE_SECONDS, WEBHOOK_TIMEOUT_SECONDS,
+};
+
+lazy_static! {
+    // Shared by all deliveries so connections to receivers are reused. Redirects
+    // aren't followed, receivers have to give the URL they want events sent to.
+    static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECONDS))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build HTTP client");
+}
+
+// Sends the deliveries that are due, and schedules a retry for each one that
+// fails until it runs out of attempts. Returns how many deliveries were attempted.
+#[tracing::instrument(name = "Deliver due webhooks", skip_all)]
+pub async fn deliver_due_webhooks(state: &AppState) -> Result<usize, WebhookStoreError> {
+    let deliveries = state
+        .webhook_store
+        .write()
+        .await
+        .claim_due_deliveries(WEBHOOK_DELIVERY_BATCH_SIZE, WEBHOOK_DELIVERY_LEASE_SECONDS)
+        .await?;
+
+    for mut delivery in deliveries.iter().cloned() {
+        // Deliveries of a deleted subscription are deleted along with it
+        let subscription = match state.webhook_store.read().await.get_subscription(&delivery.subscription_id).await {
+            Ok(subscription) => subscription,
+            Err(WebhookStoreError::SubscriptionNotFound) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let outcome = send(&subscription, &delivery).await;
+        delivery.record_attempt(outcome, Utc::now());
+        if delivery.status == DeliveryStatus::DeadLettered {
+            tracing::warn!(
+                delivery = %delivery.id,
+                subscription = %subscription.id,
+                attempts = delivery.attempts,
+                "Gave up on webhook delivery"
+            );
+        }
+
+        match state.webhook_store.write().await.update_delivery(&delivery).await {
+            Ok(()) | Err(WebhookStoreError::DeliveryNotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(deliveries.len())
+}
+
+// Posts the delivery's payload to the subscription's URL. The signature covers
+// the time of sending, so it is computed anew for every attempt.
+async fn send(subscription: &WebhookSubscription, delivery: &WebhookDelivery) -> Result<u16, String> {
+    let timestamp = Utc::now().timestamp();
+    let signature = subscription.sign(timestamp, &delivery.payload);
+
+    HTTP_CLIENT
+        .post(&subscription.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("Webhook-Id", delivery.event_id.to_string())
+        .header("Webhook-Timestamp", timestamp.to_string())
+        .header("Webhook-Signature", format!("v1={}", signature))
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map(|response| response.status().as_u16())
+        .map_err(|e| e.without_url().to_string())
+}
+
+// Periodically sends the webhook deliveries that are due
+pub async fn run_webhook_delivery(state: AppState) {
+    let mut interval = tokio::time::interval(Duration::from_secs(WEBHOOK_DELIVERY_INTERVAL_SECONDS));
+
+    loop {
+        interval.tick().await;
+        match deliver_due_webhooks(&state).await {
+            Ok(0) => {}
+            Ok(attempted) => tracing::info!(attempted, "Attempted webhook deliveries"),
+            Err(e) => tracing::error!("Failed to deliver webhooks: {:?}", e),
+        }
+    }
+}
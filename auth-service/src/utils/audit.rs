@@ -0,0 +1,19 @@
+use crate::app_state::AppState;
+use crate::domain::audit::AuditEvent;
+use crate::utils::client_info::ClientInfo;
+
+// Writes the event to the audit log, along with the client the request came from.
+// Requests still succeed when the log can't be written, so an audit log outage
+// doesn't lock users out.
+pub async fn record_event(state: &AppState, client: &ClientInfo, event: AuditEvent) {
+    let event_type = event.event_type;
+    let event = AuditEvent {
+        ip_address: client.ip_address.clone(),
+        user_agent: client.user_agent.clone(),
+        ..event
+    };
+
+    if let Err(e) = state.audit_log.write().await.append(event).await {
+        tracing::error!(event_type = event_type.as_str(), "Failed to write audit log: {:?}", e);
+    }
+}
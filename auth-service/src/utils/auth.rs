@@ -1,24 +1,94 @@
 use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
-use serde::{Deserialize, Serialize};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm};
+use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard};
 
+use crate::app_state::{AppState, SessionStoreType, TokenVersionStoreType, UserStoreType};
 use crate::domain::email::Email;
+use crate::domain::oauth::{CodeChallenge, CodeVerifier, OAuthClient};
+use crate::domain::refresh_token::RefreshToken;
+use crate::domain::role::UserRoles;
+use crate::domain::error::AuthAPIError;
+use crate::domain::session::SessionId;
+use crate::domain::user::{User, UserId, UserStatus};
+use crate::domain::webauthn::Ceremony;
 use crate::data_stores::data_store::{BannedTokenStoreType, BannedTokenStore};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
-
+use super::constants::{
+    load_jwt_key_ring, ADMIN_API_TOKEN, FEDERATED_LOGIN_COOKIE_NAME, JWT_COOKIE_NAME, OIDC_ISSUER, REFRESH_TOKEN_COOKIE_NAME,
+};
+use super::signing_key::KeyRing;
 
+lazy_static! {
+    // Keys JWTs are signed and verified with. Replaced by `reload_key_ring`.
+    static ref JWT_KEY_RING: RwLock<KeyRing> =
+        RwLock::new(load_jwt_key_ring(false).unwrap_or_else(|e| panic!("{}", e)));
+}
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token for the given session and token version,
+// carrying the user's roles and permissions
+pub fn generate_auth_cookie(
+    user_id: &UserId,
+    session_id: &SessionId,
+    token_version: i64,
+    roles: &UserRoles,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(user_id, session_id, token_version, roles)?;
     Ok(create_auth_cookie(token))
 }
 
 // Create cookie and set the value to the passed-in token string 
 fn create_auth_cookie(token: String) -> Cookie<'static> {
-    let mut cookie_builder = Cookie::build((JWT_COOKIE_NAME, token))
+    create_cookie(JWT_COOKIE_NAME, token)
+}
+
+// Create the refresh token cookie. Unlike the auth cookie it outlives the browser
+// session, for as long as the refresh token itself is valid.
+pub fn create_refresh_cookie(token: &RefreshToken) -> Cookie<'static> {
+    let mut cookie = create_cookie(REFRESH_TOKEN_COOKIE_NAME, token.as_ref().to_owned());
+    cookie.set_max_age(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS));
+    cookie
+}
+
+// Create the cookie that remembers a login through an identity provider until the
+// browser comes back to the callback
+pub fn generate_federated_login_cookie(
+    provider: &str,
+    state: &str,
+    nonce: &str,
+    code_verifier: &CodeVerifier,
+    return_to: Option<String>,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let exp = compute_expiry(FEDERATED_LOGIN_TTL_SECONDS)?;
+
+    let claims = FederatedLoginClaims {
+        provider: provider.to_owned(),
+        state: state.to_owned(),
+        nonce: nonce.to_owned(),
+        code_verifier: code_verifier.as_ref().to_owned(),
+        return_to,
+        aud: FEDERATED_LOGIN_AUDIENCE.to_owned(),
+        exp,
+    };
+    let token = create_token(&claims).map_err(GenerateTokenError::TokenError)?;
+
+    let mut cookie = create_cookie(FEDERATED_LOGIN_COOKIE_NAME, token);
+    cookie.set_max_age(time::Duration::seconds(FEDERATED_LOGIN_TTL_SECONDS));
+    Ok(cookie)
+}
+
+// Check if a federated login cookie is valid by decoding it using the key ring
+pub fn validate_federated_login_token(token: &str) -> Result<FederatedLoginClaims, jsonwebtoken::errors::Error> {
+    decode_token::<FederatedLoginClaims>(token, Some(FEDERATED_LOGIN_AUDIENCE))
+}
+
+fn create_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    let mut cookie_builder = Cookie::build((name, value))
         .path("/") // apple cookie to all URLs on the server
         .http_only(true) // prevent JavaScript from accessing the cookie
         .same_site(SameSite::Lax); // send cookie with "same-site" requests, and with "cross-site" top-level navigations.
@@ -38,12 +108,268 @@ pub enum GenerateTokenError {
     UnexpectedError,
 }
 
+#[derive(Debug)]
+pub enum ValidateTokenError {
+    TokenError(jsonwebtoken::errors::Error),
+    // The token is otherwise valid, but the user it was issued to isn't active
+    AccountNotActive(UserStatus),
+}
+
+impl From<jsonwebtoken::errors::Error> for ValidateTokenError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        ValidateTokenError::TokenError(e)
+    }
+}
+
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+// This value determines how long a refresh token is valid for. Rotating a token
+// issues a new one with a fresh lifetime.
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 2_592_000; // 30 days
+
+// This value determines how long an email verification token is valid for
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: i64 = 86400; // 24 hours
+
+// This value determines how long an email change confirmation token is valid for
+pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS: i64 = 3600; // 1 hour
+
+// This value determines how long a magic login link is valid for. Used links are
+// remembered in the banned token store, so it can't outlive a banned token.
+pub const MAGIC_LINK_TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
+const _: () = assert!(MAGIC_LINK_TOKEN_TTL_SECONDS <= TOKEN_TTL_SECONDS);
+
+// This value determines how long a passkey has to answer a WebAuthn challenge. Used
+// challenges are remembered in the banned token store like magic links.
+pub const WEBAUTHN_CHALLENGE_TTL_SECONDS: i64 = 300; // 5 minutes
+const _: () = assert!(WEBAUTHN_CHALLENGE_TTL_SECONDS <= TOKEN_TTL_SECONDS);
+
+// This value determines how long an OAuth2 client has to redeem an authorization
+// code. Redeemed codes are remembered in the banned token store.
+pub const AUTHORIZATION_CODE_TTL_SECONDS: i64 = 60;
+const _: () = assert!(AUTHORIZATION_CODE_TTL_SECONDS <= TOKEN_TTL_SECONDS);
+
+// This value determines how long an OpenID Connect ID token is valid for. Clients
+// check it right after login, so it doesn't need to outlive the access token.
+pub const ID_TOKEN_TTL_SECONDS: i64 = TOKEN_TTL_SECONDS;
+
+// This value determines how long a user has to log in at an identity provider
+pub const FEDERATED_LOGIN_TTL_SECONDS: i64 = 600; // 10 minutes
+const _: () = assert!(FEDERATED_LOGIN_TTL_SECONDS <= TOKEN_TTL_SECONDS);
+
+// Keys rotated out of the key ring keep verifying tokens for the longest JWT lifetime
+const RETIRED_KEY_RETENTION_SECONDS: i64 = max(
+    TOKEN_TTL_SECONDS,
+    max(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, EMAIL_CHANGE_TOKEN_TTL_SECONDS),
+);
+
+const fn max(a: i64, b: i64) -> i64 {
+    if a > b { a } else { b }
+}
+
+// Verification tokens carry this audience. `validate_token` rejects any token with
+// an audience, so they can never be used as auth tokens.
+const EMAIL_VERIFICATION_AUDIENCE: &str = "email-verification";
+
+// Audience of email change confirmation tokens
+const EMAIL_CHANGE_AUDIENCE: &str = "email-change";
+
+// Audience of magic login link tokens
+const MAGIC_LINK_AUDIENCE: &str = "magic-link";
+
+// Audiences of WebAuthn challenges, one per ceremony so a registration challenge
+// can't be answered with an assertion
+const WEBAUTHN_REGISTRATION_AUDIENCE: &str = "webauthn-registration";
+const WEBAUTHN_AUTHENTICATION_AUDIENCE: &str = "webauthn-authentication";
+
+// Audience of OAuth2 authorization codes
+const AUTHORIZATION_CODE_AUDIENCE: &str = "oauth-authorization-code";
+
+// Audience of the cookie kept during a login through an identity provider
+const FEDERATED_LOGIN_AUDIENCE: &str = "federated-login";
+
+// Audience of access tokens issued to OAuth2 clients, so they can't stand in for
+// the user's own auth tokens
+const ACCESS_TOKEN_AUDIENCE: &str = "oauth-access-token";
+
 // Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
+pub(crate) fn generate_auth_token(
+    user_id: &UserId,
+    session_id: &SessionId,
+    token_version: i64,
+    roles: &UserRoles,
+) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(TOKEN_TTL_SECONDS)?;
+
+    let sub = user_id.to_string();
+
+    let jti = session_id.as_ref().to_owned();
+
+    let claims = Claims {
+        sub,
+        jti,
+        ver: token_version,
+        roles: roles.roles.clone(),
+        permissions: roles.permissions.clone(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Create a signed token proving ownership of an email address
+pub fn generate_email_verification_token(email: &Email) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(EMAIL_VERIFICATION_TOKEN_TTL_SECONDS)?;
+
+    let claims = EmailVerificationClaims {
+        sub: email.as_ref().to_owned(),
+        aud: EMAIL_VERIFICATION_AUDIENCE.to_owned(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Check if an email verification token is valid by decoding it using the key ring
+pub fn validate_email_verification_token(token: &str) -> Result<EmailVerificationClaims, jsonwebtoken::errors::Error> {
+    decode_token::<EmailVerificationClaims>(token, Some(EMAIL_VERIFICATION_AUDIENCE))
+}
+
+// Create a signed token confirming that the user `user_id`, currently known by
+// `current_email`, wants to change their email to `new_email`
+pub fn generate_email_change_token(
+    user_id: &UserId,
+    current_email: &Email,
+    new_email: &Email,
+) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(EMAIL_CHANGE_TOKEN_TTL_SECONDS)?;
+
+    let claims = EmailChangeClaims {
+        sub: user_id.to_string(),
+        from: current_email.as_ref().to_owned(),
+        email: new_email.as_ref().to_owned(),
+        aud: EMAIL_CHANGE_AUDIENCE.to_owned(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Check if an email change token is valid by decoding it using the key ring
+pub fn validate_email_change_token(token: &str) -> Result<EmailChangeClaims, jsonwebtoken::errors::Error> {
+    decode_token::<EmailChangeClaims>(token, Some(EMAIL_CHANGE_AUDIENCE))
+}
+
+// Create a signed token that logs the user `user_id` in without a password
+pub fn generate_magic_link_token(user_id: &UserId, token_version: i64) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(MAGIC_LINK_TOKEN_TTL_SECONDS)?;
+
+    let claims = MagicLinkClaims {
+        sub: user_id.to_string(),
+        ver: token_version,
+        aud: MAGIC_LINK_AUDIENCE.to_owned(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Check if a magic link token is valid by decoding it using the key ring
+pub fn validate_magic_link_token(token: &str) -> Result<MagicLinkClaims, jsonwebtoken::errors::Error> {
+    decode_token::<MagicLinkClaims>(token, Some(MAGIC_LINK_AUDIENCE))
+}
+
+// Create a signed WebAuthn challenge, so no state has to be kept between the start
+// and the end of a ceremony. Registration challenges are bound to the user adding
+// a passkey, and login challenges to the user logging in when known.
+pub fn generate_webauthn_challenge(ceremony: Ceremony, user_id: Option<&UserId>) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(WEBAUTHN_CHALLENGE_TTL_SECONDS)?;
+
+    let claims = WebauthnChallengeClaims {
+        sub: user_id.map(UserId::to_string),
+        nonce: uuid::Uuid::new_v4().to_string(),
+        aud: webauthn_audience(ceremony).to_owned(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Check if a WebAuthn challenge was issued by us for the given ceremony
+pub fn validate_webauthn_challenge(
+    challenge: &str,
+    ceremony: Ceremony,
+) -> Result<WebauthnChallengeClaims, jsonwebtoken::errors::Error> {
+    decode_token::<WebauthnChallengeClaims>(challenge, Some(webauthn_audience(ceremony)))
+}
+
+fn webauthn_audience(ceremony: Ceremony) -> &'static str {
+    match ceremony {
+        Ceremony::Registration => WEBAUTHN_REGISTRATION_AUDIENCE,
+        Ceremony::Authentication => WEBAUTHN_AUTHENTICATION_AUDIENCE,
+    }
+}
+
+// Create a signed OAuth2 authorization code for the user `user_id`, redeemable
+// only by `client` with the code verifier matching `code_challenge`
+pub fn generate_authorization_code(
+    user_id: &UserId,
+    token_version: i64,
+    client: &OAuthClient,
+    redirect_uri: &str,
+    code_challenge: &CodeChallenge,
+    scope: Option<String>,
+    nonce: Option<String>,
+) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(AUTHORIZATION_CODE_TTL_SECONDS)?;
+
+    let claims = AuthorizationCodeClaims {
+        sub: user_id.to_string(),
+        ver: token_version,
+        client_id: client.id.clone(),
+        redirect_uri: redirect_uri.to_owned(),
+        code_challenge: code_challenge.as_ref().to_owned(),
+        scope,
+        nonce,
+        jti: uuid::Uuid::new_v4().to_string(),
+        aud: AUTHORIZATION_CODE_AUDIENCE.to_owned(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Check if an authorization code is valid by decoding it using the key ring
+pub fn validate_authorization_code(code: &str) -> Result<AuthorizationCodeClaims, jsonwebtoken::errors::Error> {
+    decode_token::<AuthorizationCodeClaims>(code, Some(AUTHORIZATION_CODE_AUDIENCE))
+}
+
+// Create an OpenID Connect ID token telling the client `client_id` who logged in.
+// The client's ID is the audience, so ID tokens are never accepted as auth tokens.
+pub fn generate_id_token(user: &User, client_id: &str, nonce: Option<String>) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(ID_TOKEN_TTL_SECONDS)?;
+    let iat = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    let claims = IdTokenClaims {
+        iss: OIDC_ISSUER.clone(),
+        sub: user.id.to_string(),
+        aud: client_id.to_owned(),
+        email: user.email.as_ref().to_owned(),
+        email_verified: user.email_verified,
+        nonce,
+        iat,
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
+}
+
+// Compute a JWT expiration time `ttl_seconds` from now
+fn compute_expiry(ttl_seconds: i64) -> Result<usize, GenerateTokenError> {
+    let delta = chrono::Duration::try_seconds(ttl_seconds)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
     // Create JWT expiration time
@@ -53,55 +379,432 @@ fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
         .timestamp();
 
     // Cast exp to a usize, which is what Claims expects
-    let exp: usize = exp
-        .try_into()
-        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    exp.try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)
+}
 
-    let sub = email.as_ref().to_owned();
+// Check if JWT auth token is valid by decoding it using the key ring. Tokens with
+// an audience, like access tokens issued to OAuth2 clients, are rejected. The token
+// must not be banned, the user it was issued to must still be active, the session
+// it belongs to must not have been revoked, and its token version must still be
+// the user's current one.
+pub async fn validate_token(
+    token: &str,
+    banned_token_store: &BannedTokenStoreType,
+    session_store: &SessionStoreType,
+    token_version_store: &TokenVersionStoreType,
+    user_store: &UserStoreType,
+) -> Result<Claims, ValidateTokenError> {
+    let claims = decode_token::<Claims>(token, None)?;
+    check_token_holder(
+        token,
+        &claims.sub,
+        &claims.jti,
+        claims.ver,
+        banned_token_store,
+        session_store,
+        token_version_store,
+        user_store,
+    )
+    .await?;
+    Ok(claims)
+}
 
-    let claims = Claims { sub, exp };
+// Checks the stores for a decoded token issued to `sub` for the session `jti`
+#[allow(clippy::too_many_arguments)]
+async fn check_token_holder(
+    token: &str,
+    sub: &str,
+    jti: &str,
+    ver: i64,
+    banned_token_store: &BannedTokenStoreType,
+    session_store: &SessionStoreType,
+    token_version_store: &TokenVersionStoreType,
+    user_store: &UserStoreType,
+) -> Result<(), ValidateTokenError> {
+    let invalid_token = || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
-}
+    if banned_token_store.is_token_banned(token).await.map_err(|_| invalid_token())? {
+        return Err(invalid_token().into());
+    }
+
+    let user_id = UserId::parse(sub).map_err(|_| invalid_token())?;
+    let session_id = SessionId::parse(jti.to_owned()).map_err(|_| invalid_token())?;
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
-pub async fn validate_token(token: &str, banned_token_store: &BannedTokenStoreType) -> Result<Claims, jsonwebtoken::errors::Error> {
-    if banned_token_store.is_token_banned(token).await.map_err(|_| jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken))? {
-        return Err(jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken));
+    // Admins can block users at any time, so tokens issued before then stop working
+    let status = user_store
+        .read()
+        .await
+        .get_user_by_id(&user_id)
+        .await
+        .map_err(|_| invalid_token())?
+        .status;
+    if status != UserStatus::Active {
+        return Err(ValidateTokenError::AccountNotActive(status));
     }
 
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
+    let active = session_store
+        .read()
+        .await
+        .is_session_active(&user_id, &session_id)
+        .await
+        .map_err(|_| invalid_token())?;
+    if !active {
+        return Err(invalid_token().into());
+    }
+
+    let token_version = token_version_store
+        .read()
+        .await
+        .get_version(&user_id)
+        .await
+        .map_err(|_| invalid_token())?;
+    if ver != token_version {
+        return Err(invalid_token().into());
+    }
+
+    Ok(())
+}
+
+// Validate the JWT auth cookie and return the logged-in user
+pub async fn authenticate(jar: &CookieJar, state: &AppState) -> Result<User, AuthAPIError> {
+    authenticate_session(jar, state).await.map(|(user, _)| user)
+}
+
+// Validate the JWT auth cookie and return the logged-in user and their session
+pub async fn authenticate_session(jar: &CookieJar, state: &AppState) -> Result<(User, SessionId), AuthAPIError> {
+    let (user, claims) = authenticate_claims(jar, state).await?;
+    let session_id = SessionId::parse(claims.jti).map_err(|_| AuthAPIError::InvalidToken)?;
+    Ok((user, session_id))
+}
+
+// Validate the JWT auth cookie and return the logged-in user and the token's claims
+pub(crate) async fn authenticate_claims(jar: &CookieJar, state: &AppState) -> Result<(User, Claims), AuthAPIError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+
+    let claims = validate_token(
+        cookie.value(),
+        &*state.banned_token_store.read().await,
+        &state.session_store,
+        &state.token_version_store,
+        &state.user_store,
     )
-    .map(|data| data.claims)
+    .await
+    .map_err(|e| match e {
+        ValidateTokenError::AccountNotActive(status) => AuthAPIError::AccountNotActive(status),
+        ValidateTokenError::TokenError(_) => AuthAPIError::InvalidToken,
+    })?;
+
+    let user_id = UserId::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // The token was valid a moment ago, but the user may have been deleted since
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user_by_id(&user_id)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok((user, claims))
+}
+
+// Whether `token` is the configured admin API token
+pub fn is_admin_api_token(token: &str) -> bool {
+    matches_admin_api_token(token, ADMIN_API_TOKEN.as_deref())
+}
+
+// Digests are compared rather than the tokens, so how long the comparison takes
+// tells nothing about how much of the token was right
+fn matches_admin_api_token(token: &str, admin_api_token: Option<&str>) -> bool {
+    admin_api_token.is_some_and(|admin_api_token| Sha256::digest(token) == Sha256::digest(admin_api_token))
+}
+
+// Create an access token for the client `client_id`, acting for the user within
+// `scope`. It carries no roles, so clients never get the user's admin rights.
+pub fn generate_access_token(
+    user_id: &UserId,
+    session_id: &SessionId,
+    token_version: i64,
+    client_id: &str,
+    scope: Option<String>,
+) -> Result<String, GenerateTokenError> {
+    let exp = compute_expiry(TOKEN_TTL_SECONDS)?;
+
+    let claims = AccessTokenClaims {
+        sub: user_id.to_string(),
+        jti: session_id.as_ref().to_owned(),
+        ver: token_version,
+        client_id: client_id.to_owned(),
+        scope,
+        aud: ACCESS_TOKEN_AUDIENCE.to_owned(),
+        exp,
+    };
+
+    create_token(&claims).map_err(GenerateTokenError::TokenError)
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+// Check if an access token is valid, with the same checks as `validate_token`
+pub async fn validate_access_token(
+    token: &str,
+    banned_token_store: &BannedTokenStoreType,
+    session_store: &SessionStoreType,
+    token_version_store: &TokenVersionStoreType,
+    user_store: &UserStoreType,
+) -> Result<AccessTokenClaims, ValidateTokenError> {
+    let claims = decode_token::<AccessTokenClaims>(token, Some(ACCESS_TOKEN_AUDIENCE))?;
+    check_token_holder(
+        token,
+        &claims.sub,
+        &claims.jti,
+        claims.ver,
+        banned_token_store,
+        session_store,
+        token_version_store,
+        user_store,
     )
+    .await?;
+    Ok(claims)
+}
+
+// Create JWT by encoding claims using the active signing key
+fn create_token<T: Serialize>(claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+    let key_ring = key_ring();
+    let signing_key = key_ring.active();
+    encode(&signing_key.header(), &claims, signing_key.encoding_key())
+}
+
+// Decode a JWT with the key its `kid` header names. Tokens with any audience
+// other than `audience` are rejected.
+fn decode_token<T: DeserializeOwned>(token: &str, audience: Option<&str>) -> Result<T, jsonwebtoken::errors::Error> {
+    let kid = decode_header(token)?.kid;
+
+    let key_ring = key_ring();
+    let key = key_ring
+        .find(kid.as_deref())
+        .ok_or_else(|| jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken))?;
+
+    let mut validation = key.validation();
+    if let Some(audience) = audience {
+        validation.set_audience(&[audience]);
+    }
+
+    decode::<T>(token, key.decoding_key(), &validation).map(|data| data.claims)
+}
+
+fn key_ring() -> RwLockReadGuard<'static, KeyRing> {
+    JWT_KEY_RING.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Algorithm new tokens are signed with
+pub fn signing_algorithm() -> Algorithm {
+    key_ring().active().verification_key().algorithm()
+}
+
+// Whether OpenID Connect ID tokens are issued. Clients verify them through the JWKS,
+// which an HS256 key can't be published in, and sharing JWT_SECRET with them would
+// let them forge auth tokens.
+pub fn issues_id_tokens() -> bool {
+    signing_algorithm() != Algorithm::HS256
+}
+
+// Public keys of the key ring, for publishing as a JWKS
+pub fn jwks() -> JwkSet {
+    key_ring().jwks()
+}
+
+// Reload the key ring from the configuration. A new active key takes over
+// signing immediately, while tokens signed with the previous one stay valid.
+pub fn reload_key_ring() -> Result<(), String> {
+    let next = load_jwt_key_ring(true)?;
+    JWT_KEY_RING
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .rotate(next, chrono::Duration::seconds(RETIRED_KEY_RETENTION_SECONDS));
+    Ok(())
+}
+
+// Reload the key ring whenever the process receives SIGHUP
+#[cfg(unix)]
+pub async fn reload_key_ring_on_sighup() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            tracing::error!("Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+
+    while hangups.recv().await.is_some() {
+        match reload_key_ring() {
+            Ok(()) => tracing::info!(kid = key_ring().active().kid(), "Reloaded JWT signing keys"),
+            Err(e) => tracing::error!("Failed to reload JWT signing keys: {}", e),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    // ID of the user the token was issued to
     pub sub: String,
+    // Session the token was issued for
+    pub jti: String,
+    // User's token version when the token was issued
+    pub ver: i64,
+    // Roles the user had when the token was issued and the permissions they grant.
+    // Revoking a role bumps the token version, so tokens never outlive a revocation.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerificationClaims {
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailChangeClaims {
+    // ID of the user changing their email
+    pub sub: String,
+    // Email the user had when they asked for the change
+    pub from: String,
+    // Email the user is changing to
+    pub email: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkClaims {
+    // ID of the user the link logs in
+    pub sub: String,
+    // User's token version when the link was sent, so logging out everywhere cancels it
+    pub ver: i64,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebauthnChallengeClaims {
+    // ID of the user the ceremony is for, if known
+    pub sub: Option<String>,
+    // Makes every challenge unique, even when issued within the same second
+    pub nonce: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizationCodeClaims {
+    // ID of the user who approved the authorization
+    pub sub: String,
+    // User's token version at approval, so logging out everywhere cancels unredeemed codes
+    pub ver: i64,
+    pub client_id: String,
+    // The code can only be redeemed together with the redirect URI it was sent to
+    pub redirect_uri: String,
+    pub code_challenge: String,
+    pub scope: Option<String>,
+    // Value the client asked to have echoed in the ID token
+    pub nonce: Option<String>,
+    // Makes every code unique, even when issued within the same second
+    pub jti: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccessTokenClaims {
+    // ID of the user the client acts for
+    pub sub: String,
+    // Session created when the token was issued
+    pub jti: String,
+    pub ver: i64,
+    pub client_id: String,
+    // What the user allowed the client to do
+    pub scope: Option<String>,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FederatedLoginClaims {
+    // Name of the identity provider the user was sent to
+    pub provider: String,
+    // Sent to the provider and expected back at the callback, so a callback can
+    // only complete a login the same browser started
+    pub state: String,
+    // Sent to the provider and expected in its ID token
+    pub nonce: String,
+    // PKCE verifier for redeeming the provider's authorization code
+    pub code_verifier: String,
+    // Where to send the user once they are logged in
+    pub return_to: Option<String>,
+    pub aud: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    // ID of the user who logged in
+    pub sub: String,
+    // ID of the client the token was issued to
+    pub aud: String,
+    pub email: String,
+    pub email_verified: bool,
+    // Lets the client tie the token to the login it started, to detect replays
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub iat: usize,
     pub exp: usize,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::data_stores::data_store::{HashsetBannedTokenStore, BannedTokenStoreType};
+    use crate::data_stores::data_store::{
+        HashsetBannedTokenStore, BannedTokenStoreType, HashmapSessionStore, HashmapTokenVersionStore, HashmapUserStore,
+        SessionStore, UserStore,
+    };
+    use crate::domain::password::Password;
+    use crate::domain::session::Session;
+    use crate::domain::user::TwoFAMethod;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    // A session registry holding one active session for `user_id`
+    async fn session_store_with_session(user_id: &UserId) -> (SessionStoreType, SessionId) {
+        let session = Session::new(user_id.clone(), None, None);
+        let mut store = HashmapSessionStore::default();
+        store.add_session(&session, 60).await.unwrap();
+        (Arc::new(RwLock::new(Box::new(store))), session.id)
+    }
+
+    // A user store holding one active user
+    async fn user_store_with_user() -> (UserStoreType, UserId) {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let user = User::new(email, Password::parse("password123".to_owned()).unwrap(), TwoFAMethod::None);
+        let mut store = HashmapUserStore::default();
+        store.add_user(user.clone()).await.unwrap();
+        (Arc::new(RwLock::new(Box::new(store))), user.id)
+    }
+
+    fn empty_token_version_store() -> TokenVersionStoreType {
+        Arc::new(RwLock::new(Box::new(HashmapTokenVersionStore::default())))
+    }
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let user_id = UserId::default();
+        let cookie = generate_auth_cookie(&user_id, &SessionId::default(), 0, &UserRoles::default()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -120,20 +823,33 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_create_refresh_cookie() {
+        let token = RefreshToken::default();
+        let cookie = create_refresh_cookie(&token);
+        assert_eq!(cookie.name(), REFRESH_TOKEN_COOKIE_NAME);
+        assert_eq!(cookie.value(), token.as_ref());
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.max_age(), Some(time::Duration::seconds(REFRESH_TOKEN_TTL_SECONDS)));
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let user_id = UserId::default();
+        let result = generate_auth_token(&user_id, &SessionId::default(), 0, &UserRoles::default()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
-        let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let token = generate_auth_token(&user_id, &session_id, 0, &UserRoles::default()).unwrap();
         let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
-        let result = validate_token(&token, &banned_token_store).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        let token_version_store = empty_token_version_store();
+        let result = validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.unwrap();
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.jti, session_id.as_ref());
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -143,11 +859,244 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_auth_token_carries_roles() {
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let roles = UserRoles::new(vec!["admin".to_string()], vec!["roles:manage".to_string()]);
+        let token = generate_auth_token(&user_id, &session_id, 0, &roles).unwrap();
+        let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
+        let token_version_store = empty_token_version_store();
+        let result = validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.unwrap();
+        assert_eq!(result.roles, roles.roles);
+        assert_eq!(result.permissions, roles.permissions);
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, _) = session_store_with_session(&user_id).await;
         let token = "invalid_token".to_owned();
         let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
-        let result = validate_token(&token, &banned_token_store).await;
+        let token_version_store = empty_token_version_store();
+        let result = validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_with_stale_token_version() {
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let token = generate_auth_token(&user_id, &session_id, 0, &UserRoles::default()).unwrap();
+        let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
+        let token_version_store = empty_token_version_store();
+
+        let version = token_version_store.write().await.bump_version(&user_id).await.unwrap();
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_err());
+
+        let token = generate_auth_token(&user_id, &session_id, version, &UserRoles::default()).unwrap();
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_session() {
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let token = generate_auth_token(&user_id, &session_id, 0, &UserRoles::default()).unwrap();
+        let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
+        let token_version_store = empty_token_version_store();
+
+        session_store.write().await.revoke_session(&user_id, &session_id).await.unwrap();
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_err());
+
+        // Tokens for sessions that were never registered are rejected too
+        let token = generate_auth_token(&user_id, &SessionId::default(), 0, &UserRoles::default()).unwrap();
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_for_inactive_user() {
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let token = generate_auth_token(&user_id, &session_id, 0, &UserRoles::default()).unwrap();
+        let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
+        let token_version_store = empty_token_version_store();
+
+        user_store.write().await.set_status(&user_id, UserStatus::Suspended, None).await.unwrap();
+        let result = validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await;
+        assert!(matches!(result, Err(ValidateTokenError::AccountNotActive(UserStatus::Suspended))));
+
+        user_store.write().await.set_status(&user_id, UserStatus::Active, None).await.unwrap();
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_not_an_auth_token() {
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let token = generate_access_token(&user_id, &session_id, 0, "app", Some("profile".to_string())).unwrap();
+        let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
+        let token_version_store = empty_token_version_store();
+
+        let result =
+            validate_access_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.unwrap();
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.client_id, "app");
+        assert_eq!(result.scope.as_deref(), Some("profile"));
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_err());
+
+        let auth_token = generate_auth_token(&user_id, &session_id, 0, &UserRoles::default()).unwrap();
+        assert!(
+            validate_access_token(&auth_token, &banned_token_store, &session_store, &token_version_store, &user_store)
+                .await
+                .is_err()
+        );
+
+        session_store.write().await.revoke_session(&user_id, &session_id).await.unwrap();
+        assert!(
+            validate_access_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_email_verification_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_email_verification_token(&email).unwrap();
+        let result = validate_email_verification_token(&token).unwrap();
+        assert_eq!(result.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_email_verification_token_is_not_an_auth_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let token = generate_email_verification_token(&email).unwrap();
+        let (user_store, user_id) = user_store_with_user().await;
+        let (session_store, session_id) = session_store_with_session(&user_id).await;
+        let banned_token_store = BannedTokenStoreType::Hashset(HashsetBannedTokenStore::default());
+        let token_version_store = empty_token_version_store();
+        assert!(validate_token(&token, &banned_token_store, &session_store, &token_version_store, &user_store).await.is_err());
+
+        let auth_token = generate_auth_token(&user_id, &session_id, 0, &UserRoles::default()).unwrap();
+        assert!(validate_email_verification_token(&auth_token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_email_change_token() {
+        let user_id = UserId::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let new_email = Email::parse("new@example.com".to_owned()).unwrap();
+        let token = generate_email_change_token(&user_id, &email, &new_email).unwrap();
+
+        let result = validate_email_change_token(&token).unwrap();
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.from, "test@example.com");
+        assert_eq!(result.email, "new@example.com");
+
+        // Neither kind of email token can stand in for the other
+        assert!(validate_email_verification_token(&token).is_err());
+        let verification_token = generate_email_verification_token(&new_email).unwrap();
+        assert!(validate_email_change_token(&verification_token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_magic_link_token() {
+        let user_id = UserId::default();
+        let token = generate_magic_link_token(&user_id, 3).unwrap();
+
+        let result = validate_magic_link_token(&token).unwrap();
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.ver, 3);
+
+        // Magic links only log in through the magic link route
+        assert!(decode_token::<Claims>(&token, None).is_err());
+        assert!(validate_email_change_token(&token).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_webauthn_challenge() {
+        let user_id = UserId::default();
+        let challenge = generate_webauthn_challenge(Ceremony::Registration, Some(&user_id)).unwrap();
+
+        let result = validate_webauthn_challenge(&challenge, Ceremony::Registration).unwrap();
+        assert_eq!(result.sub, Some(user_id.to_string()));
+
+        // Challenges only answer the ceremony they were issued for
+        assert!(validate_webauthn_challenge(&challenge, Ceremony::Authentication).is_err());
+        assert!(decode_token::<Claims>(&challenge, None).is_err());
+
+        let other = generate_webauthn_challenge(Ceremony::Authentication, None).unwrap();
+        assert_ne!(other, challenge);
+        assert_eq!(validate_webauthn_challenge(&other, Ceremony::Authentication).unwrap().sub, None);
+    }
+
+    #[tokio::test]
+    async fn test_validate_authorization_code() {
+        let user_id = UserId::default();
+        let client = OAuthClient::new("app".to_string(), "App".to_string(), vec![]);
+        let challenge = CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_string()).unwrap();
+        let code = generate_authorization_code(
+            &user_id,
+            2,
+            &client,
+            "https://app.example/callback",
+            &challenge,
+            Some("openid".to_string()),
+            Some("n-0S6_WzA2Mj".to_string()),
+        )
+        .unwrap();
+
+        let result = validate_authorization_code(&code).unwrap();
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.client_id, "app");
+        assert_eq!(result.code_challenge, challenge.as_ref());
+        assert_eq!(result.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+
+        // Codes aren't access tokens
+        assert!(decode_token::<Claims>(&code, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_id_token() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password123".to_owned()).unwrap();
+        let user = User::new(email, password, TwoFAMethod::None);
+        let token = generate_id_token(&user, "app", Some("n-0S6_WzA2Mj".to_string())).unwrap();
+
+        let result = decode_token::<IdTokenClaims>(&token, Some("app")).unwrap();
+        assert_eq!(result.iss, *OIDC_ISSUER);
+        assert_eq!(result.sub, user.id.to_string());
+        assert_eq!(result.email, "test@example.com");
+        assert!(!result.email_verified);
+        assert_eq!(result.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+
+        // ID tokens only identify the user to the client, they don't authenticate them here
+        assert!(decode_token::<Claims>(&token, None).is_err());
+        assert!(decode_token::<IdTokenClaims>(&token, Some("other-app")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_federated_login_token() {
+        let code_verifier = CodeVerifier::default();
+        let cookie =
+            generate_federated_login_cookie("acme", "state", "nonce", &code_verifier, Some("/authorize?a=b".to_string()))
+                .unwrap();
+        assert_eq!(cookie.name(), FEDERATED_LOGIN_COOKIE_NAME);
+        assert!(cookie.http_only().unwrap());
+
+        let result = validate_federated_login_token(cookie.value()).unwrap();
+        assert_eq!(result.provider, "acme");
+        assert_eq!(result.state, "state");
+        assert_eq!(result.code_verifier, code_verifier.as_ref());
+        assert!(decode_token::<Claims>(cookie.value(), None).is_err());
+    }
+
+    #[test]
+    fn test_matches_admin_api_token() {
+        assert!(matches_admin_api_token("s3cret-admin-token", Some("s3cret-admin-token")));
+        assert!(!matches_admin_api_token("s3cret-admin", Some("s3cret-admin-token")));
+        // Without a configured token no token is accepted, not even an empty one
+        assert!(!matches_admin_api_token("", None));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+// Just enough CBOR (RFC 8949) to read WebAuthn attestation objects and COSE keys,
+// and to write them for tests. Indefinite lengths, tags and floats aren't needed
+// there, so they are rejected.
+
+// Nesting deeper than this is never valid WebAuthn data
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Integer(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Bool(bool),
+    Null,
+}
+
+impl Value {
+    // Look up an entry of a map with an integer key, as COSE keys use
+    pub fn get_int(&self, key: i128) -> Option<&Value> {
+        self.get(&Value::Integer(key))
+    }
+
+    // Look up an entry of a map with a text key, as attestation objects use
+    pub fn get_text(&self, key: &str) -> Option<&Value> {
+        self.get(&Value::Text(key.to_owned()))
+    }
+
+    fn get(&self, key: &Value) -> Option<&Value> {
+        match self {
+            Value::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i128> {
+        match self {
+            Value::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Value::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_into(&mut out);
+        out
+    }
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Integer(value) if *value >= 0 => write_head(out, 0, *value as u64),
+            Value::Integer(value) => write_head(out, 1, (-1 - *value) as u64),
+            Value::Bytes(bytes) => {
+                write_head(out, 2, bytes.len() as u64);
+                out.extend_from_slice(bytes);
+            }
+            Value::Text(text) => {
+                write_head(out, 3, text.len() as u64);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Value::Array(items) => {
+                write_head(out, 4, items.len() as u64);
+                items.iter().for_each(|item| item.encode_into(out));
+            }
+            Value::Map(entries) => {
+                write_head(out, 5, entries.len() as u64);
+                for (key, value) in entries {
+                    key.encode_into(out);
+                    value.encode_into(out);
+                }
+            }
+            Value::Bool(false) => out.push(0xf4),
+            Value::Bool(true) => out.push(0xf5),
+            Value::Null => out.push(0xf6),
+        }
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(argument as u16).to_be_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&argument.to_be_bytes());
+        }
+    }
+}
+
+// Decode the first item in `bytes`, returning it with the number of bytes it took up.
+// Whatever follows it is left alone, since authenticator data can carry extensions
+// after the credential public key.
+pub fn decode(bytes: &[u8]) -> Result<(Value, usize), String> {
+    let mut decoder = Decoder { bytes, position: 0 };
+    let value = decoder.value(0)?;
+    Ok((value, decoder.position))
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Decoder<'_> {
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err("CBOR nested too deeply".to_string());
+        }
+
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let info = initial & 0x1f;
+
+        if major == 7 {
+            return match info {
+                20 => Ok(Value::Bool(false)),
+                21 => Ok(Value::Bool(true)),
+                22 | 23 => Ok(Value::Null),
+                _ => Err("Unsupported CBOR simple value".to_string()),
+            };
+        }
+
+        let argument = self.argument(info)?;
+        match major {
+            0 => Ok(Value::Integer(argument as i128)),
+            1 => Ok(Value::Integer(-1 - argument as i128)),
+            2 => Ok(Value::Bytes(self.take(argument)?.to_vec())),
+            3 => String::from_utf8(self.take(argument)?.to_vec())
+                .map(Value::Text)
+                .map_err(|_| "Invalid CBOR text".to_string()),
+            4 => {
+                // Every item takes at least a byte, so a longer length can't be valid
+                let mut items = Vec::with_capacity(self.capacity(argument)?);
+                for _ in 0..argument {
+                    items.push(self.value(depth + 1)?);
+                }
+                Ok(Value::Array(items))
+            }
+            5 => {
+                let mut entries = Vec::with_capacity(self.capacity(argument)?);
+                for _ in 0..argument {
+                    let key = self.value(depth + 1)?;
+                    let value = self.value(depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Value::Map(entries))
+            }
+            _ => Err("Unsupported CBOR tag".to_string()),
+        }
+    }
+
+    fn argument(&mut self, info: u8) -> Result<u64, String> {
+        let width = match info {
+            0..=23 => return Ok(info as u64),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            _ => return Err("Unsupported CBOR length".to_string()),
+        };
+        Ok(self
+            .take(width)?
+            .iter()
+            .fold(0, |argument, byte| (argument << 8) | *byte as u64))
+    }
+
+    fn capacity(&self, length: u64) -> Result<usize, String> {
+        let remaining = self.bytes.len() - self.position;
+        match usize::try_from(length) {
+            Ok(length) if length <= remaining => Ok(length),
+            _ => Err("CBOR length exceeds input".to_string()),
+        }
+    }
+
+    fn take(&mut self, length: u64) -> Result<&[u8], String> {
+        let length = self.capacity(length)?;
+        let slice = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let value = Value::Map(vec![
+            (Value::Integer(1), Value::Integer(2)),
+            (Value::Integer(-7), Value::Integer(-300)),
+            (Value::Text("fmt".to_string()), Value::Text("none".to_string())),
+            (Value::Text("data".to_string()), Value::Bytes(vec![0xab; 300])),
+            (Value::Text("list".to_string()), Value::Array(vec![Value::Bool(true), Value::Null])),
+            (Value::Integer(70_000), Value::Integer(5_000_000_000)),
+        ]);
+
+        let encoded = value.encode();
+        assert_eq!(decode(&encoded).unwrap(), (value, encoded.len()));
+    }
+
+    #[test]
+    fn test_decode_known_bytes() {
+        // {1: 2, 3: -7}, the start of a COSE key
+        let (value, length) = decode(&[0xa2, 0x01, 0x02, 0x03, 0x26]).unwrap();
+        assert_eq!(length, 5);
+        assert_eq!(value.get_int(1).and_then(Value::as_integer), Some(2));
+        assert_eq!(value.get_int(3).and_then(Value::as_integer), Some(-7));
+    }
+
+    #[test]
+    fn test_decode_leaves_trailing_bytes() {
+        let (value, length) = decode(&[0x43, 1, 2, 3, 0xff, 0xff]).unwrap();
+        assert_eq!(value, Value::Bytes(vec![1, 2, 3]));
+        assert_eq!(length, 4);
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_input() {
+        // Truncated byte string
+        assert!(decode(&[0x45, 1, 2]).is_err());
+        // Array claiming more items than there are bytes
+        assert!(decode(&[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err());
+        // Indefinite length map
+        assert!(decode(&[0xbf, 0xff]).is_err());
+        // Tagged item
+        assert!(decode(&[0xc0, 0x00]).is_err());
+        // Deep nesting
+        assert!(decode(&[0x81; 64]).is_err());
+        assert!(decode(&[]).is_err());
+    }
+}
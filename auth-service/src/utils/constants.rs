@@ -1,6 +1,12 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
 use dotenvy::dotenv;
+use jsonwebtoken::Algorithm;
 use lazy_static::lazy_static;
 use std::env as std_env;
+use std::str::FromStr;
+
+use super::signing_key::{KeyRing, SigningKey, VerificationKey};
+use crate::domain::identity_provider::IdentityProvider;
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
@@ -15,6 +21,38 @@ lazy_static! {
     pub static ref REDIS_HOST_NAME: String = set_redis_host(); // New!
 }
 
+lazy_static! {
+    pub static ref REQUIRE_EMAIL_VERIFICATION: bool = set_require_email_verification();
+}
+
+lazy_static! {
+    pub static ref TOTP_ENCRYPTION_KEY: Vec<u8> = set_totp_encryption_key();
+}
+
+lazy_static! {
+    pub static ref ACCOUNT_DELETION_GRACE_PERIOD_SECONDS: i64 = set_account_deletion_grace_period();
+}
+
+lazy_static! {
+    pub static ref MAGIC_LINK_LOGIN_ENABLED: bool = set_magic_link_login_enabled();
+}
+
+lazy_static! {
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+}
+
+lazy_static! {
+    pub static ref WEBAUTHN_ORIGIN: String = set_webauthn_origin();
+}
+
+lazy_static! {
+    pub static ref OIDC_ISSUER: String = set_oidc_issuer();
+}
+
+lazy_static! {
+    pub static ref ADMIN_API_TOKEN: Option<String> = set_admin_api_token();
+}
+
 
 fn set_token() -> String {
     dotenv().ok(); // Load environment variables
@@ -35,14 +73,217 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_require_email_verification() -> bool {
+    dotenv().ok();
+    std_env::var(env::REQUIRE_EMAIL_VERIFICATION_ENV_VAR)
+        .map(|value| value == "true")
+        .unwrap_or(false)
+}
+
+// Magic link login is on unless turned off, since the links go to the same inbox
+// as password reset tokens and don't give access to anything those don't
+fn set_magic_link_login_enabled() -> bool {
+    dotenv().ok();
+    std_env::var(env::MAGIC_LINK_LOGIN_ENABLED_ENV_VAR)
+        .map(|value| value != "false")
+        .unwrap_or(true)
+}
+
+// Passkeys are bound to this domain, so it must be the domain of the site users log in on
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_RP_ID_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+fn set_webauthn_origin() -> String {
+    dotenv().ok();
+    std_env::var(env::WEBAUTHN_ORIGIN_ENV_VAR).unwrap_or(DEFAULT_WEBAUTHN_ORIGIN.to_owned())
+}
+
+// The public URL of this service. ID tokens name it as their issuer, and clients
+// compare it with the issuer of the discovery document.
+fn set_oidc_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::OIDC_ISSUER_ENV_VAR)
+        .unwrap_or(DEFAULT_OIDC_ISSUER.to_owned())
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+// Lets scripts use the admin API without logging in as an admin. Access with a
+// token is off unless one is configured.
+fn set_admin_api_token() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::ADMIN_API_TOKEN_ENV_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+}
+
+fn set_totp_encryption_key() -> Vec<u8> {
+    dotenv().ok();
+    let key = std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set.");
+    let key = STANDARD
+        .decode(key)
+        .expect("TOTP_ENCRYPTION_KEY must be base64 encoded.");
+    if key.len() != 32 {
+        panic!("TOTP_ENCRYPTION_KEY must be 32 bytes.");
+    }
+    key
+}
+
+fn set_account_deletion_grace_period() -> i64 {
+    dotenv().ok();
+    match std_env::var(env::ACCOUNT_DELETION_GRACE_PERIOD_SECONDS_ENV_VAR) {
+        Ok(value) => value
+            .parse()
+            .ok()
+            .filter(|seconds: &i64| *seconds >= 0)
+            .expect("ACCOUNT_DELETION_GRACE_PERIOD_SECONDS must be a non-negative number of seconds."),
+        Err(_) => DEFAULT_ACCOUNT_DELETION_GRACE_PERIOD_SECONDS,
+    }
+}
+
+// Load the JWT key ring. The active key is HS256 with JWT_SECRET, or RS256/EdDSA
+// with the PEM key pair at JWT_PRIVATE_KEY_PATH and JWT_PUBLIC_KEY_PATH.
+// JWT_PREVIOUS_SECRETS and JWT_PREVIOUS_PUBLIC_KEY_PATHS (comma separated) list
+// older keys that are still accepted for verification.
+//
+// This runs again whenever the key ring is reloaded. Key files are re-read, and
+// values in `.env` override the process environment so secrets can be rotated too.
+pub(crate) fn load_jwt_key_ring(reload: bool) -> Result<KeyRing, String> {
+    if reload {
+        dotenvy::dotenv_override().ok();
+    } else {
+        dotenv().ok();
+    }
+
+    let algorithm = std_env::var(env::JWT_ALGORITHM_ENV_VAR).unwrap_or(DEFAULT_JWT_ALGORITHM.to_owned());
+    let algorithm = Algorithm::from_str(&algorithm)
+        .map_err(|_| "JWT_ALGORITHM must be HS256, RS256 or EdDSA.".to_string())?;
+
+    let read_key = |path: &str| std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e));
+    let key_path = |var: &str| std_env::var(var).map_err(|_| format!("{} must be set.", var));
+
+    let active = match algorithm {
+        Algorithm::HS256 => {
+            let secret = std_env::var(env::JWT_SECRET_ENV_VAR).map_err(|_| "JWT_SECRET must be set.".to_string())?;
+            if secret.is_empty() {
+                return Err("JWT_SECRET must not be empty.".to_string());
+            }
+            SigningKey::from_secret(secret.as_bytes())
+        }
+        _ => {
+            let private_key = read_key(&key_path(env::JWT_PRIVATE_KEY_PATH_ENV_VAR)?)?;
+            let public_key = read_key(&key_path(env::JWT_PUBLIC_KEY_PATH_ENV_VAR)?)?;
+            SigningKey::from_pem(algorithm, &private_key, &public_key)
+                .map_err(|e| format!("Invalid JWT signing key: {:?}", e))?
+        }
+    };
+
+    let list = |var: &str| -> Vec<String> {
+        std_env::var(var)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+            .collect()
+    };
+
+    let mut previous: Vec<VerificationKey> = list(env::JWT_PREVIOUS_SECRETS_ENV_VAR)
+        .iter()
+        .map(|secret| VerificationKey::from_secret(secret.as_bytes()))
+        .collect();
+    for path in list(env::JWT_PREVIOUS_PUBLIC_KEY_PATHS_ENV_VAR) {
+        let key = VerificationKey::from_public_pem(&read_key(&path)?)
+            .map_err(|e| format!("Invalid JWT public key {}: {:?}", path, e))?;
+        previous.push(key);
+    }
+
+    Ok(KeyRing::new(active, previous))
+}
+
+// Load the upstream OpenID Connect providers users can log in with. IDENTITY_PROVIDERS
+// lists their names (comma separated), and each one is configured with
+// IDENTITY_PROVIDER_<NAME>_ISSUER, _CLIENT_ID and _CLIENT_SECRET, where <NAME> is
+// the upper-cased name with dashes replaced by underscores.
+pub fn load_identity_providers() -> Vec<IdentityProvider> {
+    dotenv().ok();
+    std_env::var(env::IDENTITY_PROVIDERS_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let prefix = format!("IDENTITY_PROVIDER_{}", name.to_uppercase().replace('-', "_"));
+            let setting = |suffix: &str| {
+                let var = format!("{}_{}", prefix, suffix);
+                std_env::var(&var).unwrap_or_else(|_| panic!("{} must be set.", var))
+            };
+            IdentityProvider::new(name.to_owned(), setting("ISSUER"), setting("CLIENT_ID"), setting("CLIENT_SECRET"))
+                .unwrap_or_else(|e| panic!("{}", e))
+        })
+        .collect()
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const JWT_ALGORITHM_ENV_VAR: &str = "JWT_ALGORITHM";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_PUBLIC_KEY_PATH_ENV_VAR: &str = "JWT_PUBLIC_KEY_PATH";
+    pub const JWT_PREVIOUS_SECRETS_ENV_VAR: &str = "JWT_PREVIOUS_SECRETS";
+    pub const JWT_PREVIOUS_PUBLIC_KEY_PATHS_ENV_VAR: &str = "JWT_PREVIOUS_PUBLIC_KEY_PATHS";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const REQUIRE_EMAIL_VERIFICATION_ENV_VAR: &str = "REQUIRE_EMAIL_VERIFICATION";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const ACCOUNT_DELETION_GRACE_PERIOD_SECONDS_ENV_VAR: &str = "ACCOUNT_DELETION_GRACE_PERIOD_SECONDS";
+    pub const MAGIC_LINK_LOGIN_ENABLED_ENV_VAR: &str = "MAGIC_LINK_LOGIN_ENABLED";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const OIDC_ISSUER_ENV_VAR: &str = "OIDC_ISSUER";
+    pub const IDENTITY_PROVIDERS_ENV_VAR: &str = "IDENTITY_PROVIDERS";
+    pub const ADMIN_API_TOKEN_ENV_VAR: &str = "ADMIN_API_TOKEN";
 }
 
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
+// Ties a login through an identity provider to the browser that started it
+pub const FEDERATED_LOGIN_COOKIE_NAME: &str = "federated_login";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_JWT_ALGORITHM: &str = "HS256";
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_ORIGIN: &str = "http://localhost:8000";
+pub const DEFAULT_OIDC_ISSUER: &str = "http://localhost:3000";
+// Page size of the admin user list, unless the request asks for another
+pub const DEFAULT_ADMIN_PAGE_SIZE: i64 = 50;
+pub const MAX_ADMIN_PAGE_SIZE: i64 = 200;
+// Minimum time between two verification emails for the same address
+pub const VERIFICATION_EMAIL_COOLDOWN_SECONDS: i64 = 60;
+// Wrong codes that can be submitted for a login attempt before the user has to log in again
+pub const MAX_TWO_FA_CODE_ATTEMPTS: u32 = 3;
+// Minimum time between two 2FA code emails for the same login attempt
+pub const TWO_FA_CODE_RESEND_COOLDOWN_SECONDS: i64 = 30;
+// How many times the 2FA code of a login attempt can be resent
+pub const MAX_TWO_FA_CODE_RESENDS: u32 = 3;
+// How long a deleted account can still be restored before its data is purged
+pub const DEFAULT_ACCOUNT_DELETION_GRACE_PERIOD_SECONDS: i64 = 2_592_000; // 30 days
+// How often the purge task looks for accounts whose grace period has ended
+pub const ACCOUNT_PURGE_INTERVAL_SECONDS: u64 = 3600; // 1 hour
+// How often the delivery worker looks for webhook deliveries that are due
+pub const WEBHOOK_DELIVERY_INTERVAL_SECONDS: u64 = 5;
+// Deliveries the worker sends per run
+pub const WEBHOOK_DELIVERY_BATCH_SIZE: i64 = 20;
+// How long a delivery claimed by a worker is kept from other workers. Longer than
+// a batch can take, so a delivery is only retried by another worker if this one died.
+pub const WEBHOOK_DELIVERY_LEASE_SECONDS: i64 = 300;
+// How long receivers have to respond
+pub const WEBHOOK_TIMEOUT_SECONDS: u64 = 10;
+// Attempts at a delivery before it is dead-lettered
+pub const WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+// Delay before the first retry. It doubles with each failed attempt, up to the maximum.
+pub const WEBHOOK_RETRY_BASE_DELAY_SECONDS: i64 = 30;
+pub const WEBHOOK_RETRY_MAX_DELAY_SECONDS: i64 = 3600; // 1 hour
 
 //...
 pub mod prod {
@@ -0,0 +1,71 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::constants::TOTP_ENCRYPTION_KEY;
+
+// AES-GCM nonces are 96 bits
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, PartialEq)]
+pub enum CryptoError {
+    EncryptionFailed,
+    DecryptionFailed,
+}
+
+// Encrypt a secret for storage. The random nonce is prepended to the ciphertext
+// and the result is base64 encoded.
+pub fn encrypt_secret(plaintext: &str) -> Result<String, CryptoError> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(TOTP_ENCRYPTION_KEY.as_slice()));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(STANDARD.encode(payload))
+}
+
+// Decrypt a secret produced by `encrypt_secret`
+pub fn decrypt_secret(encoded: &str) -> Result<String, CryptoError> {
+    let payload = STANDARD.decode(encoded).map_err(|_| CryptoError::DecryptionFailed)?;
+    if payload.len() < NONCE_LENGTH {
+        return Err(CryptoError::DecryptionFailed);
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(TOTP_ENCRYPTION_KEY.as_slice()));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+    String::from_utf8(plaintext).map_err(|_| CryptoError::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let encrypted = encrypt_secret("JBSWY3DPEHPK3PXP").unwrap();
+        assert_ne!(encrypted, "JBSWY3DPEHPK3PXP");
+        assert_eq!(decrypt_secret(&encrypted).unwrap(), "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn test_encryption_is_randomised() {
+        assert_ne!(encrypt_secret("secret").unwrap(), encrypt_secret("secret").unwrap());
+    }
+
+    #[test]
+    fn test_decrypt_rejects_tampered_ciphertext() {
+        let mut payload = STANDARD.decode(encrypt_secret("secret").unwrap()).unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        assert_eq!(decrypt_secret(&STANDARD.encode(payload)), Err(CryptoError::DecryptionFailed));
+        assert_eq!(decrypt_secret("not base64!"), Err(CryptoError::DecryptionFailed));
+    }
+}
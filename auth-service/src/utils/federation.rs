@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use lazy_static::lazy_static;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::domain::identity_provider::IdentityProvider;
+use crate::domain::oauth::{CodeVerifier, OPENID_SCOPE};
+
+// How long to wait for an identity provider before giving up on a login
+const UPSTREAM_TIMEOUT_SECONDS: u64 = 10;
+
+// Providers sign ID tokens with a private key and publish the public key in their
+// JWKS. HMAC signed tokens would need the client secret as key and aren't accepted.
+const ID_TOKEN_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+lazy_static! {
+    // Shared by all logins so connections to providers are reused
+    static ref HTTP_CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(UPSTREAM_TIMEOUT_SECONDS))
+        .build()
+        .expect("Failed to build HTTP client");
+}
+
+#[derive(Debug)]
+pub enum FederationError {
+    RequestFailed(reqwest::Error),
+    IssuerMismatch,
+    MissingIdToken,
+    KeyNotFound,
+    InvalidIdToken(jsonwebtoken::errors::Error),
+    NonceMismatch,
+}
+
+impl From<reqwest::Error> for FederationError {
+    fn from(e: reqwest::Error) -> Self {
+        FederationError::RequestFailed(e)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for FederationError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        FederationError::InvalidIdToken(e)
+    }
+}
+
+// The parts of a provider's discovery document a login needs
+#[derive(Debug, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+// Claims of a provider's ID token that accounts are matched on
+#[derive(Debug, Deserialize)]
+pub struct UpstreamClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub nonce: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpstreamTokenResponse {
+    id_token: Option<String>,
+}
+
+// Fetch the provider's discovery document
+pub async fn discover(provider: &IdentityProvider) -> Result<ProviderMetadata, FederationError> {
+    let metadata: ProviderMetadata = get_json(&format!("{}/.well-known/openid-configuration", provider.issuer)).await?;
+
+    // The document must be about the issuer it was fetched from (OpenID Connect Discovery section 4.3)
+    if metadata.issuer.trim_end_matches('/') != provider.issuer {
+        return Err(FederationError::IssuerMismatch);
+    }
+    Ok(metadata)
+}
+
+// Where to send the user to log in at the provider
+pub fn authorization_url(
+    provider: &IdentityProvider,
+    metadata: &ProviderMetadata,
+    redirect_uri: &str,
+    state: &str,
+    nonce: &str,
+    code_verifier: &CodeVerifier,
+) -> String {
+    let scope = format!("{} email", OPENID_SCOPE);
+    let query = serde_urlencoded::to_string([
+        ("response_type", "code"),
+        ("client_id", provider.client_id.as_str()),
+        ("redirect_uri", redirect_uri),
+        ("scope", scope.as_str()),
+        ("state", state),
+        ("nonce", nonce),
+        ("code_challenge", code_verifier.challenge().as_ref()),
+        ("code_challenge_method", "S256"),
+    ])
+    .unwrap_or_default();
+
+    let separator = if metadata.authorization_endpoint.contains('?') { '&' } else { '?' };
+    format!("{}{}{}", metadata.authorization_endpoint, separator, query)
+}
+
+// Redeem the authorization code the provider sent the user back with, and return its ID token
+pub async fn exchange_code(
+    provider: &IdentityProvider,
+    metadata: &ProviderMetadata,
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: &CodeVerifier,
+) -> Result<String, FederationError> {
+    let response: UpstreamTokenResponse = HTTP_CLIENT
+        .post(&metadata.token_endpoint)
+        // client_secret_basic is the one client authentication method every provider supports
+        .basic_auth(&provider.client_id, Some(&provider.client_secret))
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", code_verifier.as_ref()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    response.id_token.ok_or(FederationError::MissingIdToken)
+}
+
+// Check the ID token was signed by the provider for us, for the login that sent
+// `nonce`, and return its claims
+pub async fn validate_id_token(
+    provider: &IdentityProvider,
+    metadata: &ProviderMetadata,
+    id_token: &str,
+    nonce: &str,
+) -> Result<UpstreamClaims, FederationError> {
+    let header = decode_header(id_token)?;
+    if !ID_TOKEN_ALGORITHMS.contains(&header.alg) {
+        return Err(FederationError::InvalidIdToken(jsonwebtoken::errors::ErrorKind::InvalidAlgorithm.into()));
+    }
+
+    // Providers that only publish one key may leave out the kid
+    let jwks: JwkSet = get_json(&metadata.jwks_uri).await?;
+    let jwk = match header.kid.as_deref() {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or(FederationError::KeyNotFound)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&metadata.issuer]);
+    validation.set_audience(&[&provider.client_id]);
+    let claims = decode::<UpstreamClaims>(id_token, &DecodingKey::from_jwk(jwk)?, &validation)?.claims;
+
+    // An ID token from another login can't be replayed into this one
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(FederationError::NonceMismatch);
+    }
+    Ok(claims)
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, FederationError> {
+    Ok(HTTP_CLIENT.get(url).send().await?.error_for_status()?.json().await?)
+}
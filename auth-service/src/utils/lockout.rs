@@ -0,0 +1,117 @@
+use crate::app_state::AppState;
+use crate::data_stores::data_store::LockoutPolicy;
+use crate::domain::email::Email;
+use crate::domain::error::AuthAPIError;
+use crate::domain::password::Password;
+use crate::utils::client_info::ClientInfo;
+
+// Guesses at a single account's password or 2FA code
+pub const ACCOUNT_LOCKOUT_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 5,
+    window_seconds: 900,          // 15 minutes
+    base_lockout_seconds: 60,     // 1 minute
+    max_lockout_seconds: 3600,    // 1 hour
+    backoff_reset_seconds: 86400, // 24 hours
+};
+
+// Guesses from a single client, possibly spread over many accounts. Everyone behind
+// the same NAT shares an IP, so more failures are allowed.
+pub const CLIENT_LOCKOUT_POLICY: LockoutPolicy = LockoutPolicy {
+    max_failures: 20,
+    ..ACCOUNT_LOCKOUT_POLICY
+};
+
+// The failure counters an attempt counts against: one for the account and one
+// for the client the attempt comes from
+pub(crate) struct AttemptCounters {
+    account: String,
+    client: Option<String>,
+}
+
+impl AttemptCounters {
+    // `action` keeps counters of different kinds of attempts apart, e.g. "login"
+    pub fn new(action: &str, email: &Email, client: &ClientInfo) -> Self {
+        Self {
+            account: format!("{}:email:{}", action, email.as_ref()),
+            client: client.ip_address.as_ref().map(|ip| format!("{}:ip:{}", action, ip)),
+        }
+    }
+
+    // Fails with `TooManyAttempts` while the account or the client is locked out.
+    // Check this before doing any expensive verification.
+    pub async fn check(&self, state: &AppState) -> Result<(), AuthAPIError> {
+        let store = state.failed_attempt_store.read().await;
+
+        let mut remaining = store
+            .lockout_remaining(&self.account)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        if let Some(client) = &self.client {
+            let client_remaining = store
+                .lockout_remaining(client)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            remaining = remaining.max(client_remaining);
+        }
+
+        match remaining {
+            Some(seconds) => Err(AuthAPIError::TooManyAttempts(seconds.unsigned_abs())),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn record_failure(&self, state: &AppState) -> Result<(), AuthAPIError> {
+        let mut store = state.failed_attempt_store.write().await;
+
+        let lockout = store
+            .record_failure(&self.account, &ACCOUNT_LOCKOUT_POLICY)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        if let Some(seconds) = lockout {
+            tracing::warn!(seconds, "Account locked out after too many failed attempts");
+        }
+
+        if let Some(client) = &self.client {
+            let lockout = store
+                .record_failure(client, &CLIENT_LOCKOUT_POLICY)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            if let Some(seconds) = lockout {
+                tracing::warn!(seconds, "Client locked out after too many failed attempts");
+            }
+        }
+
+        Ok(())
+    }
+
+    // Checks the password of a user who is already logged in, for actions that ask
+    // for it again. A wrong password counts as a failure, the right one clears them.
+    pub async fn validate_password(
+        &self,
+        state: &AppState,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), AuthAPIError> {
+        self.check(state).await?;
+
+        let valid = state.user_store.read().await.validate_user(email, password).await.is_ok();
+        if !valid {
+            self.record_failure(state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+
+        self.clear(state).await
+    }
+
+    // A successful attempt clears the account's failures. The client's are kept,
+    // otherwise logging into an account of your own would reset them.
+    pub async fn clear(&self, state: &AppState) -> Result<(), AuthAPIError> {
+        state
+            .failed_attempt_store
+            .write()
+            .await
+            .clear_failures(&self.account)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)
+    }
+}
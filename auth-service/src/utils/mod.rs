@@ -1,3 +1,11 @@
 pub mod constants;
+pub mod audit;
 pub mod auth;
-pub mod tracing;
\ No newline at end of file
+pub mod cbor;
+pub mod client_info;
+pub mod crypto;
+pub mod federation;
+pub mod lockout;
+pub mod signing_key;
+pub mod tracing;
+pub mod webhooks;
\ No newline at end of file
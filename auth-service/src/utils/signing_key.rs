@@ -0,0 +1,460 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters,
+    OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use simple_asn1::{from_der, oid, ASN1Block};
+
+// A key JWTs can be verified with. Asymmetric keys are published as a JWK so
+// other services can verify tokens without the private key.
+pub struct VerificationKey {
+    algorithm: Algorithm,
+    kid: String,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+// The key new JWTs are signed with, along with the matching verification key
+pub struct SigningKey {
+    encoding_key: EncodingKey,
+    verification_key: VerificationKey,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SigningKeyError {
+    UnsupportedAlgorithm,
+    InvalidKey(String),
+}
+
+impl VerificationKey {
+    // HS256 with a shared secret. Nothing is published, as the secret is also the signing key.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        let k = URL_SAFE_NO_PAD.encode(secret);
+        Self {
+            algorithm: Algorithm::HS256,
+            kid: thumbprint(&format!(r#"{{"k":"{}","kty":"oct"}}"#, k)),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    // RS256 or EdDSA (Ed25519) from a PEM encoded SPKI public key. The algorithm follows from the key type.
+    pub fn from_public_pem(public_pem: &[u8]) -> Result<Self, SigningKeyError> {
+        let invalid_key = |e: jsonwebtoken::errors::Error| SigningKeyError::InvalidKey(e.to_string());
+
+        let (algorithm, decoding_key, params) = match parse_public_key(public_pem)? {
+            PublicKey::Rsa(params) => (
+                Algorithm::RS256,
+                DecodingKey::from_rsa_pem(public_pem).map_err(invalid_key)?,
+                params,
+            ),
+            PublicKey::Ed25519(params) => (
+                Algorithm::EdDSA,
+                DecodingKey::from_ed_pem(public_pem).map_err(invalid_key)?,
+                params,
+            ),
+        };
+
+        let kid = jwk_thumbprint(&params);
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::RS256 => KeyAlgorithm::RS256,
+                    _ => KeyAlgorithm::EdDSA,
+                }),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+
+        Ok(Self {
+            algorithm,
+            kid,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    // Public key to publish in the JWKS, if any
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+
+    // Only accept tokens signed with this key's algorithm, so a public key can't be used as an HMAC secret
+    pub fn validation(&self) -> Validation {
+        Validation::new(self.algorithm)
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+impl SigningKey {
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            encoding_key: EncodingKey::from_secret(secret),
+            verification_key: VerificationKey::from_secret(secret),
+        }
+    }
+
+    // RS256 or EdDSA (Ed25519) from a PKCS#8 private key and an SPKI public key, both PEM encoded
+    pub fn from_pem(algorithm: Algorithm, private_pem: &[u8], public_pem: &[u8]) -> Result<Self, SigningKeyError> {
+        let invalid_key = |e: jsonwebtoken::errors::Error| SigningKeyError::InvalidKey(e.to_string());
+
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_pem).map_err(invalid_key)?,
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(private_pem).map_err(invalid_key)?,
+            _ => return Err(SigningKeyError::UnsupportedAlgorithm),
+        };
+
+        let verification_key = VerificationKey::from_public_pem(public_pem)?;
+        if verification_key.algorithm() != algorithm {
+            return Err(SigningKeyError::InvalidKey(format!("Public key is not a {:?} key", algorithm)));
+        }
+
+        let key = Self { encoding_key, verification_key };
+        key.check_key_pair()?;
+
+        Ok(key)
+    }
+
+    pub fn kid(&self) -> &str {
+        self.verification_key.kid()
+    }
+
+    pub fn header(&self) -> Header {
+        let mut header = Header::new(self.verification_key.algorithm());
+        header.kid = Some(self.kid().to_owned());
+        header
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn verification_key(&self) -> &VerificationKey {
+        &self.verification_key
+    }
+
+    // Sign and verify a throwaway token so mismatched key files fail at startup
+    fn check_key_pair(&self) -> Result<(), SigningKeyError> {
+        #[derive(Serialize, Deserialize)]
+        struct ProbeClaims {
+            exp: usize,
+        }
+
+        let token = encode(&self.header(), &ProbeClaims { exp: usize::MAX }, &self.encoding_key)
+            .map_err(|e| SigningKeyError::InvalidKey(e.to_string()))?;
+        decode::<ProbeClaims>(
+            &token,
+            self.verification_key.decoding_key(),
+            &self.verification_key.validation(),
+        )
+        .map_err(|_| SigningKeyError::InvalidKey("Public key does not match private key".to_string()))?;
+
+        Ok(())
+    }
+}
+
+struct RetiredKey {
+    key: VerificationKey,
+    // Keys dropped from the configuration stay valid until tokens signed with them have expired
+    expires_at: Option<DateTime<Utc>>,
+}
+
+// One key is active for signing. Older keys remain valid for verification so
+// rotating the signing key doesn't invalidate tokens that were already issued.
+pub struct KeyRing {
+    active: SigningKey,
+    previous: Vec<RetiredKey>,
+}
+
+impl KeyRing {
+    pub fn new(active: SigningKey, previous: Vec<VerificationKey>) -> Self {
+        let previous = previous
+            .into_iter()
+            .filter(|key| key.kid() != active.kid())
+            .map(|key| RetiredKey { key, expires_at: None })
+            .collect();
+        Self { active, previous }
+    }
+
+    pub fn active(&self) -> &SigningKey {
+        &self.active
+    }
+
+    // Find the key a token was signed with. Tokens issued before keys had a kid
+    // can only have been signed with the active key.
+    pub fn find(&self, kid: Option<&str>) -> Option<&VerificationKey> {
+        let kid = match kid {
+            Some(kid) => kid,
+            None => return Some(self.active.verification_key()),
+        };
+
+        if kid == self.active.kid() {
+            return Some(self.active.verification_key());
+        }
+
+        let now = Utc::now();
+        self.previous
+            .iter()
+            .filter(|retired| retired.expires_at.is_none_or(|expires_at| expires_at > now))
+            .map(|retired| &retired.key)
+            .find(|key| key.kid() == kid)
+    }
+
+    // Public keys of every key tokens may currently be verified with
+    pub fn jwks(&self) -> JwkSet {
+        let now = Utc::now();
+        let keys = std::iter::once(self.active.verification_key())
+            .chain(
+                self.previous
+                    .iter()
+                    .filter(|retired| retired.expires_at.is_none_or(|expires_at| expires_at > now))
+                    .map(|retired| &retired.key),
+            )
+            .filter_map(|key| key.jwk().cloned())
+            .collect();
+        JwkSet { keys }
+    }
+
+    // Replace this ring with a freshly loaded one. Keys that disappear from the
+    // configuration, including the active key when it changes, are kept for
+    // `retain_for` so tokens they signed can still be verified.
+    pub fn rotate(&mut self, next: KeyRing, retain_for: Duration) {
+        let old = std::mem::replace(self, next);
+        let expires_at = Utc::now() + retain_for;
+
+        let old_keys = std::iter::once(RetiredKey {
+            key: old.active.verification_key,
+            expires_at: Some(expires_at),
+        })
+        .chain(old.previous.into_iter().map(|retired| RetiredKey {
+            expires_at: Some(retired.expires_at.map_or(expires_at, |at| at.min(expires_at))),
+            key: retired.key,
+        }));
+
+        for retired in old_keys {
+            let known = retired.key.kid() == self.active.kid()
+                || self.previous.iter().any(|key| key.key.kid() == retired.key.kid());
+            let expired = retired.expires_at.is_some_and(|at| at <= Utc::now());
+            if !known && !expired {
+                self.previous.push(retired);
+            }
+        }
+    }
+}
+
+enum PublicKey {
+    Rsa(AlgorithmParameters),
+    Ed25519(AlgorithmParameters),
+}
+
+// Read the key type and key material from a SubjectPublicKeyInfo structure
+fn parse_public_key(public_pem: &[u8]) -> Result<PublicKey, SigningKeyError> {
+    let malformed = || SigningKeyError::InvalidKey("Malformed public key".to_string());
+
+    let pem = pem::parse(public_pem).map_err(|e| SigningKeyError::InvalidKey(e.to_string()))?;
+    if pem.tag() != "PUBLIC KEY" {
+        return Err(SigningKeyError::InvalidKey("Expected a PUBLIC KEY PEM block".to_string()));
+    }
+
+    let blocks = from_der(pem.contents()).map_err(|_| malformed())?;
+    let (algorithm, key) = match blocks.as_slice() {
+        [ASN1Block::Sequence(_, fields)] => match fields.as_slice() {
+            [ASN1Block::Sequence(_, algorithm), ASN1Block::BitString(_, _, key)] => match algorithm.first() {
+                Some(ASN1Block::ObjectIdentifier(_, algorithm)) => (algorithm.clone(), key.clone()),
+                _ => return Err(malformed()),
+            },
+            _ => return Err(malformed()),
+        },
+        _ => return Err(malformed()),
+    };
+
+    if algorithm == oid!(1, 2, 840, 113549, 1, 1, 1) {
+        rsa_jwk_parameters(&key).map(PublicKey::Rsa)
+    } else if algorithm == oid!(1, 3, 101, 112) {
+        ed25519_jwk_parameters(key).map(PublicKey::Ed25519)
+    } else {
+        Err(SigningKeyError::UnsupportedAlgorithm)
+    }
+}
+
+fn rsa_jwk_parameters(key: &[u8]) -> Result<AlgorithmParameters, SigningKeyError> {
+    // RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+    match from_der(key).as_deref() {
+        Ok([ASN1Block::Sequence(_, fields)]) => match fields.as_slice() {
+            [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] => Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(n.to_bytes_be().1),
+                e: URL_SAFE_NO_PAD.encode(e.to_bytes_be().1),
+            })),
+            _ => Err(SigningKeyError::InvalidKey("Malformed RSA public key".to_string())),
+        },
+        _ => Err(SigningKeyError::InvalidKey("Malformed RSA public key".to_string())),
+    }
+}
+
+fn ed25519_jwk_parameters(key: Vec<u8>) -> Result<AlgorithmParameters, SigningKeyError> {
+    if key.len() != 32 {
+        return Err(SigningKeyError::InvalidKey("Malformed Ed25519 public key".to_string()));
+    }
+
+    Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+        key_type: OctetKeyPairType::OctetKeyPair,
+        curve: EllipticCurve::Ed25519,
+        x: URL_SAFE_NO_PAD.encode(key),
+    }))
+}
+
+// RFC 7638 thumbprint: a hash of the required members in lexicographic order,
+// so the kid stays the same for as long as the key does
+fn jwk_thumbprint(params: &AlgorithmParameters) -> String {
+    let canonical = match params {
+        AlgorithmParameters::RSA(rsa) => format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n),
+        AlgorithmParameters::OctetKeyPair(okp) => format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x),
+        _ => unreachable!("only RSA and Ed25519 keys are supported"),
+    };
+    thumbprint(&canonical)
+}
+
+fn thumbprint(canonical_jwk: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical_jwk.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RSA_PRIVATE_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt/rsa_private.pem");
+    const RSA_PUBLIC_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt/rsa_public.pem");
+    const ED25519_PRIVATE_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt/ed25519_private.pem");
+    const ED25519_PUBLIC_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt/ed25519_public.pem");
+
+    fn rsa_key() -> SigningKey {
+        SigningKey::from_pem(Algorithm::RS256, RSA_PRIVATE_KEY, RSA_PUBLIC_KEY).unwrap()
+    }
+
+    fn ed25519_key() -> SigningKey {
+        SigningKey::from_pem(Algorithm::EdDSA, ED25519_PRIVATE_KEY, ED25519_PUBLIC_KEY).unwrap()
+    }
+
+    fn sign(key: &SigningKey) -> String {
+        encode(&key.header(), &serde_json::json!({ "exp": usize::MAX }), key.encoding_key()).unwrap()
+    }
+
+    #[test]
+    fn test_rsa_key_publishes_verifiable_jwk() {
+        let key = rsa_key();
+        let jwk = key.verification_key().jwk().unwrap();
+        assert_eq!(jwk.common.key_id.as_deref(), Some(key.kid()));
+
+        // A verifier holding only the JWK can check tokens signed with the key
+        let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+        let validation = key.verification_key().validation();
+        assert!(decode::<serde_json::Value>(&sign(&key), &decoding_key, &validation).is_ok());
+    }
+
+    #[test]
+    fn test_ed25519_key_publishes_verifiable_jwk() {
+        let key = ed25519_key();
+        let jwk = key.verification_key().jwk().unwrap();
+
+        let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+        let validation = key.verification_key().validation();
+        assert!(decode::<serde_json::Value>(&sign(&key), &decoding_key, &validation).is_ok());
+    }
+
+    #[test]
+    fn test_algorithm_is_inferred_from_public_key() {
+        assert_eq!(VerificationKey::from_public_pem(RSA_PUBLIC_KEY).unwrap().algorithm(), Algorithm::RS256);
+        assert_eq!(VerificationKey::from_public_pem(ED25519_PUBLIC_KEY).unwrap().algorithm(), Algorithm::EdDSA);
+    }
+
+    #[test]
+    fn test_kid_is_stable_per_key() {
+        assert_eq!(rsa_key().kid(), rsa_key().kid());
+        assert_ne!(rsa_key().kid(), ed25519_key().kid());
+        assert_eq!(SigningKey::from_secret(b"secret").kid(), SigningKey::from_secret(b"secret").kid());
+        assert_ne!(SigningKey::from_secret(b"secret").kid(), SigningKey::from_secret(b"other").kid());
+    }
+
+    #[test]
+    fn test_mismatched_key_pair_is_rejected() {
+        let result = SigningKey::from_pem(Algorithm::RS256, RSA_PRIVATE_KEY, ED25519_PUBLIC_KEY);
+        assert!(matches!(result, Err(SigningKeyError::InvalidKey(_))));
+    }
+
+    #[test]
+    fn test_unsupported_algorithm() {
+        let result = SigningKey::from_pem(Algorithm::ES256, RSA_PRIVATE_KEY, RSA_PUBLIC_KEY);
+        assert!(matches!(result, Err(SigningKeyError::UnsupportedAlgorithm)));
+    }
+
+    #[test]
+    fn test_secret_key_has_no_jwk() {
+        let key = SigningKey::from_secret(b"secret");
+        assert_eq!(key.verification_key().algorithm(), Algorithm::HS256);
+        assert!(key.verification_key().jwk().is_none());
+    }
+
+    #[test]
+    fn test_key_ring_finds_keys_by_kid() {
+        let active = rsa_key();
+        let active_kid = active.kid().to_owned();
+        let previous = VerificationKey::from_public_pem(ED25519_PUBLIC_KEY).unwrap();
+        let previous_kid = previous.kid().to_owned();
+        let ring = KeyRing::new(active, vec![previous]);
+
+        assert_eq!(ring.find(Some(&active_kid)).unwrap().kid(), active_kid);
+        assert_eq!(ring.find(Some(&previous_kid)).unwrap().kid(), previous_kid);
+        assert_eq!(ring.find(None).unwrap().kid(), active_kid);
+        assert!(ring.find(Some("unknown")).is_none());
+        assert_eq!(ring.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_active_key_for_verification() {
+        let old = SigningKey::from_secret(b"old secret");
+        let old_kid = old.kid().to_owned();
+        let mut ring = KeyRing::new(old, vec![]);
+
+        ring.rotate(KeyRing::new(rsa_key(), vec![]), Duration::hours(1));
+
+        assert_eq!(ring.active().kid(), rsa_key().kid());
+        assert_eq!(ring.find(Some(&old_kid)).unwrap().kid(), old_kid);
+    }
+
+    #[test]
+    fn test_rotation_drops_old_keys_once_retention_has_passed() {
+        let old = SigningKey::from_secret(b"old secret");
+        let old_kid = old.kid().to_owned();
+        let mut ring = KeyRing::new(old, vec![]);
+
+        ring.rotate(KeyRing::new(rsa_key(), vec![]), Duration::zero());
+
+        assert!(ring.find(Some(&old_kid)).is_none());
+    }
+
+    #[test]
+    fn test_rotation_with_unchanged_keys_is_a_no_op() {
+        let mut ring = KeyRing::new(rsa_key(), vec![]);
+        ring.rotate(KeyRing::new(rsa_key(), vec![]), Duration::hours(1));
+        assert_eq!(ring.jwks().keys.len(), 1);
+    }
+}
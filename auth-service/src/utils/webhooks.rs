@@ -0,0 +1,45 @@
+use chrono::{SecondsFormat, Utc};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::data_stores::data_store::WebhookStoreError;
+use crate::domain::user::User;
+use crate::domain::webhook::{WebhookDelivery, WebhookEventType};
+
+// Queues the event for every subscription to it. The delivery worker sends them
+// in the background, so a slow or failing receiver never holds up the request,
+// and requests still succeed when the event can't be queued.
+pub async fn publish_event(state: &AppState, event_type: WebhookEventType, user: &User) {
+    if let Err(e) = queue_deliveries(state, event_type, user).await {
+        tracing::error!(event_type = event_type.as_str(), "Failed to queue webhook deliveries: {:?}", e);
+    }
+}
+
+async fn queue_deliveries(state: &AppState, event_type: WebhookEventType, user: &User) -> Result<(), WebhookStoreError> {
+    let mut webhook_store = state.webhook_store.write().await;
+    let subscriptions = webhook_store.list_subscriptions().await?;
+    if !subscriptions.iter().any(|subscription| subscription.subscribes_to(event_type)) {
+        return Ok(());
+    }
+
+    // Every subscription gets the same body, so receivers can dedupe on its id
+    let event_id = Uuid::new_v4();
+    let payload = serde_json::json!({
+        "id": event_id,
+        "type": event_type.as_str(),
+        "occurredAt": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        "data": {
+            "userId": user.id.to_string(),
+            "email": user.email.as_ref(),
+            "status": user.status.as_str(),
+        },
+    })
+    .to_string();
+
+    let deliveries: Vec<WebhookDelivery> = subscriptions
+        .iter()
+        .filter(|subscription| subscription.subscribes_to(event_type))
+        .map(|subscription| WebhookDelivery::new(subscription.id, event_id, event_type, payload.clone()))
+        .collect();
+    webhook_store.add_deliveries(&deliveries).await
+}
@@ -0,0 +1,153 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::services::account_purge::purge_deleted_accounts;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .expect("No auth cookie found");
+    token
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_delete_account(&serde_json::json!({ "password": "password123" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_incorrect() {
+    let mut app = TestApp::new().await;
+
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app.post_delete_account(&serde_json::json!({ "password": "wrongpassword" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_block_login_until_account_restored() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let jwt_token = signup_and_login(&app, &random_email).await;
+
+    let response = app.post_delete_account(&serde_json::json!({ "password": "password123" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Every session ends with the deletion
+    let response = app.post_verify_token(&serde_json::json!({ "token": jwt_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let credentials = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+    let response = app.login(&credentials).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.post_restore_account(&serde_json::json!({
+        "email": random_email,
+        "password": "wrongpassword"
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_restore_account(&credentials).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.login(&credentials).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Only deleted accounts can be restored, and others look like a wrong password
+    let response = app.post_restore_account(&credentials).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_failed_restore_attempts() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let response = app.post_delete_account(&serde_json::json!({ "password": "password123" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for _ in 0..5 {
+        let response = app.post_restore_account(&serde_json::json!({
+            "email": random_email,
+            "password": "wrongpassword"
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Locked out, even with the right password
+    let response = app.post_restore_account(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_purge_account_once_grace_period_ends() {
+    let mut app = TestApp::new().await;
+
+    let deleted_email = get_random_email();
+    let active_email = get_random_email();
+    signup_and_login(&app, &active_email).await;
+    signup_and_login(&app, &deleted_email).await;
+
+    let response = app.post_delete_account(&serde_json::json!({ "password": "password123" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Still within the grace period
+    assert_eq!(purge_deleted_accounts(&app.app_state, 3600).await.unwrap(), 0);
+
+    assert_eq!(purge_deleted_accounts(&app.app_state, 0).await.unwrap(), 1);
+
+    let user_store = app.app_state.user_store.read().await;
+    assert!(user_store.get_user(&Email::parse(deleted_email.clone()).unwrap()).await.is_err());
+    assert!(user_store.get_user(&Email::parse(active_email).unwrap()).await.is_ok());
+    drop(user_store);
+
+    // Purged accounts can't be restored, and their email is free again
+    let response = app.post_restore_account(&serde_json::json!({
+        "email": deleted_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.signup(&serde_json::json!({
+        "email": deleted_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,267 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::role::{Role, ADMIN_ROLE};
+use auth_service::domain::user::UserId;
+use auth_service::routes::{AdminUserResponse, UsersResponse};
+use auth_service::ErrorResponse;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+use reqwest::Url;
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> Option<String> {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .map(|cookie| cookie.value().to_owned())
+}
+
+async fn signup(app: &TestApp, email: &str) -> String {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = Email::parse(email.to_owned()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    user.id.to_string()
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.login(&serde_json::json!({
+        "email": email,
+        "password": password
+    })).await
+}
+
+// Signs up an admin and logs them in, so the cookie jar holds their token
+async fn login_as_admin(app: &TestApp) {
+    let email = get_random_email();
+    let user_id = UserId::parse(&signup(app, &email).await).unwrap();
+    let admin = Role::parse(ADMIN_ROLE.to_owned()).unwrap();
+    app.app_state.role_store.write().await.grant_role(&user_id, &admin).await.unwrap();
+    assert_eq!(login(app, &email, "password123").await.status().as_u16(), 200);
+}
+
+async fn get_admin_user(app: &TestApp, user_id: &str) -> AdminUserResponse {
+    let response = app.get_admin_user(user_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<AdminUserResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserResponse")
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_reject_non_admins() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    assert_eq!(app.get_admin_user(&user_id).await.status().as_u16(), 400);
+
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 200);
+    assert_eq!(app.get_admin_user(&user_id).await.status().as_u16(), 403);
+    let suspend = serde_json::json!({ "status": "suspended" });
+    assert_eq!(app.post_admin_user_status(&user_id, &suspend).await.status().as_u16(), 403);
+    assert_eq!(app.delete_admin_user_sessions(&user_id).await.status().as_u16(), 403);
+
+    // No admin API token is configured, so none is accepted
+    let response = app
+        .http_client
+        .get(format!("{}/admin/users", &app.address))
+        .bearer_auth("not-the-admin-token")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_and_search_users() {
+    let mut app = TestApp::new().await;
+
+    // Each test has a database of its own, where the admin is the only other user
+    let prefix = uuid::Uuid::new_v4().simple().to_string();
+    for name in ["carol", "alice", "bob"] {
+        signup(&app, &format!("{}-{}@example.com", prefix, name)).await;
+    }
+    login_as_admin(&app).await;
+
+    let response = app.get_admin_users(&[("search", prefix.as_str()), ("limit", "2")]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.json::<UsersResponse>().await.unwrap();
+    assert_eq!(page.total, 3);
+    let emails: Vec<_> = page.users.iter().map(|user| user.email.clone()).collect();
+    assert_eq!(emails, vec![format!("{}-alice@example.com", prefix), format!("{}-bob@example.com", prefix)]);
+
+    let response = app.get_admin_users(&[("search", prefix.as_str()), ("limit", "2"), ("offset", "2")]).await;
+    let page = response.json::<UsersResponse>().await.unwrap();
+    assert_eq!(page.users.len(), 1);
+    assert_eq!(page.users[0].email, format!("{}-carol@example.com", prefix));
+
+    // Without a search every user is listed, the admin included
+    let page = app.get_admin_users(&()).await.json::<UsersResponse>().await.unwrap();
+    assert_eq!(page.total, 4);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_users() {
+    let mut app = TestApp::new().await;
+    login_as_admin(&app).await;
+
+    let unknown_user = uuid::Uuid::new_v4().to_string();
+    assert_eq!(app.get_admin_user(&unknown_user).await.status().as_u16(), 404);
+    assert_eq!(app.get_admin_user("not-a-user-id").await.status().as_u16(), 404);
+    let suspend = serde_json::json!({ "status": "suspended" });
+    assert_eq!(app.post_admin_user_status(&unknown_user, &suspend).await.status().as_u16(), 404);
+    assert_eq!(app.post_admin_password_reset(&unknown_user).await.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn suspended_users_are_logged_out_and_cannot_log_in() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    let token = get_cookie(&login(&app, &email, "password123").await, JWT_COOKIE_NAME).unwrap();
+
+    login_as_admin(&app).await;
+    let response = app.post_admin_user_status(&user_id, &serde_json::json!({
+        "status": "suspended",
+        "reason": "Chargeback on invoice 1042"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = response.json::<AdminUserResponse>().await.unwrap();
+    assert_eq!(user.status, "suspended");
+    assert_eq!(user.status_reason.as_deref(), Some("Chargeback on invoice 1042"));
+    assert!(user.status_changed_at.is_some());
+
+    // The user's tokens and logins are refused with an error saying why, but not
+    // with the reason, which is only for admins
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Account is suspended");
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(response.json::<ErrorResponse>().await.unwrap().error, "Account is suspended");
+
+    login_as_admin(&app).await;
+    let response = app.post_admin_user_status(&user_id, &serde_json::json!({ "status": "active" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = get_admin_user(&app, &user_id).await;
+    assert_eq!(user.status, "active");
+    assert_eq!(user.status_reason, None);
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 200);
+
+    // Tokens issued before the suspension don't come back to life
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_unknown_statuses() {
+    let mut app = TestApp::new().await;
+
+    let user_id = signup(&app, &get_random_email()).await;
+    login_as_admin(&app).await;
+
+    let response = app.post_admin_user_status(&user_id, &serde_json::json!({ "status": "banned" })).await;
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(get_admin_user(&app, &user_id).await.status, "active");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_toggle_2fa() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    login_as_admin(&app).await;
+
+    let response = app.post_admin_user_2fa(&user_id, &serde_json::json!({ "requires2FA": true })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = response.json::<AdminUserResponse>().await.unwrap();
+    assert!(user.requires_2fa);
+    assert_eq!(user.two_fa_method, "email");
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 206);
+
+    login_as_admin(&app).await;
+    let response = app.post_admin_user_2fa(&user_id, &serde_json::json!({ "requires2FA": false })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!get_admin_user(&app, &user_id).await.requires_2fa);
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn forced_password_reset_invalidates_the_password() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    let token = get_cookie(&login(&app, &email, "password123").await, JWT_COOKIE_NAME).unwrap();
+
+    login_as_admin(&app).await;
+    assert_eq!(app.post_admin_password_reset(&user_id).await.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 401);
+
+    // The user was sent a token to choose a new password with
+    let reset_token = app.replace_password_reset_token(&email).await;
+    let response = app.post_password_reset_confirm(&serde_json::json!({
+        "email": email,
+        "token": reset_token,
+        "newPassword": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(login(&app, &email, "newpassword123").await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_all_sessions_of_a_user() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    let response = login(&app, &email, "password123").await;
+    let token = get_cookie(&response, JWT_COOKIE_NAME).unwrap();
+    let refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).unwrap();
+
+    login_as_admin(&app).await;
+    assert_eq!(app.delete_admin_user_sessions(&user_id).await.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+
+    // The user's refresh token can't start a new session either
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_TOKEN_COOKIE_NAME, refresh_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    assert_eq!(app.post_token_refresh().await.status().as_u16(), 401);
+
+    // The account itself still works
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,219 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::role::{Role, ADMIN_ROLE};
+use auth_service::domain::user::UserId;
+use auth_service::get_postgres_pool;
+use auth_service::routes::{AuditLogResponse, AuditLogVerificationResponse};
+use auth_service::utils::constants::DATABASE_URL;
+
+async fn signup(app: &TestApp, email: &str) -> String {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = Email::parse(email.to_owned()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    user.id.to_string()
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.login(&serde_json::json!({
+        "email": email,
+        "password": password
+    })).await
+}
+
+// Signs up an admin and logs them in, so the cookie jar holds their token.
+// Returns the admin's user ID.
+async fn login_as_admin(app: &TestApp) -> String {
+    let email = get_random_email();
+    let user_id = signup(app, &email).await;
+    let admin = Role::parse(ADMIN_ROLE.to_owned()).unwrap();
+    app.app_state
+        .role_store
+        .write()
+        .await
+        .grant_role(&UserId::parse(&user_id).unwrap(), &admin)
+        .await
+        .unwrap();
+    assert_eq!(login(app, &email, "password123").await.status().as_u16(), 200);
+    user_id
+}
+
+async fn get_audit_log(app: &TestApp, query: &serde_json::Value) -> AuditLogResponse {
+    let response = app.get_audit_log(query).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse")
+}
+
+async fn verify_audit_log(app: &TestApp) -> AuditLogVerificationResponse {
+    let response = app.get_audit_log_verification().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<AuditLogVerificationResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogVerificationResponse")
+}
+
+#[tokio::test]
+async fn should_record_logins_and_failed_logins() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    assert_eq!(login(&app, &email, "wrongpassword123").await.status().as_u16(), 401);
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 200);
+    let unknown_email = get_random_email();
+    assert_eq!(login(&app, &unknown_email, "password123").await.status().as_u16(), 400);
+
+    login_as_admin(&app).await;
+
+    // Newest first
+    let log = get_audit_log(&app, &serde_json::json!({ "userId": user_id })).await;
+    let events: Vec<(&str, Option<&str>)> = log
+        .events
+        .iter()
+        .map(|event| (event.event_type.as_str(), event.details.as_deref()))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            ("login_succeeded", Some("password")),
+            ("login_failed", Some("incorrect_password")),
+            ("signup", None),
+        ]
+    );
+    assert_eq!(log.total, 3);
+    assert!(log.events.iter().all(|event| event.email.as_deref() == Some(email.as_str())));
+
+    // Guesses at accounts that don't exist are traced by email
+    let log = get_audit_log(&app, &serde_json::json!({ "email": unknown_email })).await;
+    assert_eq!(log.total, 1);
+    assert_eq!(log.events[0].event_type, "login_failed");
+    assert_eq!(log.events[0].details.as_deref(), Some("unknown_account"));
+    assert_eq!(log.events[0].user_id, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_who_took_admin_actions() {
+    let mut app = TestApp::new().await;
+
+    let user_id = signup(&app, &get_random_email()).await;
+    let admin_id = login_as_admin(&app).await;
+
+    let suspend = serde_json::json!({ "status": "suspended", "reason": "Chargeback" });
+    assert_eq!(app.post_admin_user_status(&user_id, &suspend).await.status().as_u16(), 200);
+
+    let log = get_audit_log(&app, &serde_json::json!({ "type": "user_status_changed" })).await;
+    assert_eq!(log.total, 1);
+    let event = &log.events[0];
+    assert_eq!(event.user_id.as_deref(), Some(user_id.as_str()));
+    assert_eq!(event.actor.as_deref(), Some(admin_id.as_str()));
+    assert_eq!(event.details.as_deref(), Some("suspended: Chargeback"));
+
+    let log = get_audit_log(&app, &serde_json::json!({ "actor": admin_id })).await;
+    assert_eq!(log.total, 1);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_paginate_and_filter_by_time() {
+    let mut app = TestApp::new().await;
+
+    for _ in 0..3 {
+        signup(&app, &get_random_email()).await;
+    }
+    login_as_admin(&app).await;
+
+    let query = serde_json::json!({ "type": "signup", "limit": 2, "offset": 1 });
+    let log = get_audit_log(&app, &query).await;
+    assert_eq!(log.total, 4);
+    assert_eq!((log.limit, log.offset), (2, 1));
+    assert_eq!(log.events.len(), 2);
+    assert!(log.events[0].id > log.events[1].id);
+
+    let log = get_audit_log(&app, &serde_json::json!({ "since": "2100-01-01T00:00:00Z" })).await;
+    assert_eq!(log.total, 0);
+    let log = get_audit_log(&app, &serde_json::json!({ "until": "2100-01-01T00:00:00Z" })).await;
+    assert_eq!(log.total, 5);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_for_malformed_filters() {
+    let mut app = TestApp::new().await;
+
+    login_as_admin(&app).await;
+
+    let queries = [
+        serde_json::json!({ "type": "login" }),
+        serde_json::json!({ "userId": "not-a-uuid" }),
+        serde_json::json!({ "since": "yesterday" }),
+    ];
+    for query in queries {
+        assert_eq!(app.get_audit_log(&query).await.status().as_u16(), 422, "Failed for query: {:?}", query);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_non_admins() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+    assert_eq!(login(&app, &email, "password123").await.status().as_u16(), 200);
+
+    assert_eq!(app.get_audit_log(&serde_json::json!({})).await.status().as_u16(), 403);
+    assert_eq!(app.get_audit_log_verification().await.status().as_u16(), 403);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_detect_tampering() {
+    let mut app = TestApp::new().await;
+
+    signup(&app, &get_random_email()).await;
+    login_as_admin(&app).await;
+
+    let verification = verify_audit_log(&app).await;
+    assert!(verification.valid);
+    assert_eq!(verification.checked, 3);
+
+    // The table refuses changes...
+    let pg_pool = get_postgres_pool(&format!("{}/{}", DATABASE_URL.as_str(), app.db_name))
+        .await
+        .unwrap();
+    let update = "UPDATE audit_log SET details = 'nothing to see here' WHERE id = 2";
+    assert!(sqlx::query(update).execute(&pg_pool).await.is_err());
+    assert!(sqlx::query("DELETE FROM audit_log WHERE id = 2").execute(&pg_pool).await.is_err());
+
+    // ...and changes made around the trigger break the chain
+    sqlx::query("ALTER TABLE audit_log DISABLE TRIGGER audit_log_no_update_or_delete")
+        .execute(&pg_pool)
+        .await
+        .unwrap();
+    sqlx::query(update).execute(&pg_pool).await.unwrap();
+    pg_pool.close().await;
+
+    let verification = verify_audit_log(&app).await;
+    assert!(!verification.valid);
+    assert_eq!(verification.checked, 1);
+    assert_eq!(verification.first_invalid_id, Some(2));
+
+    app.clean_up().await;
+}
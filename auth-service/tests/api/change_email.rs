@@ -0,0 +1,208 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::user::UserStatus;
+use auth_service::utils::auth::generate_email_change_token;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .expect("No auth cookie found");
+    token
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_change_email(&serde_json::json!({
+        "newEmail": get_random_email(),
+        "password": "password123"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_email_invalid_or_unchanged() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    for new_email in ["not-an-email".to_string(), random_email] {
+        let response = app.post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": "password123"
+        })).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_incorrect() {
+    let mut app = TestApp::new().await;
+
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app.post_change_email(&serde_json::json!({
+        "newEmail": get_random_email(),
+        "password": "wrongpassword"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_taken() {
+    let mut app = TestApp::new().await;
+
+    let taken_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": taken_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app.post_change_email(&serde_json::json!({
+        "newEmail": taken_email,
+        "password": "password123"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_email_and_end_sessions_once_confirmed() {
+    let mut app = TestApp::new().await;
+
+    let old_email = get_random_email();
+    let new_email = get_random_email();
+    let jwt_token = signup_and_login(&app, &old_email).await;
+
+    let response = app.post_change_email(&serde_json::json!({
+        "newEmail": new_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Nothing changes until the new address confirms
+    let old = Email::parse(old_email.clone()).unwrap();
+    let new = Email::parse(new_email.clone()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&old).await.unwrap();
+
+    // The token is normally only delivered to the new address
+    let token = generate_email_change_token(&user.id, &old, &new).unwrap();
+    let response = app.post_change_email_confirm(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let changed = app.app_state.user_store.read().await.get_user(&new).await.unwrap();
+    assert_eq!(changed.id, user.id);
+    assert!(changed.email_verified);
+
+    // Sessions started before the change are logged out
+    let response = app.post_verify_token(&serde_json::json!({ "token": jwt_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Only the new email can log in
+    let response = app.login(&serde_json::json!({
+        "email": old_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.login(&serde_json::json!({
+        "email": new_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Tokens are single-use
+    let response = app.post_change_email_confirm(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_taken_before_confirmation() {
+    let mut app = TestApp::new().await;
+
+    let old_email = get_random_email();
+    let new_email = get_random_email();
+    signup_and_login(&app, &old_email).await;
+
+    let old = Email::parse(old_email).unwrap();
+    let new = Email::parse(new_email.clone()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&old).await.unwrap();
+    let token = generate_email_change_token(&user.id, &old, &new).unwrap();
+
+    // Someone else signs up with the address while the confirmation is pending
+    app.signup(&serde_json::json!({
+        "email": new_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.post_change_email_confirm(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_account_not_active_before_confirmation() {
+    let mut app = TestApp::new().await;
+
+    let old_email = get_random_email();
+    let new_email = get_random_email();
+    signup_and_login(&app, &old_email).await;
+
+    let old = Email::parse(old_email).unwrap();
+    let new = Email::parse(new_email).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&old).await.unwrap();
+    let token = generate_email_change_token(&user.id, &old, &new).unwrap();
+
+    app.app_state.user_store.write().await.set_status(&user.id, UserStatus::Suspended, None).await.unwrap();
+
+    let response = app.post_change_email_confirm(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(app.app_state.user_store.read().await.get_user(&old).await.is_ok());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_confirmation_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_change_email_confirm(&serde_json::json!({ "token": "invalid" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,159 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .expect("No auth cookie found");
+    token
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app.post_change_password(&serde_json::json!({
+        "newPassword": "newpassword123"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_incorrect() {
+    let mut app = TestApp::new().await;
+
+    signup_and_login(&app, &get_random_email()).await;
+
+    for current_password in ["wrongpassword", "short"] {
+        let response = app.post_change_password(&serde_json::json!({
+            "currentPassword": current_password,
+            "newPassword": "newpassword123"
+        })).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_incorrect_current_passwords() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    for _ in 0..5 {
+        let response = app.post_change_password(&serde_json::json!({
+            "currentPassword": "wrongpassword",
+            "newPassword": "newpassword123"
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The guesses count against logging in as well
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_rejected_by_policy() {
+    let mut app = TestApp::new().await;
+
+    signup_and_login(&app, &get_random_email()).await;
+
+    // Too short, or unchanged
+    for new_password in ["short", "password123"] {
+        let response = app.post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": new_password
+        })).await;
+
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_change_password_and_log_out_other_sessions() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let old_token = signup_and_login(&app, &random_email).await;
+
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .expect("No auth cookie found");
+
+    // Tokens issued before the change are rejected, the fresh one works
+    let response = app.post_verify_token(&serde_json::json!({ "token": old_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.post_verify_token(&serde_json::json!({ "token": new_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
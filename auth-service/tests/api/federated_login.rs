@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use base64::{engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD}, Engine};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use sha2::{Digest, Sha256};
+
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::domain::email::Email;
+use auth_service::domain::identity_provider::IdentityProvider;
+use auth_service::utils::constants::{FEDERATED_LOGIN_COOKIE_NAME, JWT_COOKIE_NAME};
+use auth_service::ErrorResponse;
+
+const PROVIDER: &str = "acme";
+const CLIENT_ID: &str = "auth-service";
+const CLIENT_SECRET: &str = "client-secret";
+const KEY_ID: &str = "fake-key";
+
+// What the fake provider remembers about a login between approving it and redeeming its code
+#[derive(Clone)]
+struct Grant {
+    email: String,
+    email_verified: bool,
+    nonce: String,
+    code_challenge: String,
+    redirect_uri: String,
+}
+
+#[derive(Clone)]
+struct FakeProviderState {
+    issuer: String,
+    private_key: Arc<Vec<u8>>,
+    public_key: Arc<Vec<u8>>,
+    grants: Arc<Mutex<HashMap<String, Grant>>>,
+}
+
+// Stand-in for an OpenID Connect provider, with a discovery document, a JWKS and
+// a token endpoint that redeems codes for Ed25519 signed ID tokens. Tests play
+// the user's part at the login page by approving logins directly.
+struct FakeIdentityProvider {
+    state: FakeProviderState,
+}
+
+impl FakeIdentityProvider {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+
+        let private_key = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let public_key = Ed25519KeyPair::from_pkcs8(private_key.as_ref()).unwrap().public_key().as_ref().to_vec();
+        let state = FakeProviderState {
+            issuer,
+            private_key: Arc::new(private_key.as_ref().to_vec()),
+            public_key: Arc::new(public_key),
+            grants: Arc::new(Mutex::new(HashMap::new())),
+        };
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Self { state }
+    }
+
+    async fn register_with(&self, app: &TestApp) {
+        let provider = IdentityProvider::new(
+            PROVIDER.to_string(),
+            self.state.issuer.clone(),
+            CLIENT_ID.to_string(),
+            CLIENT_SECRET.to_string(),
+        )
+        .unwrap();
+        app.app_state.identity_provider_store.write().await.add_provider(&provider).await.unwrap();
+    }
+
+    // Logs the user in at the provider for the login the service redirected to,
+    // and returns the parameters the provider sends the browser back with
+    fn approve(&self, login: &reqwest::Response, email: &str, email_verified: bool) -> HashMap<&'static str, String> {
+        assert_eq!(login.status().as_u16(), 303);
+        let location = reqwest::Url::parse(login.headers()["location"].to_str().unwrap()).unwrap();
+        assert_eq!(location.as_str().split('?').next().unwrap(), format!("{}/authorize", self.state.issuer));
+
+        let params: HashMap<String, String> = location.query_pairs().into_owned().collect();
+        assert_eq!(params["client_id"], CLIENT_ID);
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert!(params["scope"].split(' ').any(|scope| scope == "openid"));
+
+        let code = uuid::Uuid::new_v4().to_string();
+        let grant = Grant {
+            email: email.to_string(),
+            email_verified,
+            nonce: params["nonce"].clone(),
+            code_challenge: params["code_challenge"].clone(),
+            redirect_uri: params["redirect_uri"].clone(),
+        };
+        self.state.grants.lock().unwrap().insert(code.clone(), grant);
+
+        HashMap::from([("code", code), ("state", params["state"].clone())])
+    }
+
+    // Makes the ID token for `code` carry a nonce from some other login
+    fn replace_nonce(&self, code: &str) {
+        self.state.grants.lock().unwrap().get_mut(code).unwrap().nonce = "other-nonce".to_string();
+    }
+}
+
+async fn discovery(State(state): State<FakeProviderState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "issuer": state.issuer,
+        "authorization_endpoint": format!("{}/authorize", state.issuer),
+        "token_endpoint": format!("{}/token", state.issuer),
+        "jwks_uri": format!("{}/jwks", state.issuer),
+    }))
+}
+
+async fn jwks(State(state): State<FakeProviderState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "keys": [{
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "x": URL_SAFE_NO_PAD.encode(state.public_key.as_slice()),
+            "kid": KEY_ID,
+            "alg": "EdDSA",
+            "use": "sig",
+        }]
+    }))
+}
+
+async fn token(
+    State(state): State<FakeProviderState>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let invalid_grant = (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid_grant" }))).into_response();
+
+    let credentials = format!("Basic {}", STANDARD.encode(format!("{}:{}", CLIENT_ID, CLIENT_SECRET)));
+    if headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()) != Some(credentials.as_str()) {
+        return (StatusCode::UNAUTHORIZED, Json(serde_json::json!({ "error": "invalid_client" }))).into_response();
+    }
+
+    let grant = match form.get("code").and_then(|code| state.grants.lock().unwrap().remove(code)) {
+        Some(grant) => grant,
+        None => return invalid_grant,
+    };
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(form["code_verifier"].as_bytes()));
+    if challenge != grant.code_challenge || form["redirect_uri"] != grant.redirect_uri {
+        return invalid_grant;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let claims = serde_json::json!({
+        "iss": state.issuer,
+        "sub": format!("upstream-{}", grant.email),
+        "aud": CLIENT_ID,
+        "iat": now,
+        "exp": now + 300,
+        "email": grant.email,
+        "email_verified": grant.email_verified,
+        "nonce": grant.nonce,
+    });
+    let mut header = Header::new(Algorithm::EdDSA);
+    header.kid = Some(KEY_ID.to_string());
+    let id_token = jsonwebtoken::encode(&header, &claims, &EncodingKey::from_ed_der(&state.private_key)).unwrap();
+
+    Json(serde_json::json!({
+        "access_token": "upstream-access-token",
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+    .into_response()
+}
+
+fn has_cookie(response: &reqwest::Response, name: &str) -> bool {
+    response.cookies().any(|cookie| cookie.name() == name && !cookie.value().is_empty())
+}
+
+async fn error_message(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[tokio::test]
+async fn should_create_user_on_first_login_through_provider() {
+    let mut app = TestApp::new().await;
+    let provider = FakeIdentityProvider::start().await;
+    provider.register_with(&app).await;
+
+    let login = app.get_federated_login(PROVIDER, None).await;
+    assert!(has_cookie(&login, FEDERATED_LOGIN_COOKIE_NAME));
+    let email = get_random_email();
+    let callback = provider.approve(&login, &email, true);
+
+    let response = app.get_federated_callback(PROVIDER, &callback).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["location"], "/");
+    assert!(has_cookie(&response, JWT_COOKIE_NAME));
+
+    let user = app.app_state.user_store.read().await.get_user(&Email::parse(email).unwrap()).await.unwrap();
+    assert!(user.email_verified);
+    assert_eq!(app.get_sessions().await.status().as_u16(), 200);
+
+    // The login can't be finished a second time
+    let response = app.get_federated_callback(PROVIDER, &callback).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_link_existing_user_by_email() {
+    let mut app = TestApp::new().await;
+    let provider = FakeIdentityProvider::start().await;
+    provider.register_with(&app).await;
+
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    let existing = app.app_state.user_store.read().await.get_user(&Email::parse(email.clone()).unwrap()).await.unwrap();
+    app.app_state.user_store.write().await.mark_email_verified(&existing.email).await.unwrap();
+
+    let login = app.get_federated_login(PROVIDER, Some("/authorize?client_id=app")).await;
+    let response = app.get_federated_callback(PROVIDER, &provider.approve(&login, &email, true)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers()["location"], "/authorize?client_id=app");
+    assert!(has_cookie(&response, JWT_COOKIE_NAME));
+
+    let user = app.app_state.user_store.read().await.get_user(&existing.email).await.unwrap();
+    assert_eq!(user.id, existing.id);
+
+    // The password keeps working
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_lock_out_previous_owner_of_unverified_account() {
+    let mut app = TestApp::new().await;
+    let provider = FakeIdentityProvider::start().await;
+    provider.register_with(&app).await;
+
+    // Someone signed up with the address before its owner did
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let login = app.get_federated_login(PROVIDER, None).await;
+    let response = app.get_federated_callback(PROVIDER, &provider.approve(&login, &email, true)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(has_cookie(&response, JWT_COOKIE_NAME));
+
+    let user = app.app_state.user_store.read().await.get_user(&Email::parse(email.clone()).unwrap()).await.unwrap();
+    assert!(user.email_verified);
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_still_require_2fa_for_linked_users() {
+    let mut app = TestApp::new().await;
+    let provider = FakeIdentityProvider::start().await;
+    provider.register_with(&app).await;
+
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    })).await;
+
+    let login = app.get_federated_login(PROVIDER, None).await;
+    let response = app.get_federated_callback(PROVIDER, &provider.approve(&login, &email, true)).await;
+    assert_eq!(response.status().as_u16(), 303);
+    assert!(response.headers()["location"].to_str().unwrap().starts_with("/?login_attempt_id="));
+    assert!(!has_cookie(&response, JWT_COOKIE_NAME));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_unverified_provider_emails() {
+    let mut app = TestApp::new().await;
+    let provider = FakeIdentityProvider::start().await;
+    provider.register_with(&app).await;
+
+    let email = get_random_email();
+    let login = app.get_federated_login(PROVIDER, None).await;
+    let response = app.get_federated_callback(PROVIDER, &provider.approve(&login, &email, false)).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let user = app.app_state.user_store.read().await.get_user(&Email::parse(email).unwrap()).await;
+    assert!(user.is_err());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_callbacks_for_other_logins() {
+    let mut app = TestApp::new().await;
+    let provider = FakeIdentityProvider::start().await;
+    provider.register_with(&app).await;
+
+    // A callback the browser didn't start a login for
+    let response = app.get_federated_callback(PROVIDER, &[("code", "code"), ("state", "state")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // A callback carrying someone else's state
+    let login = app.get_federated_login(PROVIDER, None).await;
+    let mut callback = provider.approve(&login, &get_random_email(), true);
+    callback.insert("state", "other-state".to_string());
+    let response = app.get_federated_callback(PROVIDER, &callback).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // An ID token issued for another login
+    let login = app.get_federated_login(PROVIDER, None).await;
+    let callback = provider.approve(&login, &get_random_email(), true);
+    provider.replace_nonce(&callback["code"]);
+    let response = app.get_federated_callback(PROVIDER, &callback).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(error_message(response).await, "Login with identity provider failed");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_provider() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_federated_login("unknown", None).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
@@ -1,11 +1,23 @@
 use auth_service::{Application, get_postgres_pool, get_redis_client};
 use uuid::Uuid;
-use auth_service::data_stores::data_store::{UserStore, BannedTokenStoreType, TwoFACodeStore, BannedTokenStore};
+use auth_service::data_stores::data_store::{PasswordResetToken, UserStore, BannedTokenStoreType, TwoFACodeStore, BannedTokenStore, PasswordResetTokenStore, TotpSecretStore, RecoveryCodeStore, RefreshTokenStore, SessionStore, TokenVersionStore, FailedAttemptStore, HashmapFailedAttemptStore, WebauthnCredentialStore, OAuthClientStore, HashmapIdentityProviderStore, IdentityProviderStore, RoleStore, AuditLog, WebhookStore};
 use auth_service::data_stores::redis_two_fa_code_store::RedisTwoFACodeStore;
 use auth_service::data_stores::redis_banned_token_store::RedisBannedTokenStore;
+use auth_service::data_stores::redis_password_reset_token_store::RedisPasswordResetTokenStore;
 use auth_service::data_stores::postgres_user_store::PostgresUserStore;
+use auth_service::data_stores::postgres_oauth_client_store::PostgresOAuthClientStore;
+use auth_service::data_stores::postgres_role_store::PostgresRoleStore;
+use auth_service::data_stores::postgres_audit_log::PostgresAuditLog;
+use auth_service::data_stores::postgres_webhook_store::PostgresWebhookStore;
+use auth_service::data_stores::postgres_totp_secret_store::PostgresTotpSecretStore;
+use auth_service::data_stores::postgres_recovery_code_store::PostgresRecoveryCodeStore;
+use auth_service::data_stores::postgres_refresh_token_store::PostgresRefreshTokenStore;
+use auth_service::data_stores::postgres_session_store::PostgresSessionStore;
+use auth_service::data_stores::postgres_token_version_store::PostgresTokenVersionStore;
+use auth_service::data_stores::postgres_webauthn_credential_store::PostgresWebauthnCredentialStore;
 use auth_service::services::mock_email_client::MockEmailClient;
-use auth_service::app_state::app_state::AppState;
+use auth_service::app_state::AppState;
+use auth_service::domain::email::Email;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use reqwest::cookie::Jar;
@@ -32,12 +44,24 @@ impl TestApp {
         let (pg_pool, db_name) = configure_postgresql().await;
         println!("✅ PostgreSQL pool configured");
 
-        let user_store = Arc::new(RwLock::new(Box::new(PostgresUserStore::new(pg_pool)) as Box<dyn UserStore + Send + Sync>));
+        let user_store = Arc::new(RwLock::new(Box::new(PostgresUserStore::new(pg_pool.clone())) as Box<dyn UserStore + Send + Sync>));
+        let totp_secret_store = Arc::new(RwLock::new(Box::new(PostgresTotpSecretStore::new(pg_pool.clone())) as Box<dyn TotpSecretStore + Send + Sync>));
+        let recovery_code_store = Arc::new(RwLock::new(Box::new(PostgresRecoveryCodeStore::new(pg_pool.clone())) as Box<dyn RecoveryCodeStore + Send + Sync>));
+        let refresh_token_store = Arc::new(RwLock::new(Box::new(PostgresRefreshTokenStore::new(pg_pool.clone())) as Box<dyn RefreshTokenStore + Send + Sync>));
+        let session_store = Arc::new(RwLock::new(Box::new(PostgresSessionStore::new(pg_pool.clone())) as Box<dyn SessionStore + Send + Sync>));
+        let webauthn_credential_store = Arc::new(RwLock::new(Box::new(PostgresWebauthnCredentialStore::new(pg_pool.clone())) as Box<dyn WebauthnCredentialStore + Send + Sync>));
+        let oauth_client_store = Arc::new(RwLock::new(Box::new(PostgresOAuthClientStore::new(pg_pool.clone())) as Box<dyn OAuthClientStore + Send + Sync>));
+        let role_store = Arc::new(RwLock::new(Box::new(PostgresRoleStore::new(pg_pool.clone())) as Box<dyn RoleStore + Send + Sync>));
+        let audit_log = Arc::new(RwLock::new(Box::new(PostgresAuditLog::new(pg_pool.clone())) as Box<dyn AuditLog + Send + Sync>));
+        let webhook_store = Arc::new(RwLock::new(Box::new(PostgresWebhookStore::new(pg_pool.clone())) as Box<dyn WebhookStore + Send + Sync>));
+        let identity_provider_store = Arc::new(RwLock::new(Box::new(HashmapIdentityProviderStore::default()) as Box<dyn IdentityProviderStore + Send + Sync>));
         println!("✅ User store configured");
 
         let cookie_jar = Arc::new(Jar::default());
+        // Redirects aren't followed, so tests can inspect where the OAuth2 endpoints send the browser
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
         println!("✅ HTTP client configured");
@@ -45,14 +69,19 @@ impl TestApp {
         println!("🔧 Configuring Redis stores...");
         let banned_token_store = Arc::new(RwLock::new(BannedTokenStoreType::Redis(RedisBannedTokenStore::new(Arc::new(RwLock::new(configure_redis()))))));
         let two_fa_code_store = Arc::new(RwLock::new(Box::new(RedisTwoFACodeStore::new(Arc::new(RwLock::new(configure_redis())))) as Box<dyn TwoFACodeStore + Send + Sync>));
+        let password_reset_token_store = Arc::new(RwLock::new(Box::new(RedisPasswordResetTokenStore::new(Arc::new(RwLock::new(configure_redis())))) as Box<dyn PasswordResetTokenStore + Send + Sync>));
+        let token_version_store = Arc::new(RwLock::new(Box::new(PostgresTokenVersionStore::new(pg_pool, Arc::new(RwLock::new(configure_redis())))) as Box<dyn TokenVersionStore + Send + Sync>));
         println!("✅ Redis stores configured");
 
-        let email_client = Arc::new(RwLock::new(Box::new(MockEmailClient::default()) as Box<dyn auth_service::domain::EmailClient + Send + Sync>));
-        let app_state = AppState::new(user_store, banned_token_store, two_fa_code_store, email_client);
+        // Every test client has the same IP, so failed attempts are counted per app rather than in the shared Redis
+        let failed_attempt_store = Arc::new(RwLock::new(Box::new(HashmapFailedAttemptStore::default()) as Box<dyn FailedAttemptStore + Send + Sync>));
+
+        let email_client = Arc::new(RwLock::new(Box::new(MockEmailClient) as Box<dyn auth_service::domain::EmailClient + Send + Sync>));
+        let app_state = AppState::new(user_store, banned_token_store, two_fa_code_store, email_client, password_reset_token_store, totp_secret_store, recovery_code_store, refresh_token_store, session_store, token_version_store, failed_attempt_store, webauthn_credential_store, oauth_client_store, identity_provider_store, role_store, audit_log, webhook_store);
         println!("✅ App state configured");
 
         println!("🔧 Building application...");
-        let app = Application::build(app_state.clone(), &test::APP_ADDRESS) // Clone the app_state
+        let app = Application::build(app_state.clone(), test::APP_ADDRESS) // Clone the app_state
             .await
             .expect("Failed to build app");
         println!("✅ Application built successfully");
@@ -91,7 +120,7 @@ impl TestApp {
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -102,7 +131,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -114,7 +143,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -123,16 +152,47 @@ impl TestApp {
 
     pub async fn logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
 
-    pub async fn verify_token(&self) -> reqwest::Response {
+    pub async fn post_token_refresh(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
+            .post(format!("{}/token/refresh", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -144,6 +204,16 @@ impl TestApp {
         banned_token_store.is_token_banned(token).await.unwrap_or(false)
     }
 
+    // The store only keeps a hash of the reset token that was emailed, so swap in
+    // a token the test knows
+    pub async fn replace_password_reset_token(&self, email: &str) -> String {
+        let email = Email::parse(email.to_owned()).unwrap();
+        let token = PasswordResetToken::default();
+        let mut token_store = self.app_state.password_reset_token_store.write().await;
+        token_store.add_token(&email, &token).await.unwrap();
+        token.as_ref().to_owned()
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -156,6 +226,450 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link_consume<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link/consume", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webauthn/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webauthn_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_authorize<Form>(&self, form: &Form) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/authorize", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_token<Form>(&self, form: &Form) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_federated_login(&self, provider: &str, return_to: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(format!("{}/login/{}", &self.address, provider));
+        if let Some(return_to) = return_to {
+            request = request.query(&[("return_to", return_to)]);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_federated_callback<Query>(&self, provider: &str, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/callback/{}", &self.address, provider))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: Option<&str>) -> reqwest::Response {
+        let mut request = self.http_client.get(format!("{}/userinfo", &self.address));
+        if let Some(access_token) = access_token {
+            request = request.bearer_auth(access_token);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/openid-configuration", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_users<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/admin/users", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_user(&self, user_id: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users/{}", &self.address, user_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_user_status<Body>(&self, user_id: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/users/{}/status", &self.address, user_id))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_user_2fa<Body>(&self, user_id: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/users/{}/2fa", &self.address, user_id))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_password_reset(&self, user_id: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/users/{}/password-reset", &self.address, user_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_admin_user_sessions(&self, user_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin/users/{}/sessions", &self.address, user_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_user_roles(&self, user_id: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users/{}/roles", &self.address, user_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_user_role<Body>(&self, user_id: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/users/{}/roles", &self.address, user_id))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_user_role(&self, user_id: &str, role: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin/users/{}/roles/{}", &self.address, user_id, role))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_log<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/admin/audit-log", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_log_verification(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-log/verify", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_webhook<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/webhooks", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_webhooks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/webhooks", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_webhook(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin/webhooks/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_webhook_deliveries<Query>(&self, id: &str, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/admin/webhooks/{}/deliveries", &self.address, id))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/resend-2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/delete", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_restore_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/restore", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/2fa/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn clean_up(&mut self) {
         self.clean_up_called = true;
         let db_name = self.db_name.clone();
@@ -170,9 +684,6 @@ impl Drop for TestApp {
         if !self.clean_up_called {
             panic!("TestApp was dropped without calling clean_up() first!");
         }
-        
-        // Call the original clean_up method
-        self.clean_up();
     }
 }
 
@@ -0,0 +1,16 @@
+use crate::helpers::TestApp;
+use jsonwebtoken::jwk::JwkSet;
+
+#[tokio::test]
+async fn should_return_empty_key_set_for_hs256() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Tests sign with the shared JWT_SECRET, which must never be published
+    let jwks = response.json::<JwkSet>().await.expect("Could not deserialize response body to JwkSet");
+    assert!(jwks.keys.is_empty());
+
+    app.clean_up().await;
+}
@@ -1,5 +1,5 @@
 use crate::helpers::{TestApp, get_random_email};
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse, routes::TwoFactorAuthResponse};
+use auth_service::{utils::constants::JWT_COOKIE_NAME, routes::TwoFactorAuthResponse};
 
 // #[tokio::test]
 // async fn login_returns_200() {
@@ -154,5 +154,84 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
         assert_eq!(login_attempt_id, stored_login_attempt_id.as_ref());
     }
     
+    app.clean_up().await;
+}
+#[tokio::test]
+async fn should_return_429_after_too_many_failed_attempts() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    for _ in 0..5 {
+        let response = app.login(&serde_json::json!({
+            "email": random_email,
+            "password": "wrongpassword"
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // Locked out, even with the right password
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get("Retry-After")
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 60);
+
+    // Other accounts aren't affected
+    let other_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": other_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    let response = app.login(&serde_json::json!({
+        "email": other_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_client_guesses_across_accounts() {
+    let mut app = TestApp::new().await;
+
+    // Spread over many accounts, so no single account gets locked out
+    for _ in 0..20 {
+        let response = app.login(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123"
+        })).await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+
     app.clean_up().await;
 }
\ No newline at end of file
@@ -0,0 +1,143 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::routes::TwoFactorAuthResponse;
+use auth_service::utils::auth::generate_magic_link_token;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+// Signs the user up and returns the magic link token they would have been emailed
+async fn signup_and_get_magic_link(app: &TestApp, email: &str, requires_2fa: bool) -> String {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    })).await;
+
+    let email = Email::parse(email.to_string()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    let token_version = app.app_state.token_version_store.read().await.get_version(&user.id).await.unwrap();
+    generate_magic_link_token(&user.id, token_version).unwrap()
+}
+
+#[tokio::test]
+async fn should_return_200_for_known_and_unknown_emails() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    // Unknown emails get the same response so accounts can't be enumerated
+    for email in [random_email, get_random_email()] {
+        let response = app.post_magic_link(&serde_json::json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_requests() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    // Unknown emails are limited the same way, so the limit doesn't give accounts away
+    for email in [random_email, get_random_email()] {
+        for _ in 0..5 {
+            let response = app.post_magic_link(&serde_json::json!({ "email": email })).await;
+            assert_eq!(response.status().as_u16(), 200);
+        }
+        let response = app.post_magic_link(&serde_json::json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 429);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_invalid() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_magic_link(&serde_json::json!({ "email": "not-an-email" })).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_log_in_with_magic_link_only_once() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let token = signup_and_get_magic_link(&app, &random_email, false).await;
+
+    let response = app.post_magic_link_consume(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty()));
+
+    let response = app.post_magic_link_consume(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_206_if_user_requires_2fa() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let token = signup_and_get_magic_link(&app, &random_email, true).await;
+
+    let response = app.post_magic_link_consume(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(!response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Failed to parse response")
+        .login_attempt_id;
+    let email = Email::parse(random_email).unwrap();
+    let (stored_login_attempt_id, _) = app.app_state.two_fa_code_store.read().await.get_code(&email).await.unwrap();
+    assert_eq!(stored_login_attempt_id.as_ref(), login_attempt_id);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_invalid_or_outdated() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_magic_link_consume(&serde_json::json!({ "token": "invalid" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging out everywhere cancels links that were already sent
+    let random_email = get_random_email();
+    let token = signup_and_get_magic_link(&app, &random_email, false).await;
+    let email = Email::parse(random_email).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    app.app_state.token_version_store.write().await.bump_version(&user.id).await.unwrap();
+
+    let response = app.post_magic_link_consume(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_magic_link_consume(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
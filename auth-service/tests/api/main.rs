@@ -1,7 +1,26 @@
+mod account;
+mod admin;
+mod audit_log;
+mod change_email;
+mod change_password;
+mod federated_login;
 mod helpers;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth;
+mod password_reset;
+mod recovery_codes;
+mod refresh_token;
+mod resend_2fa;
+mod roles;
 mod root;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
+mod webauthn;
+mod webhooks;
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::oauth::{CodeVerifier, OAuthClient};
+use auth_service::domain::role::{Role, ADMIN_ROLE};
+use auth_service::routes::{OpenIdConfiguration, TokenResponse, UserinfoResponse};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, OIDC_ISSUER};
+use auth_service::ErrorResponse;
+use reqwest::Url;
+
+const REDIRECT_URI: &str = "http://app.example/callback";
+
+async fn add_client(app: &TestApp) -> OAuthClient {
+    let client = OAuthClient::new(
+        uuid::Uuid::new_v4().to_string(),
+        "Test <App>".to_string(),
+        vec![REDIRECT_URI.to_string()],
+    );
+    app.app_state.oauth_client_store.write().await.add_client(&client).await.unwrap();
+    client
+}
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    email
+}
+
+fn code_verifier() -> CodeVerifier {
+    CodeVerifier::parse(format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())).unwrap()
+}
+
+fn authorize_request(client: &OAuthClient, verifier: &CodeVerifier) -> HashMap<&'static str, String> {
+    HashMap::from([
+        ("response_type", "code".to_string()),
+        ("client_id", client.id.clone()),
+        ("redirect_uri", REDIRECT_URI.to_string()),
+        ("code_challenge", verifier.challenge().as_ref().to_string()),
+        ("code_challenge_method", "S256".to_string()),
+        ("state", "xyz".to_string()),
+    ])
+}
+
+// Query parameters of the redirect a response answers with
+fn redirect_params(response: &reqwest::Response) -> (String, HashMap<String, String>) {
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response.headers()["location"].to_str().unwrap();
+    let (target, query) = location.split_once('?').unwrap();
+    let params = query
+        .split('&')
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap();
+            (name.to_string(), value.to_string())
+        })
+        .collect();
+    (target.to_string(), params)
+}
+
+// Approves the authorization on the consent screen and returns the code the client receives
+async fn approve(app: &TestApp, mut form: HashMap<&'static str, String>) -> String {
+    form.insert("decision", "approve".to_string());
+
+    let (target, params) = redirect_params(&app.post_authorize(&form).await);
+    assert_eq!(target, REDIRECT_URI);
+    assert_eq!(params["state"], "xyz");
+    params["code"].clone()
+}
+
+fn token_request(client: &OAuthClient, code: &str, verifier: &CodeVerifier) -> HashMap<&'static str, String> {
+    HashMap::from([
+        ("grant_type", "authorization_code".to_string()),
+        ("code", code.to_string()),
+        ("redirect_uri", REDIRECT_URI.to_string()),
+        ("client_id", client.id.clone()),
+        ("code_verifier", verifier.as_ref().to_string()),
+    ])
+}
+
+async fn token_error(response: reqwest::Response) -> String {
+    assert_eq!(response.status().as_u16(), 400);
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[tokio::test]
+async fn should_issue_access_token_for_approved_authorization() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+    let verifier = code_verifier();
+
+    let response = app.get_authorize(&authorize_request(&client, &verifier)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Test &lt;App&gt;"));
+    assert!(!page.contains("{{"));
+
+    let code = approve(&app, authorize_request(&client, &verifier)).await;
+    let response = app.post_token(&token_request(&client, &code, &verifier)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["cache-control"], "no-store");
+    let token = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(token.token_type, "Bearer");
+    assert!(token.id_token.is_none());
+
+    let response = app.get_userinfo(Some(&token.access_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Codes can only be redeemed once
+    let response = app.post_token(&token_request(&client, &code, &verifier)).await;
+    assert_eq!(token_error(response).await, "invalid_grant");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_send_logged_out_users_to_login_page() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    let response = app.get_authorize(&authorize_request(&client, &code_verifier())).await;
+
+    let (target, params) = redirect_params(&response);
+    assert_eq!(target, "/");
+    assert!(params["return_to"].starts_with("%2Fauthorize%3F"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_redirect_to_unregistered_uris() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+    let verifier = code_verifier();
+
+    let mut request = authorize_request(&client, &verifier);
+    request.insert("redirect_uri", "http://evil.example/callback".to_string());
+    let response = app.get_authorize(&request).await;
+    assert_eq!(token_error(response).await, "invalid_request");
+
+    let mut request = authorize_request(&client, &verifier);
+    request.insert("client_id", "unknown".to_string());
+    let response = app.get_authorize(&request).await;
+    assert_eq!(token_error(response).await, "invalid_request");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_pkce() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+    let verifier = code_verifier();
+
+    let mut request = authorize_request(&client, &verifier);
+    request.insert("code_challenge_method", "plain".to_string());
+    let (target, params) = redirect_params(&app.get_authorize(&request).await);
+    assert_eq!(target, REDIRECT_URI);
+    assert_eq!(params["error"], "invalid_request");
+    assert_eq!(params["state"], "xyz");
+
+    let mut request = authorize_request(&client, &verifier);
+    request.remove("code_challenge");
+    let (_, params) = redirect_params(&app.get_authorize(&request).await);
+    assert_eq!(params["error"], "invalid_request");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_with_error_if_user_denies() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+
+    let mut form = authorize_request(&client, &code_verifier());
+    form.insert("decision", "deny".to_string());
+    let (_, params) = redirect_params(&app.post_authorize(&form).await);
+    assert_eq!(params["error"], "access_denied");
+    assert!(!params.contains_key("code"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_code_with_wrong_verifier_or_redirect_uri() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+    let verifier = code_verifier();
+    let code = approve(&app, authorize_request(&client, &verifier)).await;
+
+    let response = app.post_token(&token_request(&client, &code, &code_verifier())).await;
+    assert_eq!(token_error(response).await, "invalid_grant");
+
+    let mut request = token_request(&client, &code, &verifier);
+    request.insert("redirect_uri", "http://app.example/other".to_string());
+    assert_eq!(token_error(app.post_token(&request).await).await, "invalid_grant");
+
+    let mut request = token_request(&client, &code, &verifier);
+    request.insert("grant_type", "password".to_string());
+    assert_eq!(token_error(app.post_token(&request).await).await, "unsupported_grant_type");
+
+    // Failed attempts don't use up the code
+    let response = app.post_token(&token_request(&client, &code, &verifier)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+async fn redeem(app: &TestApp, client: &OAuthClient, code: &str, verifier: &CodeVerifier) -> TokenResponse {
+    let response = app.post_token(&token_request(client, code, verifier)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+}
+
+#[tokio::test]
+async fn should_not_accept_access_tokens_as_auth_tokens() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    let email = signup_and_login(&app).await;
+    let email = Email::parse(email).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    let admin = Role::parse(ADMIN_ROLE.to_owned()).unwrap();
+    app.app_state.role_store.write().await.grant_role(&user.id, &admin).await.unwrap();
+
+    let verifier = code_verifier();
+    let code = approve(&app, authorize_request(&client, &verifier)).await;
+    let token = redeem(&app, &client, &code, &verifier).await;
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token.access_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Even an admin's access token doesn't open the admin API
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", JWT_COOKIE_NAME, token.access_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.get_admin_users(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+// The tests sign tokens with HS256, which ID tokens are never issued with
+#[tokio::test]
+async fn should_not_issue_id_token_with_hs256_key() {
+    let mut app = TestApp::new().await;
+
+    let client = add_client(&app).await;
+    let email = signup_and_login(&app).await;
+    let verifier = code_verifier();
+
+    let mut request = authorize_request(&client, &verifier);
+    request.insert("scope", "openid".to_string());
+    request.insert("nonce", "n-0S6_WzA2Mj".to_string());
+    let response = app.get_authorize(&request).await;
+    assert!(response.text().await.unwrap().contains("n-0S6_WzA2Mj"));
+
+    let code = approve(&app, request).await;
+    let token = redeem(&app, &client, &code, &verifier).await;
+    assert!(token.id_token.is_none());
+
+    // The client can still find out who the user is from the userinfo endpoint
+    let response = app.get_userinfo(Some(&token.access_token)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let userinfo = response
+        .json::<UserinfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserinfoResponse");
+    assert_eq!(userinfo.email, email);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_userinfo_requests_without_valid_access_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_userinfo(None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["www-authenticate"], r#"Bearer error="invalid_token""#);
+
+    let response = app.get_userinfo(Some("invalid")).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging out everywhere revokes access tokens issued to clients too
+    let client = add_client(&app).await;
+    signup_and_login(&app).await;
+    let verifier = code_verifier();
+    let code = approve(&app, authorize_request(&client, &verifier)).await;
+    let token = redeem(&app, &client, &code, &verifier).await;
+
+    assert_eq!(app.post_logout_all().await.status().as_u16(), 200);
+    let response = app.get_userinfo(Some(&token.access_token)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_publish_openid_configuration() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let configuration = response
+        .json::<OpenIdConfiguration>()
+        .await
+        .expect("Could not deserialize response body to OpenIdConfiguration");
+    assert_eq!(configuration.issuer, *OIDC_ISSUER);
+    assert_eq!(configuration.token_endpoint, format!("{}/token", *OIDC_ISSUER));
+    assert_eq!(configuration.jwks_uri, format!("{}/.well-known/jwks.json", *OIDC_ISSUER));
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256"]);
+    assert!(configuration.scopes_supported.is_empty());
+    assert!(configuration.id_token_signing_alg_values_supported.is_empty());
+
+    app.clean_up().await;
+}
@@ -0,0 +1,179 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+use reqwest::Url;
+
+#[tokio::test]
+async fn should_return_200_for_unknown_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": get_random_email()
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_requests() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    // Unknown emails are limited the same way, so the limit doesn't give accounts away
+    for email in [random_email, get_random_email()] {
+        for _ in 0..5 {
+            let response = app.post_password_reset_request(&serde_json::json!({ "email": email })).await;
+            assert_eq!(response.status().as_u16(), 200);
+        }
+        let response = app.post_password_reset_request(&serde_json::json!({ "email": email })).await;
+        assert_eq!(response.status().as_u16(), 429);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": "invalid_email"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    app.post_password_reset_request(&serde_json::json!({
+        "email": random_email
+    })).await;
+
+    let response = app.post_password_reset_confirm(&serde_json::json!({
+        "email": random_email,
+        "token": "a".repeat(32),
+        "newPassword": "newpassword123"
+    })).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reset_password_and_reject_reused_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    // A session on another device, whose token isn't presented with the reset
+    let other_token = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    }))
+    .await
+    .cookies()
+    .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+    .expect("No auth cookie found")
+    .value()
+    .to_owned();
+
+    let login_response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    let old_token = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let old_refresh_token = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_TOKEN_COOKIE_NAME)
+        .expect("No refresh token cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_password_reset_request(&serde_json::json!({
+        "email": random_email
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app.replace_password_reset_token(&random_email).await;
+
+    let confirm_body = serde_json::json!({
+        "email": random_email,
+        "token": token,
+        "newPassword": "newpassword123"
+    });
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The session the reset was performed from is no longer valid, and neither are the others
+    assert!(app.is_token_banned(&old_token).await);
+    let response = app.post_verify_token(&serde_json::json!({ "token": other_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Refresh tokens issued before the reset can't mint new sessions
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_TOKEN_COOKIE_NAME, old_refresh_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The token is single-use
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Sessions started after the reset are valid
+    let new_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let response = app.post_verify_token(&serde_json::json!({ "token": new_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
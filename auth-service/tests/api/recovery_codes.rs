@@ -0,0 +1,165 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::recovery_code::RECOVERY_CODE_COUNT;
+use auth_service::domain::user::UserStatus;
+use auth_service::routes::{
+    RecoveryCodesResponse, RecoveryCodesStatusResponse, SignupResponse, TwoFactorAuthResponse, Verify2FAResponse,
+};
+
+// Signs up a user with 2FA enabled and returns the recovery codes issued at signup
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    })).await;
+
+    response
+        .json::<SignupResponse>()
+        .await
+        .unwrap()
+        .recovery_codes
+        .expect("No recovery codes issued at signup")
+}
+
+async fn login_with_code(app: &TestApp, email: &str, code: &str) -> reqwest::Response {
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await
+}
+
+#[tokio::test]
+async fn should_accept_recovery_code_only_once() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_code(&app, &random_email, &codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<Verify2FAResponse>().await.unwrap();
+    assert_eq!(body.recovery_codes_remaining, RECOVERY_CODE_COUNT - 1);
+
+    app.logout().await;
+
+    let response = login_with_code(&app, &random_email, &codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Codes are accepted regardless of case or separator
+    let retyped_code = codes[1].replace('-', "").to_uppercase();
+    let response = login_with_code(&app, &random_email, &retyped_code).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<RecoveryCodesStatusResponse>().await.unwrap();
+    assert_eq!(body.remaining, RECOVERY_CODE_COUNT - 2);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_burn_recovery_code_if_account_not_active() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    // Suspended between entering the password and the second factor
+    let email = Email::parse(random_email.clone()).unwrap();
+    let user_id = app.app_state.user_store.read().await.get_user(&email).await.unwrap().id;
+    app.app_state.user_store.write().await.set_status(&user_id, UserStatus::Suspended, None).await.unwrap();
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": codes[0]
+    })).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let remaining = app.app_state.recovery_code_store.read().await.remaining_codes(&email).await.unwrap();
+    assert_eq!(remaining, RECOVERY_CODE_COUNT);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_old_codes_on_regenerate() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let old_codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = login_with_code(&app, &random_email, &old_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_codes = response.json::<RecoveryCodesResponse>().await.unwrap().recovery_codes;
+    assert_eq!(new_codes.len(), RECOVERY_CODE_COUNT);
+
+    app.logout().await;
+
+    let response = login_with_code(&app, &random_email, &old_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login_with_code(&app, &random_email, &new_codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_regenerating_without_2fa() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_getting_status_without_jwt_cookie() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
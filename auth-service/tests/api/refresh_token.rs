@@ -0,0 +1,175 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::user::UserStatus;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+use reqwest::Url;
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> Option<String> {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .map(|cookie| cookie.value().to_owned())
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).expect("No refresh token cookie found")
+}
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_TOKEN_COOKIE_NAME, token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_refresh_token_unknown() {
+    let mut app = TestApp::new().await;
+
+    set_refresh_cookie(&app, &"a".repeat(64));
+
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_rotate_refresh_token_and_issue_new_jwt() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let first_token = signup_and_login(&app, &random_email).await;
+
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwt = get_cookie(&response, JWT_COOKIE_NAME).expect("No auth cookie found");
+    let second_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).expect("No refresh token cookie found");
+    assert_ne!(first_token, second_token);
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": jwt })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The rotated token keeps working
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_on_reuse() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let first_token = signup_and_login(&app, &random_email).await;
+
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let second_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).expect("No refresh token cookie found");
+
+    // Replaying the rotated token is treated as theft
+    set_refresh_cookie(&app, &first_token);
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // ...which also locks out the token that replaced it
+    set_refresh_cookie(&app, &second_token);
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_end_session_on_reuse() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let first_token = signup_and_login(&app, &random_email).await;
+
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let jwt = get_cookie(&response, JWT_COOKIE_NAME).expect("No auth cookie found");
+
+    set_refresh_cookie(&app, &first_token);
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Auth tokens minted for the session stop working along with it
+    let response = app.post_verify_token(&serde_json::json!({ "token": jwt })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_refuse_refresh_for_blocked_or_deleted_users() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let email = Email::parse(random_email).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+
+    app.app_state.user_store.write().await.set_status(&user.id, UserStatus::Suspended, None).await.unwrap();
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    // The token family is gone, so lifting the suspension doesn't bring the session back
+    app.app_state.user_store.write().await.set_status(&user.id, UserStatus::Active, None).await.unwrap();
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.login(&serde_json::json!({
+        "email": email.as_ref(),
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.app_state.user_store.write().await.mark_deleted(&user.id).await.unwrap();
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_refresh_token_on_logout() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let token = signup_and_login(&app, &random_email).await;
+
+    let response = app.logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &token);
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,124 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::routes::TwoFactorAuthResponse;
+use auth_service::domain::email::Email;
+
+#[tokio::test]
+async fn should_return_429_if_code_was_sent_too_recently() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = signup_and_start_2fa_login(&app, &random_email).await;
+
+    let response = app.post_resend_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // The code that was already sent still works
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_login_attempt_id_is_unknown() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_start_2fa_login(&app, &random_email).await;
+
+    let response = app.post_resend_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": uuid::Uuid::new_v4().to_string()
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_email_and_user_without_emailed_codes() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    for email in [random_email, get_random_email()] {
+        let response = app.post_resend_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": uuid::Uuid::new_v4().to_string()
+        })).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({
+            "email": "invalid_email",
+            "loginAttemptId": uuid::Uuid::new_v4().to_string()
+        }),
+        serde_json::json!({
+            "email": get_random_email(),
+            "loginAttemptId": "invalid_login_attempt_id"
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_resend_2fa(test_case).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", test_case);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_resend_2fa(&serde_json::json!({
+        "email": get_random_email()
+    })).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
+
+// Signs up with emailed 2FA, logs in and returns the login attempt ID and the code that was sent
+async fn signup_and_start_2fa_login(app: &TestApp, email: &str) -> (String, String) {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    })).await;
+
+    let login_response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    let login_attempt_id = login_response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Failed to parse response")
+        .login_attempt_id;
+
+    let email = Email::parse(email.to_string()).unwrap();
+    let (_, code) = app.app_state.two_fa_code_store.read().await.get_code(&email).await.unwrap();
+
+    (login_attempt_id, code.as_ref().to_string())
+}
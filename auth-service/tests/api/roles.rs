@@ -0,0 +1,202 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::user::UserId;
+use auth_service::domain::role::{Role, UserRoles, ADMIN_ROLE, MANAGE_ROLES_PERMISSION};
+use auth_service::routes::VerifyTokenResponse;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup(app: &TestApp, email: &str) -> String {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = Email::parse(email.to_owned()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    user.id.to_string()
+}
+
+// Logs in and returns the JWT of the new session. The cookie jar always holds
+// the most recent login.
+async fn login(app: &TestApp, email: &str) -> String {
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .expect("No auth cookie found");
+    token
+}
+
+async fn grant_role(app: &TestApp, user_id: &str, role: &str) {
+    let user_id = UserId::parse(user_id).unwrap();
+    let role = Role::parse(role.to_owned()).unwrap();
+    app.app_state.role_store.write().await.grant_role(&user_id, &role).await.unwrap();
+}
+
+// Signs up an admin and logs them in, so the cookie jar holds their token
+async fn login_as_admin(app: &TestApp) {
+    let email = get_random_email();
+    let user_id = signup(app, &email).await;
+    grant_role(app, &user_id, ADMIN_ROLE).await;
+    login(app, &email).await;
+}
+
+async fn define_support_role(app: &TestApp) {
+    let role = Role::parse("support".to_owned()).unwrap();
+    app.app_state
+        .role_store
+        .write()
+        .await
+        .define_role(&role, &["tickets:read".to_owned()])
+        .await
+        .unwrap();
+}
+
+async fn verify_token(app: &TestApp, token: &str) -> VerifyTokenResponse {
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse")
+}
+
+#[tokio::test]
+async fn should_return_403_without_permission() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    assert_eq!(app.get_user_roles(&user_id).await.status().as_u16(), 400);
+
+    login(&app, &email).await;
+    assert_eq!(app.get_user_roles(&user_id).await.status().as_u16(), 403);
+    let response = app.post_user_role(&user_id, &serde_json::json!({ "role": ADMIN_ROLE })).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.delete_user_role(&user_id, ADMIN_ROLE).await.status().as_u16(), 403);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn granted_roles_are_embedded_in_tokens() {
+    let mut app = TestApp::new().await;
+    define_support_role(&app).await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    login_as_admin(&app).await;
+
+    let response = app.post_user_role(&user_id, &serde_json::json!({ "role": "support" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let roles = response.json::<UserRoles>().await.unwrap();
+    assert_eq!(roles.roles, vec!["support"]);
+    assert_eq!(roles.permissions, vec!["tickets:read"]);
+
+    // Granting a role twice changes nothing
+    let response = app.post_user_role(&user_id, &serde_json::json!({ "role": "support" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.get_user_roles(&user_id).await;
+    assert_eq!(response.json::<UserRoles>().await.unwrap(), roles);
+
+    let token = login(&app, &email).await;
+    let verified = verify_token(&app, &token).await;
+    assert_eq!(verified.user_id, user_id);
+    assert_eq!(verified.roles, vec!["support"]);
+    assert_eq!(verified.permissions, vec!["tickets:read"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn users_without_roles_get_empty_claims() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+    let token = login(&app, &email).await;
+
+    let verified = verify_token(&app, &token).await;
+    assert!(verified.roles.is_empty());
+    assert!(verified.permissions.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn revoking_a_role_invalidates_tokens_carrying_it() {
+    let mut app = TestApp::new().await;
+    define_support_role(&app).await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    grant_role(&app, &user_id, "support").await;
+    let token = login(&app, &email).await;
+    assert_eq!(verify_token(&app, &token).await.roles, vec!["support"]);
+
+    login_as_admin(&app).await;
+    let response = app.delete_user_role(&user_id, "support").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.json::<UserRoles>().await.unwrap().roles.is_empty());
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let token = login(&app, &email).await;
+    assert!(verify_token(&app, &token).await.permissions.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_users_and_roles() {
+    let mut app = TestApp::new().await;
+
+    let user_id = signup(&app, &get_random_email()).await;
+    login_as_admin(&app).await;
+
+    let unknown_user = uuid::Uuid::new_v4().to_string();
+    assert_eq!(app.get_user_roles(&unknown_user).await.status().as_u16(), 404);
+    assert_eq!(app.get_user_roles("not-a-user-id").await.status().as_u16(), 404);
+
+    let response = app.post_user_role(&user_id, &serde_json::json!({ "role": "unknown" })).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = app.post_user_role(&user_id, &serde_json::json!({ "role": "Not A Role" })).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    // The user was never granted the role
+    assert_eq!(app.delete_user_role(&user_id, ADMIN_ROLE).await.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn admins_can_manage_roles() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    login_as_admin(&app).await;
+
+    let response = app.post_user_role(&user_id, &serde_json::json!({ "role": ADMIN_ROLE })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = login(&app, &email).await;
+    let verified = verify_token(&app, &token).await;
+    assert_eq!(verified.roles, vec![ADMIN_ROLE]);
+    assert_eq!(verified.permissions, vec![MANAGE_ROLES_PERMISSION]);
+
+    // The new admin can now manage roles themselves
+    assert_eq!(app.get_user_roles(&user_id).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,205 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::routes::SessionsResponse;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME};
+
+fn get_cookie(response: &reqwest::Response, name: &str) -> Option<String> {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .map(|cookie| cookie.value().to_owned())
+}
+
+// Logs in and returns the JWT of the new session. The cookie jar always holds
+// the most recent session.
+async fn login(app: &TestApp, email: &str) -> String {
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    get_cookie(&response, JWT_COOKIE_NAME).expect("No auth cookie found")
+}
+
+async fn signup(app: &TestApp, email: &str) {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn get_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+    assert_eq!(app.post_logout_all().await.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_logged_in_user() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    // Sessions of other users are not listed
+    let other_email = get_random_email();
+    signup(&app, &other_email).await;
+    login(&app, &other_email).await;
+    login(&app, &random_email).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+    assert!(sessions[2].current);
+    assert!(sessions.iter().all(|session| session.ip_address.as_deref() == Some("127.0.0.1")));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_single_session() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let other_token = login(&app, &random_email).await;
+    let current_token = login(&app, &random_email).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    let other_session = sessions.iter().find(|session| !session.current).expect("No other session found");
+
+    let response = app.delete_session(&other_session.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &other_token).await, 401);
+    assert_eq!(verify_token_status(&app, &current_token).await, 200);
+    assert_eq!(get_sessions(&app).await.sessions.len(), 1);
+
+    // A revoked session can't be revoked again
+    let response = app.delete_session(&other_session.id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_log_out_when_revoking_current_session() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let token = login(&app, &random_email).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+    let response = app.delete_session(&sessions[0].id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    assert_eq!(app.post_token_refresh().await.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_or_foreign_session() {
+    let mut app = TestApp::new().await;
+
+    let victim_email = get_random_email();
+    signup(&app, &victim_email).await;
+    let victim_token = login(&app, &victim_email).await;
+    let victim_session = get_sessions(&app).await.sessions.remove(0);
+
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    let response = app.delete_session(&victim_session.id).await;
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(verify_token_status(&app, &victim_token).await, 200);
+
+    let response = app.delete_session("not-a-session").await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_every_session_on_logout_all() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    let first_token = login(&app, &random_email).await;
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    let second_token = get_cookie(&response, JWT_COOKIE_NAME).expect("No auth cookie found");
+    let refresh_token = get_cookie(&response, REFRESH_TOKEN_COOKIE_NAME).expect("No refresh token cookie found");
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &first_token).await, 401);
+    assert_eq!(verify_token_status(&app, &second_token).await, 401);
+
+    // Refresh tokens of the revoked sessions can't start them again
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", REFRESH_TOKEN_COOKIE_NAME, refresh_token),
+        &reqwest::Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    assert_eq!(app.post_token_refresh().await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_session_across_token_refresh() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+    let session_id = get_sessions(&app).await.sessions.remove(0).id;
+
+    let response = app.post_token_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = get_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, session_id);
+    assert!(sessions[0].current);
+
+    app.clean_up().await;
+}
@@ -1,5 +1,6 @@
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::{routes::SignupResponse, ErrorResponse};
+use auth_service::domain::recovery_code::RECOVERY_CODE_COUNT;
 
 // #[tokio::test]
 // async fn signup_returns_200() {
@@ -58,18 +59,15 @@ async fn should_return_201_if_valid_input() {
 
     assert_eq!(response.status().as_u16(), 201);
 
-    let expected_response = SignupResponse {
-        message: "User created successfully!".to_owned(),
-    };
+    let body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
 
     // Assert that we are getting the correct response body!
-    assert_eq!(
-        response
-            .json::<SignupResponse>()
-            .await
-            .expect("Could not deserialize response body to UserBody"),
-        expected_response
-    );
+    assert_eq!(body.message, "User created successfully!");
+    // Enabling 2FA at signup hands out recovery codes
+    assert_eq!(body.recovery_codes.map(|codes| codes.len()), Some(RECOVERY_CODE_COUNT));
     
     app.clean_up().await;
 }
@@ -135,6 +133,8 @@ async fn should_return_409_if_email_already_exists() {
         "requires2FA": true
     })).await;
 
+    assert_eq!(response.status().as_u16(), 201);
+
     let response2 = app.signup(&serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -0,0 +1,168 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::recovery_code::RECOVERY_CODE_COUNT;
+use auth_service::routes::{TotpConfirmResponse, TotpEnrollResponse, TwoFactorAuthResponse};
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+fn current_code(secret: &str, email: &str) -> String {
+    totp(secret, email).generate_current().unwrap()
+}
+
+// Each code is only accepted once, so a second login needs the code of the next
+// 30 second step, which is still within the allowed clock skew
+fn next_code(secret: &str, email: &str) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    totp(secret, email).generate(now + 30)
+}
+
+fn totp(secret: &str, email: &str) -> TOTP {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_owned()).to_bytes().unwrap(),
+        None,
+        email.to_owned(),
+    )
+    .unwrap()
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_enrolling_without_jwt_cookie() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_otpauth_uri_on_enroll() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<TotpEnrollResponse>()
+        .await
+        .expect("Could not deserialize response body to TotpEnrollResponse");
+    assert!(body.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(body.otpauth_uri.contains(&body.secret));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirmed_with_incorrect_code() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let body = app.post_totp_enroll().await.json::<TotpEnrollResponse>().await.unwrap();
+    let code = current_code(&body.secret, &random_email);
+    let wrong_code = if code == "123456" { "654321" } else { "123456" };
+
+    let response = app.post_totp_confirm(&serde_json::json!({ "code": wrong_code })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_totp_code_on_login_once_confirmed() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let body = app.post_totp_enroll().await.json::<TotpEnrollResponse>().await.unwrap();
+
+    let response = app.post_totp_confirm(&serde_json::json!({
+        "code": current_code(&body.secret, &random_email)
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let confirm_body = response.json::<TotpConfirmResponse>().await.unwrap();
+    assert_eq!(confirm_body.recovery_codes.len(), RECOVERY_CODE_COUNT);
+
+    app.logout().await;
+
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": next_code(&body.secret, &random_email)
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_totp_code_reused() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let body = app.post_totp_enroll().await.json::<TotpEnrollResponse>().await.unwrap();
+    let confirm_code = current_code(&body.secret, &random_email);
+    let response = app.post_totp_confirm(&serde_json::json!({ "code": confirm_code })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.logout().await;
+
+    // Neither the code that confirmed the secret nor one used to log in can be used again
+    let code = next_code(&body.secret, &random_email);
+    for (code, status) in [(&confirm_code, 401), (&code, 200), (&code, 401)] {
+        let response = app.login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        })).await;
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .unwrap()
+            .login_attempt_id;
+
+        let response = app.post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code
+        })).await;
+        assert_eq!(response.status().as_u16(), status);
+        app.logout().await;
+    }
+
+    app.clean_up().await;
+}
@@ -1,6 +1,8 @@
-use crate::helpers::TestApp;
+use crate::helpers::{TestApp, get_random_email};
 use auth_service::routes::TwoFactorAuthResponse;
 use auth_service::domain::email::Email;
+use auth_service::domain::user::UserStatus;
+use auth_service::ErrorResponse;
 use auth_service::utils::constants::JWT_COOKIE_NAME;
 
 #[tokio::test]
@@ -39,8 +41,7 @@ async fn verify_2fa_returns_200() {
     })).await;
 
     assert_eq!(verify_response.status().as_u16(), 200);
-    let cookies = verify_response.headers().get("set-cookie").unwrap();
-    assert!(cookies.to_str().unwrap().contains(JWT_COOKIE_NAME));
+    assert!(verify_response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
     
     app.clean_up().await;
 }
@@ -124,7 +125,7 @@ async fn should_return_401_if_old_code() {
         "password": "password123"
     })).await;
     
-    let second_login_response = second_response.json::<TwoFactorAuthResponse>()
+    let _second_login_response = second_response.json::<TwoFactorAuthResponse>()
         .await
         .expect("Failed to parse response");
     
@@ -189,4 +190,124 @@ async fn should_return_401_if_same_code_twice() {
     assert_eq!(second_verify_response.status().as_u16(), 401);
     
     app.clean_up().await;
+}
+#[tokio::test]
+async fn should_return_410_after_too_many_incorrect_codes() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = signup_and_start_2fa_login(&app, &random_email).await;
+
+    for expected_status in [401, 401, 410] {
+        let response = app.post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "000000"
+        })).await;
+        assert_eq!(response.status().as_u16(), expected_status);
+    }
+
+    // The code has been thrown away, so even the right one no longer works
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging in again issues a new code
+    let (login_attempt_id, code) = start_2fa_login(&app, &random_email).await;
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_failed_attempts() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let (mut login_attempt_id, mut code) = signup_and_start_2fa_login(&app, &random_email).await;
+
+    // Failures add up across login attempts
+    for expected_status in [401, 401, 410, 401, 401] {
+        let response = app.post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "000000"
+        })).await;
+        assert_eq!(response.status().as_u16(), expected_status);
+
+        if expected_status == 410 {
+            (login_attempt_id, code) = start_2fa_login(&app, &random_email).await;
+        }
+    }
+
+    // Locked out, even with the right code
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("Retry-After"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_if_account_locked_during_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = signup_and_start_2fa_login(&app, &random_email).await;
+
+    let email = Email::parse(random_email.clone()).unwrap();
+    let user_id = app.app_state.user_store.read().await.get_user(&email).await.unwrap().id;
+    app.app_state.user_store.write().await.set_status(&user_id, UserStatus::Locked, None).await.unwrap();
+
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code
+    })).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(!response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Account is locked");
+
+    app.clean_up().await;
+}
+
+async fn signup_and_start_2fa_login(app: &TestApp, email: &str) -> (String, String) {
+    app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    })).await;
+
+    start_2fa_login(app, email).await
+}
+
+// Logs in and returns the login attempt ID and the 2FA code that was sent
+async fn start_2fa_login(app: &TestApp, email: &str) -> (String, String) {
+    let login_response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    let login_attempt_id = login_response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Failed to parse response")
+        .login_attempt_id;
+
+    let email = Email::parse(email.to_string()).unwrap();
+    let (_, code) = app.app_state.two_fa_code_store.read().await.get_code(&email).await.unwrap();
+
+    (login_attempt_id, code.as_ref().to_string())
 }
\ No newline at end of file
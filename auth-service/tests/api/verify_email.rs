@@ -0,0 +1,85 @@
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::utils::auth::generate_email_verification_token;
+
+#[tokio::test]
+async fn should_return_200_and_verify_email_if_valid_token() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let response = app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = Email::parse(random_email).unwrap();
+    assert!(!app.app_state.user_store.read().await.get_user(&email).await.unwrap().email_verified);
+
+    let token = generate_email_verification_token(&email).unwrap();
+    let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(app.app_state.user_store.read().await.get_user(&email).await.unwrap().email_verified);
+
+    // Tokens are single-use
+    let response = app.post_verify_email(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_verify_email(&serde_json::json!({ "token": "invalid" })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_verify_email(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_if_resent_too_soon() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    // Signup has just sent a verification email
+    let response = app.post_resend_verification_email(&serde_json::json!({
+        "email": random_email
+    })).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_when_resending_for_unknown_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_resend_verification_email(&serde_json::json!({
+        "email": get_random_email()
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
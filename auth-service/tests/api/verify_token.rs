@@ -1,4 +1,5 @@
-use crate::helpers::TestApp;
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
 
 #[tokio::test]
 async fn verify_token_returns_200() {
@@ -60,5 +61,37 @@ async fn should_return_401_if_invalid_token() {
 
     assert_eq!(response.status().as_u16(), 401);
     
+    app.clean_up().await;
+}
+#[tokio::test]
+async fn should_return_401_if_token_version_is_stale() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    app.signup(&serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+
+    let login_response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    let jwt_token = login_response
+        .cookies()
+        .find(|cookie| cookie.name() == auth_service::utils::constants::JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    // Bumping the version invalidates the token without it ever being banned
+    let email = Email::parse(random_email).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    app.app_state.token_version_store.write().await.bump_version(&user.id).await.unwrap();
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": jwt_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
     app.clean_up().await;
 }
\ No newline at end of file
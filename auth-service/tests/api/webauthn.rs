@@ -0,0 +1,356 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+use sha2::{Digest, Sha256};
+
+use crate::helpers::{TestApp, get_random_email};
+use auth_service::domain::email::Email;
+use auth_service::domain::webauthn::WebauthnCredential;
+use auth_service::routes::{
+    AuthenticationOptionsResponse, RegisterPasskeyResponse, RegistrationOptionsResponse, TwoFactorAuthResponse,
+};
+use auth_service::utils::cbor::Value;
+use auth_service::utils::constants::{JWT_COOKIE_NAME, WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID};
+
+// Stands in for a security key or platform authenticator, with a single ES256 credential
+struct SoftwareAuthenticator {
+    key_pair: EcdsaKeyPair,
+    credential_id: Vec<u8>,
+    sign_count: u32,
+    // Whether it reports having checked the user's PIN or biometric
+    user_verified: bool,
+    rng: SystemRandom,
+}
+
+impl SoftwareAuthenticator {
+    fn new(user_verified: bool) -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        let credential_id = uuid::Uuid::new_v4().as_bytes().to_vec();
+
+        Self { key_pair, credential_id, sign_count: 0, user_verified, rng }
+    }
+
+    fn id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+
+    fn flags(&self, attested: bool) -> u8 {
+        let mut flags = 0x01;
+        if self.user_verified {
+            flags |= 0x04;
+        }
+        if attested {
+            flags |= 0x40;
+        }
+        flags
+    }
+
+    fn authenticator_data(&self, flags: u8) -> Vec<u8> {
+        let mut data = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&self.sign_count.to_be_bytes());
+        data
+    }
+
+    // The body a browser would send to finish registration after `navigator.credentials.create()`
+    fn create(&self, challenge: &str, origin: &str) -> serde_json::Value {
+        let public_key = self.key_pair.public_key().as_ref();
+        let cose_key = Value::Map(vec![
+            (Value::Integer(1), Value::Integer(2)),
+            (Value::Integer(3), Value::Integer(-7)),
+            (Value::Integer(-1), Value::Integer(1)),
+            (Value::Integer(-2), Value::Bytes(public_key[1..33].to_vec())),
+            (Value::Integer(-3), Value::Bytes(public_key[33..].to_vec())),
+        ]);
+
+        let mut authenticator_data = self.authenticator_data(self.flags(true));
+        authenticator_data.extend_from_slice(&[0; 16]);
+        authenticator_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        authenticator_data.extend_from_slice(&self.credential_id);
+        authenticator_data.extend_from_slice(&cose_key.encode());
+
+        let attestation_object = Value::Map(vec![
+            (Value::Text("fmt".to_string()), Value::Text("none".to_string())),
+            (Value::Text("attStmt".to_string()), Value::Map(vec![])),
+            (Value::Text("authData".to_string()), Value::Bytes(authenticator_data)),
+        ]);
+
+        serde_json::json!({
+            "id": self.id(),
+            "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data("webauthn.create", challenge, origin)),
+            "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object.encode()),
+        })
+    }
+
+    // The assertion a browser would return from `navigator.credentials.get()`
+    fn get(&mut self, challenge: &str) -> serde_json::Value {
+        self.sign_count += 1;
+        let authenticator_data = self.authenticator_data(self.flags(false));
+        let client_data = client_data("webauthn.get", challenge, &WEBAUTHN_ORIGIN);
+
+        let mut message = authenticator_data.clone();
+        message.extend_from_slice(&Sha256::digest(&client_data));
+        let signature = self.key_pair.sign(&self.rng, &message).unwrap();
+
+        serde_json::json!({
+            "id": self.id(),
+            "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+            "authenticatorData": URL_SAFE_NO_PAD.encode(authenticator_data),
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        })
+    }
+}
+
+fn client_data(kind: &str, challenge: &str, origin: &str) -> Vec<u8> {
+    serde_json::json!({ "type": kind, "challenge": challenge, "origin": origin })
+        .to_string()
+        .into_bytes()
+}
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": requires_2fa
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// Registers the authenticator's credential for the user without going through the routes
+async fn add_passkey(app: &TestApp, email: &str, authenticator: &SoftwareAuthenticator) {
+    let email = Email::parse(email.to_string()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    let credential = WebauthnCredential::new(
+        authenticator.id(),
+        user.id,
+        authenticator.key_pair.public_key().as_ref().to_vec(),
+        0,
+    );
+    app.app_state.webauthn_credential_store.write().await.add_credential(&credential).await.unwrap();
+}
+
+async fn start_login(app: &TestApp, email: Option<&str>) -> AuthenticationOptionsResponse {
+    let response = app.post_webauthn_login_start(&serde_json::json!({ "email": email })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<AuthenticationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuthenticationOptionsResponse")
+}
+
+async fn start_registration(app: &TestApp) -> RegistrationOptionsResponse {
+    let response = app.post_webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<RegistrationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to RegistrationOptionsResponse")
+}
+
+#[tokio::test]
+async fn should_register_passkey_and_log_in_with_it() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email, false).await;
+    let response = app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let mut authenticator = SoftwareAuthenticator::new(true);
+    let options = start_registration(&app).await.public_key;
+    assert_eq!(options.rp.id, *WEBAUTHN_RP_ID);
+    assert_eq!(options.user.name, random_email);
+    assert!(options.exclude_credentials.is_empty());
+
+    let response = app
+        .post_webauthn_register_finish(&authenticator.create(&options.challenge, &WEBAUTHN_ORIGIN))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let registered = response
+        .json::<RegisterPasskeyResponse>()
+        .await
+        .expect("Could not deserialize response body to RegisterPasskeyResponse");
+    assert_eq!(registered.id, authenticator.id());
+
+    // The registered passkey is excluded from further registrations
+    let options = start_registration(&app).await.public_key;
+    assert_eq!(options.exclude_credentials[0].id, authenticator.id());
+
+    let options = start_login(&app, Some(&random_email)).await.public_key;
+    assert_eq!(options.allow_credentials.len(), 1);
+    assert_eq!(options.allow_credentials[0].id, authenticator.id());
+
+    let assertion = authenticator.get(&options.challenge);
+    let response = app.post_webauthn_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty()));
+
+    // Each challenge can only be answered once
+    let response = app.post_webauthn_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_registration_from_another_origin() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email, false).await;
+    app.login(&serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    })).await;
+
+    let authenticator = SoftwareAuthenticator::new(false);
+    let options = start_registration(&app).await.public_key;
+    let response = app
+        .post_webauthn_register_finish(&authenticator.create(&options.challenge, "https://evil.example"))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    // A login challenge can't be used to register
+    let options = start_login(&app, None).await.public_key;
+    let response = app
+        .post_webauthn_register_finish(&authenticator.create(&options.challenge, &WEBAUTHN_ORIGIN))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_or_cloned_passkey() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email, false).await;
+
+    let mut unknown = SoftwareAuthenticator::new(false);
+    let options = start_login(&app, None).await.public_key;
+    let response = app.post_webauthn_login_finish(&unknown.get(&options.challenge)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let mut authenticator = SoftwareAuthenticator::new(true);
+    add_passkey(&app, &random_email, &authenticator).await;
+    authenticator.sign_count = 10;
+    let options = start_login(&app, None).await.public_key;
+    let response = app.post_webauthn_login_finish(&authenticator.get(&options.challenge)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A copy of the authenticator is behind on its signature counter
+    authenticator.sign_count = 5;
+    let options = start_login(&app, None).await.public_key;
+    let response = app.post_webauthn_login_finish(&authenticator.get(&options.challenge)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_reveal_unknown_emails() {
+    let mut app = TestApp::new().await;
+
+    let options = start_login(&app, Some(&get_random_email())).await.public_key;
+    assert!(options.allow_credentials.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_user_verification_for_passkey_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email, true).await;
+
+    let mut authenticator = SoftwareAuthenticator::new(false);
+    add_passkey(&app, &random_email, &authenticator).await;
+    let options = start_login(&app, Some(&random_email)).await.public_key;
+    assert_eq!(options.user_verification, "required");
+
+    let assertion = authenticator.get(&options.challenge);
+    let response = app.post_webauthn_login_finish(&assertion).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    // The same passkey can't then pass as the second factor of its own login
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+        "passkey": assertion
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME));
+
+    authenticator.user_verified = true;
+    let options = start_login(&app, Some(&random_email)).await.public_key;
+    let response = app.post_webauthn_login_finish(&authenticator.get(&options.challenge)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_passkey_as_second_factor() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    signup(&app, &random_email, true).await;
+    let mut authenticator = SoftwareAuthenticator::new(false);
+    add_passkey(&app, &random_email, &authenticator).await;
+
+    // Someone else's passkey doesn't count
+    let other_email = get_random_email();
+    signup(&app, &other_email, false).await;
+    let mut other = SoftwareAuthenticator::new(false);
+    add_passkey(&app, &other_email, &other).await;
+
+    let login_attempt_id = app
+        .login(&serde_json::json!({
+            "email": random_email,
+            "password": "password123"
+        }))
+        .await
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let options = start_login(&app, None).await.public_key;
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "passkey": other.get(&options.challenge)
+    })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let options = start_login(&app, Some(&random_email)).await.public_key;
+    let response = app.post_verify_2fa(&serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "passkey": authenticator.get(&options.challenge)
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.cookies().any(|cookie| cookie.name() == JWT_COOKIE_NAME && !cookie.value().is_empty()));
+
+    app.clean_up().await;
+}
@@ -0,0 +1,408 @@
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use ring::hmac;
+
+use crate::helpers::{get_random_email, TestApp};
+use auth_service::domain::email::Email;
+use auth_service::domain::role::{Role, ADMIN_ROLE};
+use auth_service::domain::user::UserId;
+use auth_service::get_postgres_pool;
+use auth_service::routes::{AuditLogResponse, CreateWebhookResponse, WebhookDeliveriesResponse, WebhooksResponse};
+use auth_service::services::webhook_delivery::deliver_due_webhooks;
+use auth_service::utils::constants::{DATABASE_URL, WEBHOOK_MAX_ATTEMPTS};
+
+// A delivery as the receiver got it
+#[derive(Clone, Debug)]
+struct ReceivedDelivery {
+    headers: HeaderMap,
+    body: String,
+}
+
+#[derive(Clone)]
+struct FakeReceiverState {
+    status: Arc<Mutex<StatusCode>>,
+    received: Arc<Mutex<Vec<ReceivedDelivery>>>,
+}
+
+// Stand-in for a service that subscribed to webhooks. It records every delivery
+// and answers with whatever status the test sets.
+struct FakeReceiver {
+    url: String,
+    state: FakeReceiverState,
+}
+
+impl FakeReceiver {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+
+        let state = FakeReceiverState {
+            status: Arc::new(Mutex::new(StatusCode::NO_CONTENT)),
+            received: Arc::new(Mutex::new(Vec::new())),
+        };
+        let router = Router::new().route("/hooks", post(receive)).with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Self { url, state }
+    }
+
+    fn respond_with(&self, status: StatusCode) {
+        *self.state.status.lock().unwrap() = status;
+    }
+
+    fn received(&self) -> Vec<ReceivedDelivery> {
+        self.state.received.lock().unwrap().clone()
+    }
+}
+
+async fn receive(State(state): State<FakeReceiverState>, headers: HeaderMap, body: String) -> StatusCode {
+    state.received.lock().unwrap().push(ReceivedDelivery { headers, body });
+    *state.status.lock().unwrap()
+}
+
+async fn signup(app: &TestApp, email: &str) -> String {
+    let response = app.signup(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = Email::parse(email.to_owned()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    user.id.to_string()
+}
+
+async fn login(app: &TestApp, email: &str) {
+    let response = app.login(&serde_json::json!({
+        "email": email,
+        "password": "password123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+// Signs up an admin and logs them in, so the cookie jar holds their token.
+// Returns the admin's email.
+async fn login_as_admin(app: &TestApp) -> String {
+    let email = get_random_email();
+    let user_id = UserId::parse(&signup(app, &email).await).unwrap();
+    let admin = Role::parse(ADMIN_ROLE.to_owned()).unwrap();
+    app.app_state.role_store.write().await.grant_role(&user_id, &admin).await.unwrap();
+    login(app, &email).await;
+    email
+}
+
+// The audit log records admins by user ID
+async fn user_id_of(app: &TestApp, email: &str) -> String {
+    let email = Email::parse(email.to_owned()).unwrap();
+    let user = app.app_state.user_store.read().await.get_user(&email).await.unwrap();
+    user.id.to_string()
+}
+
+async fn create_webhook(app: &TestApp, url: &str, events: &[&str]) -> CreateWebhookResponse {
+    let response = app.post_webhook(&serde_json::json!({ "url": url, "events": events })).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<CreateWebhookResponse>()
+        .await
+        .expect("Could not deserialize response body to CreateWebhookResponse")
+}
+
+async fn get_deliveries(app: &TestApp, id: &str) -> WebhookDeliveriesResponse {
+    let response = app.get_webhook_deliveries(id, &serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<WebhookDeliveriesResponse>()
+        .await
+        .expect("Could not deserialize response body to WebhookDeliveriesResponse")
+}
+
+// Checks the delivery's signature the way a receiver would
+fn is_signed_with(delivery: &ReceivedDelivery, secret: &str) -> bool {
+    let timestamp = delivery.headers["webhook-timestamp"].to_str().unwrap();
+    let signature = delivery.headers["webhook-signature"].to_str().unwrap().strip_prefix("v1=").unwrap();
+    let signature: Vec<u8> = (0..signature.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&signature[i..i + 2], 16).unwrap())
+        .collect();
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    hmac::verify(&key, format!("{}.{}", timestamp, delivery.body).as_bytes(), &signature).is_ok()
+}
+
+#[tokio::test]
+async fn should_deliver_signed_events_for_signup_login_and_logout() {
+    let mut app = TestApp::new().await;
+    let receiver = FakeReceiver::start().await;
+
+    login_as_admin(&app).await;
+    let webhook = create_webhook(&app, &receiver.url, &["user.signed_up", "user.logged_in", "user.logged_out"]).await;
+    assert!(webhook.secret.starts_with("whsec_"));
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    login(&app, &email).await;
+    assert_eq!(app.logout().await.status().as_u16(), 200);
+
+    // Nothing is sent until the worker runs
+    assert!(receiver.received().is_empty());
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 3);
+
+    let received = receiver.received();
+    let events: Vec<serde_json::Value> = received.iter().map(|delivery| serde_json::from_str(&delivery.body).unwrap()).collect();
+    let mut types: Vec<&str> = events.iter().map(|event| event["type"].as_str().unwrap()).collect();
+    types.sort();
+    assert_eq!(types, vec!["user.logged_in", "user.logged_out", "user.signed_up"]);
+    for (delivery, event) in received.iter().zip(&events) {
+        assert!(is_signed_with(delivery, &webhook.secret));
+        assert_eq!(delivery.headers["content-type"], "application/json");
+        assert_eq!(delivery.headers["webhook-id"].to_str().unwrap(), event["id"].as_str().unwrap());
+        assert_eq!(event["data"]["userId"], user_id.as_str());
+        assert_eq!(event["data"]["email"], email.as_str());
+    }
+
+    // Nothing is sent twice
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 0);
+    assert_eq!(receiver.received().len(), 3);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_deliver_subscribed_events() {
+    let mut app = TestApp::new().await;
+    let signups = FakeReceiver::start().await;
+    let logins = FakeReceiver::start().await;
+
+    login_as_admin(&app).await;
+    create_webhook(&app, &signups.url, &["user.signed_up"]).await;
+    create_webhook(&app, &logins.url, &["user.logged_in"]).await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login(&app, &email).await;
+    deliver_due_webhooks(&app.app_state).await.unwrap();
+
+    let types = |receiver: &FakeReceiver| -> Vec<String> {
+        receiver
+            .received()
+            .iter()
+            .map(|delivery| serde_json::from_str::<serde_json::Value>(&delivery.body).unwrap()["type"].as_str().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(types(&signups), vec!["user.signed_up"]);
+    assert_eq!(types(&logins), vec!["user.logged_in"]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_deliver_events_for_account_changes() {
+    let mut app = TestApp::new().await;
+    let receiver = FakeReceiver::start().await;
+
+    login_as_admin(&app).await;
+    create_webhook(&app, &receiver.url, &["user.password_changed", "user.deleted", "user.restored", "user.status_changed"]).await;
+
+    let email = get_random_email();
+    let user_id = signup(&app, &email).await;
+    login(&app, &email).await;
+    let response = app.post_change_password(&serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_delete_account(&serde_json::json!({ "password": "newpassword123" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app.post_restore_account(&serde_json::json!({
+        "email": email,
+        "password": "newpassword123"
+    })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    login_as_admin(&app).await;
+    let response = app.post_admin_user_status(&user_id, &serde_json::json!({ "status": "suspended" })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 4);
+    let events: Vec<serde_json::Value> = receiver
+        .received()
+        .iter()
+        .map(|delivery| serde_json::from_str(&delivery.body).unwrap())
+        .collect();
+    let mut types: Vec<&str> = events.iter().map(|event| event["type"].as_str().unwrap()).collect();
+    types.sort();
+    assert_eq!(types, vec!["user.deleted", "user.password_changed", "user.restored", "user.status_changed"]);
+    for event in &events {
+        assert_eq!(event["data"]["userId"], user_id.as_str());
+    }
+    let status_changed = events.iter().find(|event| event["type"] == "user.status_changed").unwrap();
+    assert_eq!(status_changed["data"]["status"], "suspended");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_retry_failed_deliveries_and_dead_letter_them() {
+    let mut app = TestApp::new().await;
+    let receiver = FakeReceiver::start().await;
+    receiver.respond_with(StatusCode::INTERNAL_SERVER_ERROR);
+
+    let admin_email = login_as_admin(&app).await;
+    let webhook = create_webhook(&app, &receiver.url, &["user.logged_in"]).await;
+    login(&app, &admin_email).await;
+
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 1);
+    let deliveries = get_deliveries(&app, &webhook.webhook.id).await;
+    assert_eq!(deliveries.total, 1);
+    let delivery = &deliveries.deliveries[0];
+    assert_eq!(delivery.status, "pending");
+    assert_eq!(delivery.attempts, 1);
+    assert_eq!(delivery.last_response_status, Some(500));
+    assert!(delivery.next_attempt_at.is_some());
+
+    // The retry waits for the backoff
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 0);
+
+    // Skip ahead to the last attempt
+    let pg_pool = get_postgres_pool(&format!("{}/{}", DATABASE_URL.as_str(), app.db_name))
+        .await
+        .unwrap();
+    sqlx::query("UPDATE webhook_deliveries SET attempts = $1, next_attempt_at = NOW()")
+        .bind(WEBHOOK_MAX_ATTEMPTS - 1)
+        .execute(&pg_pool)
+        .await
+        .unwrap();
+    pg_pool.close().await;
+
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 1);
+    let delivery = &get_deliveries(&app, &webhook.webhook.id).await.deliveries[0];
+    assert_eq!(delivery.status, "dead_lettered");
+    assert_eq!(delivery.attempts, WEBHOOK_MAX_ATTEMPTS);
+    assert_eq!(delivery.next_attempt_at, None);
+
+    // Dead-lettered deliveries aren't tried again, even once the receiver recovers
+    receiver.respond_with(StatusCode::OK);
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 0);
+    assert_eq!(receiver.received().len(), 2);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_delivery_history() {
+    let mut app = TestApp::new().await;
+    let receiver = FakeReceiver::start().await;
+
+    let admin_email = login_as_admin(&app).await;
+    let webhook = create_webhook(&app, &receiver.url, &["user.logged_in"]).await;
+    for _ in 0..3 {
+        login(&app, &admin_email).await;
+    }
+    deliver_due_webhooks(&app.app_state).await.unwrap();
+
+    let deliveries = get_deliveries(&app, &webhook.webhook.id).await;
+    assert_eq!(deliveries.total, 3);
+    assert!(deliveries.deliveries.iter().all(|delivery| delivery.status == "succeeded"));
+    assert!(deliveries.deliveries.iter().all(|delivery| delivery.last_response_status == Some(204)));
+    assert!(deliveries.deliveries.iter().all(|delivery| delivery.last_attempt_at.is_some()));
+
+    let response = app.get_webhook_deliveries(&webhook.webhook.id, &serde_json::json!({ "limit": 2, "offset": 2 })).await;
+    let page = response.json::<WebhookDeliveriesResponse>().await.unwrap();
+    assert_eq!((page.limit, page.offset, page.total), (2, 2, 3));
+    assert_eq!(page.deliveries.len(), 1);
+
+    let unknown = uuid::Uuid::new_v4().to_string();
+    assert_eq!(app.get_webhook_deliveries(&unknown, &serde_json::json!({})).await.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_and_delete_webhooks() {
+    let mut app = TestApp::new().await;
+    let receiver = FakeReceiver::start().await;
+
+    let admin_email = login_as_admin(&app).await;
+    let first = create_webhook(&app, &receiver.url, &["user.logged_in", "user.logged_in"]).await;
+    let second = create_webhook(&app, &receiver.url, &["user.logged_out"]).await;
+    assert_eq!(first.webhook.events, vec!["user.logged_in"]);
+
+    let response = app.get_webhooks().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let webhooks = response.json::<WebhooksResponse>().await.unwrap().webhooks;
+    let ids: Vec<&str> = webhooks.iter().map(|webhook| webhook.id.as_str()).collect();
+    assert_eq!(ids, vec![first.webhook.id.as_str(), second.webhook.id.as_str()]);
+    // The secret is only shown when the webhook is created
+    let body = app.get_webhooks().await.json::<serde_json::Value>().await.unwrap();
+    assert!(body["webhooks"][0].get("secret").is_none());
+
+    // Pending deliveries go with the webhook
+    login(&app, &admin_email).await;
+    assert_eq!(app.delete_webhook(&first.webhook.id).await.status().as_u16(), 200);
+    assert_eq!(app.delete_webhook(&first.webhook.id).await.status().as_u16(), 404);
+    assert_eq!(app.delete_webhook("not-a-uuid").await.status().as_u16(), 404);
+    assert_eq!(deliver_due_webhooks(&app.app_state).await.unwrap(), 0);
+    assert!(receiver.received().is_empty());
+
+    let webhooks = app.get_webhooks().await.json::<WebhooksResponse>().await.unwrap().webhooks;
+    assert_eq!(webhooks.len(), 1);
+
+    // Both changes are in the audit log, with the admin who made them
+    let admin_id = user_id_of(&app, &admin_email).await;
+    let log = app.get_audit_log(&serde_json::json!({ "type": "webhook_created" })).await.json::<AuditLogResponse>().await.unwrap();
+    assert_eq!(log.total, 2);
+    assert!(log.events.iter().all(|event| event.actor.as_deref() == Some(admin_id.as_str())));
+    let log = app.get_audit_log(&serde_json::json!({ "type": "webhook_deleted" })).await.json::<AuditLogResponse>().await.unwrap();
+    assert_eq!(log.total, 1);
+    assert_eq!(log.events[0].actor.as_deref(), Some(admin_id.as_str()));
+    assert_eq!(log.events[0].details.as_deref(), Some(first.webhook.id.as_str()));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_422_for_invalid_webhooks() {
+    let mut app = TestApp::new().await;
+
+    login_as_admin(&app).await;
+
+    let requests = [
+        serde_json::json!({ "url": "not a url", "events": ["user.logged_in"] }),
+        serde_json::json!({ "url": "ftp://example.com/hooks", "events": ["user.logged_in"] }),
+        serde_json::json!({ "url": "https://example.com/hooks", "events": ["user.updated"] }),
+        serde_json::json!({ "url": "https://example.com/hooks", "events": [] }),
+        serde_json::json!({ "url": "https://example.com/hooks" }),
+    ];
+    for request in requests {
+        assert_eq!(app.post_webhook(&request).await.status().as_u16(), 422, "Failed for request: {:?}", request);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_non_admins() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login(&app, &email).await;
+
+    let request = serde_json::json!({ "url": "https://example.com/hooks", "events": ["user.logged_in"] });
+    assert_eq!(app.post_webhook(&request).await.status().as_u16(), 403);
+    assert_eq!(app.get_webhooks().await.status().as_u16(), 403);
+    let id = uuid::Uuid::new_v4().to_string();
+    assert_eq!(app.delete_webhook(&id).await.status().as_u16(), 403);
+    assert_eq!(app.get_webhook_deliveries(&id, &serde_json::json!({})).await.status().as_u16(), 403);
+
+    app.clean_up().await;
+}